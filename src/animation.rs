@@ -0,0 +1,475 @@
+//! Keyframe animation.
+//!
+//! An `Animation` changes camera, light and shape parameters over time.  Each
+//! animated parameter is a `Track` of keyframes.  `render_frames()` renders the
+//! animation as a sequence of numbered images.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{Camera, RenderOptions};
+use crate::color::Color;
+use crate::error::Error;
+use crate::matrix::Matrix;
+use crate::transform;
+use crate::tuple::Tuple;
+use crate::world::World;
+
+/// How a value changes between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Accelerates from the first keyframe and decelerates toward the next one.
+    Smooth,
+}
+
+impl Easing {
+    /// Maps linear progress `t` in `[0, 1]` between two keyframes to eased progress.
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Values that can be blended between keyframes.
+pub trait Interpolate: Clone {
+    /// Returns value between `self` (`t == 0`) and `o` (`t == 1`).
+    fn interpolate(&self, o: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, o: &Self, t: f64) -> Self {
+        self + (o - self) * t
+    }
+}
+
+impl Interpolate for Tuple {
+    fn interpolate(&self, o: &Self, t: f64) -> Self {
+        self + &(&(o - self) * t)
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(&self, o: &Self, t: f64) -> Self {
+        self.interpolated(o, t)
+    }
+}
+
+impl Interpolate for Matrix {
+    /// Rotations turn smoothly (see `transform::Decomposition::interpolated()`).
+    /// Non-affine or non-invertible matrices are interpolated element-wise.
+    fn interpolate(&self, o: &Self, t: f64) -> Self {
+        match (transform::decompose(self), transform::decompose(o)) {
+            (Ok(a), Ok(b)) => a.interpolated(&b, t).matrix(),
+            _ => self.interpolated(o, t),
+        }
+    }
+}
+
+/// A value at a given time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+
+    /// How the value changes from this keyframe to the next one.
+    pub easing: Easing,
+}
+
+/// A value changing over time.
+///
+/// The value is constant before the first and after the last keyframe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    /// Sorted by time.
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Track<T> {
+    /// Creates a track with a single keyframe at time 0.
+    pub fn constant(value: T) -> Track<T> {
+        Track {
+            keyframes: vec![Keyframe {
+                time: 0.0,
+                value,
+                easing: Easing::Linear,
+            }],
+        }
+    }
+
+    /// Creates a track with the given keyframe.
+    pub fn new(time: f64, value: T, easing: Easing) -> Track<T> {
+        Track {
+            keyframes: vec![Keyframe {
+                time,
+                value,
+                easing,
+            }],
+        }
+    }
+
+    /// Returns this track with an additional keyframe.
+    pub fn key(mut self, time: f64, value: T, easing: Easing) -> Track<T> {
+        let pos = self.keyframes.partition_point(|k| k.time <= time);
+        self.keyframes.insert(
+            pos,
+            Keyframe {
+                time,
+                value,
+                easing,
+            },
+        );
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    /// Returns the value at `time`.
+    pub fn value_at(&self, time: f64) -> T {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            self.keyframes[0].value.clone()
+        } else if next == self.keyframes.len() {
+            self.keyframes[next - 1].value.clone()
+        } else {
+            let k0 = &self.keyframes[next - 1];
+            let k1 = &self.keyframes[next];
+            let t = k0.easing.apply((time - k0.time) / (k1.time - k0.time));
+            k0.value.interpolate(&k1.value, t)
+        }
+    }
+}
+
+/// Animated parameters of `transform::view()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewTrack {
+    pub from: Track<Tuple>,
+    pub to: Track<Tuple>,
+    pub up: Track<Tuple>,
+}
+
+/// Animated parameters of a shape.  `None` parameters are left unchanged.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ShapeAnimation {
+    pub transform: Option<Track<Matrix>>,
+    pub color: Option<Track<Color>>,
+    pub ambient: Option<Track<f64>>,
+    pub diffuse: Option<Track<f64>>,
+    pub specular: Option<Track<f64>>,
+    pub shininess: Option<Track<f64>>,
+}
+
+/// Animated parameters of a world and its camera.  `None` parameters are left unchanged.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Animation {
+    /// Camera position and orientation.  Takes precedence over `camera_transform`.
+    pub camera_view: Option<ViewTrack>,
+    pub camera_transform: Option<Track<Matrix>>,
+    pub light_position: Option<Track<Tuple>>,
+
+    /// Animated shapes as (index in `World::objects`, parameters) pairs.  A
+    /// shape with a `transform` track cannot have a `motion`, which would
+    /// override it.
+    pub shapes: Vec<(usize, ShapeAnimation)>,
+}
+
+impl Animation {
+    /// Returns an error if this animation does not fit `world`: animated
+    /// shapes must exist, and shapes with a transformation track must not move.
+    pub fn validate(&self, world: &World) -> Result<(), Error> {
+        for (index, anim) in &self.shapes {
+            let shape = world.objects.get(*index).ok_or(Error::NoSuchShape {
+                index: *index,
+                count: world.objects.len(),
+            })?;
+            if anim.transform.is_some() && shape.motion.is_some() {
+                return Err(Error::InvalidShape {
+                    index: *index,
+                    kind: shape.object.name(),
+                    error: Box::new(Error::TransformOverriddenByMotion),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates `world` and `camera` to their state at `time`.  Fails, leaving
+    /// them unchanged, if this animation does not fit `world` (see
+    /// `validate()`).
+    pub fn apply(&self, world: &mut World, camera: &mut Camera, time: f64) -> Result<(), Error> {
+        self.validate(world)?;
+        if let Some(v) = &self.camera_view {
+            camera.set_transform(transform::view(
+                &v.from.value_at(time),
+                &v.to.value_at(time),
+                &v.up.value_at(time),
+            ));
+        } else if let Some(t) = &self.camera_transform {
+            camera.set_transform(t.value_at(time));
+        }
+
+        if let Some(p) = &self.light_position {
+            world.light.position = p.value_at(time);
+        }
+
+        for (index, anim) in &self.shapes {
+            let shape = &mut world.objects[*index];
+            if let Some(t) = &anim.transform {
+                shape.set_transform(t.value_at(time));
+            }
+            let m = &mut shape.material;
+            if let Some(t) = &anim.color {
+                m.color = t.value_at(time);
+            }
+            if let Some(t) = &anim.ambient {
+                m.ambient = t.value_at(time);
+            }
+            if let Some(t) = &anim.diffuse {
+                m.diffuse = t.value_at(time);
+            }
+            if let Some(t) = &anim.specular {
+                m.specular = t.value_at(time);
+            }
+            if let Some(t) = &anim.shininess {
+                m.shininess = t.value_at(time);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Times at which frames of an animation are rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    /// Time of the first frame.
+    pub start: f64,
+    pub frames_per_second: f64,
+    pub frame_count: usize,
+}
+
+impl Timeline {
+    /// Returns time of frame number `frame`.
+    pub fn time_of(&self, frame: usize) -> f64 {
+        self.start + frame as f64 / self.frames_per_second
+    }
+}
+
+/// Renders `animation` of `world` seen by `camera` at each frame of `timeline`.
+///
+/// Frames are written as raw PPM files named `frame_0000.ppm`, `frame_0001.ppm`...
+/// in directory `dir`, which is created if needed.  Returns paths of written files.
+/// On return, `world` and `camera` are in the state of the last frame.
+pub fn render_frames(
+    world: &mut World,
+    camera: &mut Camera,
+    animation: &Animation,
+    timeline: &Timeline,
+    options: &RenderOptions,
+    dir: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    animation
+        .validate(world)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::with_capacity(timeline.frame_count);
+    for frame in 0..timeline.frame_count {
+        animation
+            .apply(world, camera, timeline.time_of(frame))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let canvas = camera.render_with(world, options);
+        let path = dir.join(format!("frame_{:04}.ppm", frame));
+        canvas.to_raw_ppm(File::create(&path)?)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::shape::Motion;
+    use crate::tuple::ORIGIN;
+    use crate::world::test_util;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn track_is_constant_outside_keyframes() {
+        let t = Track::new(1.0, 10.0, Easing::Linear).key(2.0, 20.0, Easing::Linear);
+        assert_eq!(t.value_at(0.0), 10.0);
+        assert_eq!(t.value_at(3.0), 20.0);
+    }
+
+    #[test]
+    fn interpolating_linearly() {
+        let t = Track::new(0.0, 0.0, Easing::Linear)
+            .key(2.0, 10.0, Easing::Linear)
+            .key(1.0, 20.0, Easing::Linear);
+        assert_eq!(t.keyframes()[1].value, 20.0);
+        assert_eq!(t.value_at(0.5), 10.0);
+        assert_eq!(t.value_at(1.0), 20.0);
+        assert_eq!(t.value_at(1.25), 17.5);
+    }
+
+    #[test]
+    fn interpolating_smoothly() {
+        let t = Track::new(0.0, 0.0, Easing::Smooth).key(1.0, 1.0, Easing::Linear);
+        assert_eq!(t.value_at(0.5), 0.5);
+        assert!(t.value_at(0.1) < 0.1);
+        assert!(t.value_at(0.9) > 0.9);
+    }
+
+    #[test]
+    fn interpolating_points_and_colors() {
+        let p = Track::constant(ORIGIN).key(1.0, Tuple::new_point(2.0, 4.0, 0.0), Easing::Linear);
+        assert_eq!(p.value_at(0.5), Tuple::new_point(1.0, 2.0, 0.0));
+        let c = Track::constant(color::BLACK).key(1.0, color::WHITE, Easing::Linear);
+        assert_eq!(c.value_at(0.25), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn interpolating_rotations() {
+        let r = Track::constant(Matrix::new_4x4_identity()).key(
+            1.0,
+            transform::rotation_x(PI / 2.0),
+            Easing::Linear,
+        );
+        assert_eq!(r.value_at(0.5), transform::rotation_x(PI / 4.0));
+    }
+
+    fn turntable() -> Animation {
+        Animation {
+            camera_view: Some(ViewTrack {
+                from: Track::constant(Tuple::new_point(0.0, 0.0, -5.0)).key(
+                    1.0,
+                    Tuple::new_point(5.0, 0.0, 0.0),
+                    Easing::Linear,
+                ),
+                to: Track::constant(ORIGIN),
+                up: Track::constant(Tuple::new_vector(0.0, 1.0, 0.0)),
+            }),
+            light_position: Some(Track::constant(Tuple::new_point(0.0, 10.0, 0.0))),
+            shapes: vec![(
+                1,
+                ShapeAnimation {
+                    transform: Some(Track::constant(transform::scaling(0.5, 0.5, 0.5)).key(
+                        1.0,
+                        transform::scaling(0.7, 0.7, 0.7),
+                        Easing::Smooth,
+                    )),
+                    color: Some(Track::constant(color::RED)),
+                    diffuse: Some(Track::constant(0.2).key(1.0, 0.4, Easing::Linear)),
+                    ..ShapeAnimation::default()
+                },
+            )],
+            ..Animation::default()
+        }
+    }
+
+    #[test]
+    fn applying_animation() {
+        let mut w = test_util::default_world();
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        turntable().apply(&mut w, &mut c, 0.5).unwrap();
+        assert_eq!(
+            *c.transform(),
+            transform::view(
+                &Tuple::new_point(2.5, 0.0, -2.5),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0)
+            )
+        );
+        assert_eq!(w.light.position, Tuple::new_point(0.0, 10.0, 0.0));
+        assert_eq!(*w.objects[1].transform(), transform::scaling(0.6, 0.6, 0.6));
+        assert_eq!(w.objects[1].material.color, color::RED);
+        assert!((w.objects[1].material.diffuse - 0.3).abs() < 1e-9);
+        assert_eq!(w.objects[0], test_util::default_world().objects[0]);
+    }
+
+    #[test]
+    fn camera_transform_track_is_used_without_view_track() {
+        let mut w = test_util::default_world();
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        let a = Animation {
+            camera_transform: Some(Track::constant(transform::translation(0.0, 0.0, -5.0))),
+            ..Animation::default()
+        };
+        a.apply(&mut w, &mut c, 3.0).unwrap();
+        assert_eq!(*c.transform(), transform::translation(0.0, 0.0, -5.0));
+    }
+
+    #[test]
+    fn animating_missing_shape_fails() {
+        let mut w = test_util::default_world();
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        let mut a = turntable();
+        a.shapes[0].0 = 2;
+        assert_eq!(
+            a.apply(&mut w, &mut c, 0.5),
+            Err(Error::NoSuchShape { index: 2, count: 2 })
+        );
+        assert_eq!(*c.transform(), Matrix::new_4x4_identity());
+    }
+
+    #[test]
+    fn transform_track_cannot_animate_moving_shape() {
+        let mut w = test_util::default_world();
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        w.objects[1].motion = Some(Motion::linear(
+            Matrix::new_4x4_identity(),
+            transform::translation(1.0, 0.0, 0.0),
+        ));
+        let err = turntable().apply(&mut w, &mut c, 0.5).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shape 1 (sphere): transformation is overridden by motion"
+        );
+
+        // Material tracks still apply.
+        let mut a = turntable();
+        a.shapes[0].1.transform = None;
+        a.apply(&mut w, &mut c, 0.5).unwrap();
+        assert_eq!(w.objects[1].material.color, color::RED);
+    }
+
+    #[test]
+    fn rendering_numbered_frames() {
+        let dir = std::env::temp_dir().join("rustytracer_rendering_numbered_frames");
+        let _ = fs::remove_dir_all(&dir);
+        let mut w = test_util::default_world();
+        let mut c = Camera::new(4, 3, PI / 2.0);
+        let timeline = Timeline {
+            start: 0.0,
+            frames_per_second: 2.0,
+            frame_count: 3,
+        };
+        assert_eq!(timeline.time_of(2), 1.0);
+        let paths = render_frames(
+            &mut w,
+            &mut c,
+            &turntable(),
+            &timeline,
+            &RenderOptions::default(),
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                dir.join("frame_0000.ppm"),
+                dir.join("frame_0001.ppm"),
+                dir.join("frame_0002.ppm")
+            ]
+        );
+        for p in &paths {
+            assert!(fs::read(p).unwrap().starts_with(b"P6\n4 3\n"));
+        }
+        assert_eq!(*w.objects[1].transform(), transform::scaling(0.7, 0.7, 0.7));
+    }
+}
@@ -0,0 +1,173 @@
+//! Arbitrary output variables (AOV): per-pixel data rendered along with the
+//! image, for compositing and debugging.
+
+use crate::canvas::Canvas;
+use crate::color::{self, Color};
+use crate::ray::Ray;
+use crate::world::World;
+
+/// A quantity rendered per pixel by `Camera::render_passes()`.
+///
+/// Surface quantities (`Depth`, `Normal`, `Albedo` and `ObjectId`) come from
+/// the ray through the center of the pixel, so that they are not blended at
+/// edges.  Lighting quantities are averaged over all samples like the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Distance from the camera to the first surface hit, in all channels.
+    /// Infinite for rays hitting nothing.
+    Depth,
+    /// World space shading normal at the first hit, facing the camera, with x,
+    /// y and z in red, green and blue.  Black for rays hitting nothing.
+    Normal,
+    /// Color of the surface at the first hit, before lighting.  Black for
+    /// rays hitting nothing.
+    Albedo,
+    /// Index in `World::objects` of the first shape hit, in all channels.  -1
+    /// for rays hitting nothing.
+    ObjectId,
+    /// See `Radiance::direct`.
+    Direct,
+    /// See `Radiance::indirect`.
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 6] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object-id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    /// Returns AOV whose `name()` is `name`, if any.
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.iter().copied().find(|aov| aov.name() == name)
+    }
+}
+
+/// Canvases rendered by `Camera::render_passes()`.
+pub struct Passes {
+    /// The image, as rendered by `Camera::render_with()`.
+    pub beauty: Canvas,
+    /// One canvas per requested AOV, in the requested order.
+    pub aovs: Vec<(Aov, Canvas)>,
+}
+
+impl Passes {
+    /// Returns canvas of `aov`, if requested.
+    pub fn get(&self, aov: Aov) -> Option<&Canvas> {
+        self.aovs.iter().find(|(a, _)| *a == aov).map(|(_, c)| c)
+    }
+}
+
+/// Surface quantities at the first hit of a ray.
+pub(crate) struct SurfaceValues {
+    depth: f64,
+    normal: Color,
+    albedo: Color,
+    object_id: f64,
+}
+
+impl SurfaceValues {
+    /// Computes surface quantities along `ray` in `world`, if any ray.
+    pub(crate) fn new(world: &World, ray: Option<&Ray>) -> SurfaceValues {
+        let mut values = SurfaceValues {
+            depth: f64::INFINITY,
+            normal: color::BLACK,
+            albedo: color::BLACK,
+            object_id: -1.0,
+        };
+        let ray = match ray {
+            Some(ray) => ray,
+            None => return values,
+        };
+        if let Some(hit) = world.cast_ray(ray) {
+            let n = &hit.normal;
+            values.depth = hit.distance * ray.direction().magnitude();
+            values.normal = Color::new(n.x(), n.y(), n.z());
+            values.albedo = world.objects[hit.index].color_at(&hit.point, ray.time());
+            values.object_id = hit.index as f64;
+        }
+        values
+    }
+
+    /// Returns value of surface AOV `aov`.
+    pub(crate) fn get(&self, aov: Aov) -> Color {
+        let gray = |v| Color::new(v, v, v);
+        match aov {
+            Aov::Depth => gray(self.depth),
+            Aov::Normal => self.normal.clone(),
+            Aov::Albedo => self.albedo.clone(),
+            Aov::ObjectId => gray(self.object_id),
+            Aov::Direct | Aov::Indirect => panic!("{} is not a surface AOV", aov.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::Tuple;
+    use crate::world::test_util;
+
+    #[test]
+    fn aov_names() {
+        for aov in &Aov::ALL {
+            assert_eq!(Aov::from_name(aov.name()), Some(*aov));
+        }
+        assert_eq!(Aov::from_name("beauty"), None);
+    }
+
+    #[test]
+    fn surface_values_at_first_hit() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let v = SurfaceValues::new(&w, Some(&r));
+        assert_eq!(v.get(Aov::Depth), Color::new(4.0, 4.0, 4.0));
+        assert_eq!(v.get(Aov::Normal), Color::new(0.0, 0.0, -1.0));
+        assert_eq!(v.get(Aov::Albedo), Color::new(0.8, 1.0, 0.6));
+        assert_eq!(v.get(Aov::ObjectId), Color::new(0.0, 0.0, 0.0));
+
+        // From inside the outer sphere, the inner one is hit first.
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -0.75),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let v = SurfaceValues::new(&w, Some(&r));
+        assert_eq!(v.get(Aov::Depth), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(v.get(Aov::ObjectId), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn surface_values_without_hit() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 1.0, 0.0),
+        );
+        for v in &[
+            SurfaceValues::new(&w, Some(&r)),
+            SurfaceValues::new(&w, None),
+        ] {
+            assert_eq!(v.depth, f64::INFINITY);
+            assert_eq!(v.get(Aov::Normal), color::BLACK);
+            assert_eq!(v.get(Aov::ObjectId), Color::new(-1.0, -1.0, -1.0));
+        }
+    }
+}
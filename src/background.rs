@@ -0,0 +1,98 @@
+//! Color of rays escaping the scene.
+
+use crate::color::{self, Color};
+use crate::pattern::{Texture, UvMapping};
+use crate::tuple::{Tuple, ORIGIN};
+
+/// What rays that hit nothing see, as a function of their direction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    /// Same color in all directions.
+    Color(Color),
+    /// Vertical gradient from `bottom` (looking down) to `top` (looking up).
+    Gradient { top: Color, bottom: Color },
+    /// Panoramic image covering all directions, e.g. an HDR environment map
+    /// loaded with `Canvas::from_pfm()`.  The center of the image is in the +z
+    /// direction.  See `UvMapping::Spherical`.
+    Equirectangular(Texture),
+    /// Six images, one per face of a cube surrounding the scene, laid out as a
+    /// cross.  See `UvMapping::Cube`.
+    Cube(Texture),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(color::BLACK)
+    }
+}
+
+impl Background {
+    /// Returns color seen in direction `dir`.
+    pub fn color_at(&self, dir: &Tuple) -> Color {
+        debug_assert!(dir.is_vector());
+        match self {
+            Background::Color(c) => c.clone(),
+            Background::Gradient { top, bottom } => {
+                let t = (dir.normalized().y() + 1.0) / 2.0;
+                bottom.interpolated(top, t)
+            }
+            Background::Equirectangular(texture) => {
+                let (u, v) = UvMapping::Spherical.map(&(&ORIGIN + dir));
+                texture.color_at(u, v)
+            }
+            Background::Cube(texture) => {
+                let (u, v) = UvMapping::Cube.map(&(&ORIGIN + dir));
+                texture.color_at(u, v)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_background_is_black() {
+        assert_eq!(
+            Background::default().color_at(&Tuple::new_vector(0.0, 0.0, 1.0)),
+            color::BLACK
+        );
+    }
+
+    #[test]
+    fn gradient_background() {
+        let b = Background::Gradient {
+            top: color::WHITE,
+            bottom: color::BLUE,
+        };
+        assert_eq!(b.color_at(&Tuple::new_vector(0.0, 3.0, 0.0)), color::WHITE);
+        assert_eq!(b.color_at(&Tuple::new_vector(0.0, -1.0, 0.0)), color::BLUE);
+        assert_eq!(
+            b.color_at(&Tuple::new_vector(2.0, 0.0, 0.0)),
+            Color::new(0.5, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn equirectangular_background() {
+        let b = Background::Equirectangular(Texture::Function(|u, v| Color::new(u, v, 0.0)));
+        assert_eq!(
+            b.color_at(&Tuple::new_vector(0.0, 0.0, 2.0)),
+            Color::new(0.5, 0.5, 0.0)
+        );
+        assert_eq!(
+            b.color_at(&Tuple::new_vector(0.0, 1.0, 0.0)),
+            Color::new(0.5, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn cube_background() {
+        let b = Background::Cube(Texture::Function(|u, v| Color::new(u, v, 0.0)));
+        assert_eq!(
+            b.color_at(&Tuple::new_vector(0.0, 0.0, 2.0)),
+            Color::new(0.375, 0.5, 0.0)
+        );
+    }
+}
@@ -0,0 +1,524 @@
+//! Bidirectional scattering distribution functions (BSDF).
+//!
+//! A BSDF tells how much of the light arriving at a surface point from a
+//! direction is scattered toward another one.  All directions are unit vectors
+//! pointing away from the surface: `wo` toward the viewer, `wi` toward the light,
+//! and `n` is the shading normal on the side of `wo`.
+
+use std::f64::consts::PI;
+
+use crate::color::{self, Color};
+use crate::integrator;
+use crate::rng::Rng;
+use crate::tuple::Tuple;
+
+/// Smallest GGX alpha, avoiding infinite peaks for perfectly smooth surfaces.
+const MIN_ALPHA: f64 = 0.001;
+
+/// Reflectance at normal incidence of dielectrics, e.g. plastic or paint.
+const DIELECTRIC_F0: f64 = 0.04;
+
+/// A scattering model usable for direct lighting and by sampling integrators.
+pub trait Bsdf {
+    /// Returns the fraction of light arriving from `wi` scattered toward `wo`,
+    /// per unit solid angle (the cosine factor is not included).
+    fn eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color;
+
+    /// Picks a direction `wi` for light scattered toward `wo`, with a
+    /// probability density roughly proportional to its contribution.  Returns
+    /// `None` if no light is scattered toward `wo`.
+    fn sample(&self, wo: &Tuple, n: &Tuple, rng: &mut Rng) -> Option<BsdfSample>;
+
+    /// Returns probability density (per unit solid angle) of `sample()`
+    /// picking `wi`.
+    fn pdf(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64;
+}
+
+/// A direction picked by `Bsdf::sample()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BsdfSample {
+    pub wi: Tuple,
+    /// `Bsdf::eval()` for `wi`.
+    pub f: Color,
+    /// `Bsdf::pdf()` for `wi`.
+    pub pdf: f64,
+}
+
+impl BsdfSample {
+    /// Returns the Monte Carlo weight of this sample: `f * cos / pdf`.
+    pub fn weight(&self, n: &Tuple) -> Color {
+        &self.f * (Tuple::dot(&self.wi, n).abs() / self.pdf)
+    }
+}
+
+/// Ideal diffuse reflection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lambertian {
+    pub albedo: Color,
+}
+
+impl Bsdf for Lambertian {
+    fn eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color {
+        if same_hemisphere(wo, wi, n) {
+            &self.albedo * (1.0 / PI)
+        } else {
+            color::BLACK
+        }
+    }
+
+    fn sample(&self, wo: &Tuple, n: &Tuple, rng: &mut Rng) -> Option<BsdfSample> {
+        sample_with(self, wo, n, integrator::cosine_sample_hemisphere(n, rng))
+    }
+
+    fn pdf(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64 {
+        cosine_pdf(wo, wi, n)
+    }
+}
+
+/// Diffuse reflection of rough surfaces such as clay or plaster, which look
+/// flatter than Lambertian ones.
+///
+/// See Oren and Nayar, "Generalization of Lambert's Reflectance Model" (1994),
+/// qualitative model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrenNayar {
+    pub albedo: Color,
+    /// Standard deviation of the slope angle of surface facets, in radians.
+    /// 0 is Lambertian.
+    pub sigma: f64,
+}
+
+impl Bsdf for OrenNayar {
+    fn eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color {
+        if !same_hemisphere(wo, wi, n) {
+            return color::BLACK;
+        }
+        let s2 = self.sigma * self.sigma;
+        let a = 1.0 - 0.5 * s2 / (s2 + 0.33);
+        let b = 0.45 * s2 / (s2 + 0.09);
+        let cos_i = Tuple::dot(wi, n).abs().min(1.0);
+        let cos_o = Tuple::dot(wo, n).abs().min(1.0);
+        let sin_i = (1.0 - cos_i * cos_i).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).sqrt();
+
+        // Cosine of the azimuth difference, from projections on the tangent plane.
+        let cos_phi = if sin_i > 1e-6 && sin_o > 1e-6 {
+            let pi = (wi - &(n * Tuple::dot(wi, n))).normalized();
+            let po = (wo - &(n * Tuple::dot(wo, n))).normalized();
+            Tuple::dot(&pi, &po).max(0.0)
+        } else {
+            0.0
+        };
+        // sin(alpha) * tan(beta) with alpha the larger and beta the smaller angle.
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o)
+        };
+        &self.albedo * ((a + b * cos_phi * sin_alpha * tan_beta) / PI)
+    }
+
+    fn sample(&self, wo: &Tuple, n: &Tuple, rng: &mut Rng) -> Option<BsdfSample> {
+        sample_with(self, wo, n, integrator::cosine_sample_hemisphere(n, rng))
+    }
+
+    fn pdf(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64 {
+        cosine_pdf(wo, wi, n)
+    }
+}
+
+/// Specular reflection on a rough surface made of GGX-distributed mirror
+/// microfacets, with Schlick's approximation of Fresnel reflectance.
+///
+/// See Walter et al., "Microfacet Models for Refraction through Rough
+/// Surfaces" (2007).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Microfacet {
+    /// Reflectance at normal incidence.
+    pub f0: Color,
+    /// Perceptual roughness in `[0, 1]`: 0 is a mirror.
+    pub roughness: f64,
+}
+
+impl Microfacet {
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+
+    /// GGX normal distribution for microfacet normal `h`.
+    fn d(&self, h: &Tuple, n: &Tuple) -> f64 {
+        let a2 = self.alpha() * self.alpha();
+        let cos = Tuple::dot(h, n);
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        let t = cos * cos * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    /// Smith masking for direction `v`.
+    fn g1(&self, v: &Tuple, n: &Tuple) -> f64 {
+        let a2 = self.alpha() * self.alpha();
+        let cos = Tuple::dot(v, n).abs();
+        2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+    }
+}
+
+impl Bsdf for Microfacet {
+    fn eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color {
+        if !same_hemisphere(wo, wi, n) {
+            return color::BLACK;
+        }
+        let cos_o = Tuple::dot(wo, n).abs();
+        let cos_i = Tuple::dot(wi, n).abs();
+        if cos_o == 0.0 || cos_i == 0.0 {
+            return color::BLACK;
+        }
+        let h = (wo + wi).normalized();
+        let f = schlick(&self.f0, Tuple::dot(wo, &h));
+        let g = self.g1(wo, n) * self.g1(wi, n);
+        &f * (self.d(&h, n) * g / (4.0 * cos_o * cos_i))
+    }
+
+    fn sample(&self, wo: &Tuple, n: &Tuple, rng: &mut Rng) -> Option<BsdfSample> {
+        // Pick a microfacet normal with density D(h) cos(h) and mirror wo on it.
+        let a2 = self.alpha() * self.alpha();
+        let u = rng.next_f64();
+        let phi = 2.0 * PI * rng.next_f64();
+        let cos_theta = ((1.0 - u) / (1.0 + (a2 - 1.0) * u)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let (t, b) = integrator::orthonormal_basis(n);
+        let h =
+            &(&(&t * (sin_theta * phi.cos())) + &(&b * (sin_theta * phi.sin()))) + &(n * cos_theta);
+        let wi = Tuple::reflected(&-wo, &h);
+        sample_with(self, wo, n, wi)
+    }
+
+    fn pdf(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64 {
+        if !same_hemisphere(wo, wi, n) {
+            return 0.0;
+        }
+        let h = (wo + wi).normalized();
+        let wo_h = Tuple::dot(wo, &h).abs();
+        if wo_h == 0.0 {
+            return 0.0;
+        }
+        self.d(&h, n) * Tuple::dot(&h, n).abs() / (4.0 * wo_h)
+    }
+}
+
+/// Diffuse scattering model of `Physical` materials.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffuseModel {
+    Lambertian,
+    /// Oren-Nayar model with `sigma` derived from the roughness.
+    OrenNayar,
+}
+
+/// Metallic/roughness parameters of a physically based material, as used by
+/// most modeling tools.  The base color is the color of the `Material`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Physical {
+    /// 0 for dielectrics (diffuse base color, white highlights), 1 for metals
+    /// (no diffuse, highlights of base color).  In-between values blend both.
+    pub metallic: f64,
+    /// 0 is polished, 1 is fully rough.
+    pub roughness: f64,
+    pub diffuse: DiffuseModel,
+}
+
+impl Default for Physical {
+    fn default() -> Self {
+        Physical {
+            metallic: 0.0,
+            roughness: 0.5,
+            diffuse: DiffuseModel::Lambertian,
+        }
+    }
+}
+
+impl Physical {
+    /// Returns BSDF of a surface with these parameters and `base_color`.
+    pub fn bsdf(&self, base_color: &Color) -> Principled {
+        let f0 = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        Principled {
+            diffuse_albedo: base_color * (1.0 - self.metallic),
+            diffuse: self.diffuse,
+            sigma: self.roughness * PI / 4.0,
+            specular: Microfacet {
+                f0: f0.interpolated(base_color, self.metallic),
+                roughness: self.roughness,
+            },
+        }
+    }
+}
+
+/// Diffuse base under a specular microfacet coat.  Light reflected by the coat
+/// does not reach the base, so the surface never reflects more light than it
+/// receives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principled {
+    diffuse_albedo: Color,
+    diffuse: DiffuseModel,
+    /// Oren-Nayar roughness.
+    sigma: f64,
+    specular: Microfacet,
+}
+
+impl Principled {
+    /// Returns probability of sampling the specular lobe.
+    fn specular_probability(&self) -> f64 {
+        let s = self.specular.f0.luminance();
+        let d = self.diffuse_albedo.luminance() * (1.0 - s);
+        if s + d <= 0.0 {
+            1.0
+        } else {
+            // Sample the specular lobe even when the diffuse one dominates,
+            // since highlights are small and bright.
+            (s / (s + d)).max(0.25)
+        }
+    }
+
+    fn diffuse_eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color {
+        match self.diffuse {
+            DiffuseModel::Lambertian => Lambertian {
+                albedo: self.diffuse_albedo.clone(),
+            }
+            .eval(wo, wi, n),
+            DiffuseModel::OrenNayar => OrenNayar {
+                albedo: self.diffuse_albedo.clone(),
+                sigma: self.sigma,
+            }
+            .eval(wo, wi, n),
+        }
+    }
+}
+
+impl Bsdf for Principled {
+    fn eval(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> Color {
+        if !same_hemisphere(wo, wi, n) {
+            return color::BLACK;
+        }
+        // The base receives light transmitted through the coat on the way in
+        // and out.
+        let transmitted = |v: &Tuple| {
+            let f = schlick(&self.specular.f0, Tuple::dot(v, n).abs());
+            &color::WHITE - &f
+        };
+        let diffuse = &(&self.diffuse_eval(wo, wi, n) * &transmitted(wi)) * &transmitted(wo);
+        &diffuse + &self.specular.eval(wo, wi, n)
+    }
+
+    fn sample(&self, wo: &Tuple, n: &Tuple, rng: &mut Rng) -> Option<BsdfSample> {
+        let wi = if rng.next_f64() < self.specular_probability() {
+            self.specular.sample(wo, n, rng)?.wi
+        } else {
+            integrator::cosine_sample_hemisphere(n, rng)
+        };
+        sample_with(self, wo, n, wi)
+    }
+
+    fn pdf(&self, wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64 {
+        let p = self.specular_probability();
+        p * self.specular.pdf(wo, wi, n) + (1.0 - p) * cosine_pdf(wo, wi, n)
+    }
+}
+
+/// Returns sample of `bsdf` for direction `wi`, or `None` if it carries no light.
+fn sample_with<B: Bsdf>(bsdf: &B, wo: &Tuple, n: &Tuple, wi: Tuple) -> Option<BsdfSample> {
+    let pdf = bsdf.pdf(wo, &wi, n);
+    if pdf <= 0.0 {
+        return None;
+    }
+    Some(BsdfSample {
+        f: bsdf.eval(wo, &wi, n),
+        wi,
+        pdf,
+    })
+}
+
+/// Returns whether `wo` and `wi` are on the same side of the surface, i.e. light
+/// is reflected rather than transmitted.
+fn same_hemisphere(wo: &Tuple, wi: &Tuple, n: &Tuple) -> bool {
+    Tuple::dot(wo, n) * Tuple::dot(wi, n) > 0.0
+}
+
+/// Probability density of cosine-weighted hemisphere sampling.
+fn cosine_pdf(wo: &Tuple, wi: &Tuple, n: &Tuple) -> f64 {
+    if same_hemisphere(wo, wi, n) {
+        Tuple::dot(wi, n).abs() / PI
+    } else {
+        0.0
+    }
+}
+
+/// Schlick's approximation of Fresnel reflectance for angle of incidence with
+/// cosine `cos`.
+fn schlick(f0: &Color, cos: f64) -> Color {
+    let k = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0.interpolated(&color::WHITE, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n() -> Tuple {
+        Tuple::new_vector(0.0, 1.0, 0.0)
+    }
+
+    fn wo() -> Tuple {
+        Tuple::new_vector(0.6, 0.8, 0.0)
+    }
+
+    /// Returns Monte Carlo estimate of the fraction of light arriving from `wo`
+    /// reflected in all directions.
+    fn albedo<B: Bsdf>(bsdf: &B, wo: &Tuple) -> Color {
+        let mut rng = Rng::new(0);
+        let count = 20000;
+        let mut sum = color::BLACK;
+        for _ in 0..count {
+            if let Some(s) = bsdf.sample(wo, &n(), &mut rng) {
+                sum = &sum + &s.weight(&n());
+            }
+        }
+        &sum * (1.0 / count as f64)
+    }
+
+    fn check_sampling_consistency<B: Bsdf>(bsdf: &B) {
+        let mut rng = Rng::new(1);
+        for _ in 0..100 {
+            if let Some(s) = bsdf.sample(&wo(), &n(), &mut rng) {
+                assert!((s.wi.magnitude() - 1.0).abs() < 1e-9);
+                assert!((s.pdf - bsdf.pdf(&wo(), &s.wi, &n())).abs() < 1e-9);
+                assert_eq!(s.f, bsdf.eval(&wo(), &s.wi, &n()));
+            }
+        }
+    }
+
+    #[test]
+    fn lambertian() {
+        let b = Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        };
+        let wi = Tuple::new_vector(0.0, 0.0, 1.0);
+        let up = Tuple::new_vector(0.0, 1.0, 0.0);
+        assert_eq!(
+            b.eval(&wo(), &up, &n()),
+            Color::new(0.5 / PI, 0.5 / PI, 0.5 / PI)
+        );
+        assert_eq!(b.eval(&wo(), &-&up, &n()), color::BLACK);
+        assert_eq!(b.pdf(&wo(), &wi, &n()), 0.0);
+        assert_eq!(b.pdf(&wo(), &up, &n()), 1.0 / PI);
+        check_sampling_consistency(&b);
+        // Cosine-weighted samples all weigh exactly the albedo.
+        let mut rng = Rng::new(0);
+        let s = b.sample(&wo(), &n(), &mut rng).unwrap();
+        assert_eq!(s.weight(&n()), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn oren_nayar_without_roughness_is_lambertian() {
+        let albedo = Color::new(0.2, 0.4, 0.6);
+        let on = OrenNayar {
+            albedo: albedo.clone(),
+            sigma: 0.0,
+        };
+        let l = Lambertian { albedo };
+        let wi = Tuple::new_vector(-0.3, 0.9, 0.1).normalized();
+        assert_eq!(on.eval(&wo(), &wi, &n()), l.eval(&wo(), &wi, &n()));
+        check_sampling_consistency(&on);
+    }
+
+    #[test]
+    fn oren_nayar_brightens_backscattering() {
+        let on = OrenNayar {
+            albedo: color::WHITE,
+            sigma: 0.5,
+        };
+        let back = on.eval(&wo(), &wo(), &n());
+        let forward = on.eval(&wo(), &Tuple::new_vector(-0.6, 0.8, 0.0), &n());
+        assert!(back.red() > forward.red());
+        assert!(albedo(&on, &wo()).red() <= 1.0);
+    }
+
+    #[test]
+    fn microfacet_sampling_is_consistent() {
+        for roughness in &[0.05, 0.3, 1.0] {
+            check_sampling_consistency(&Microfacet {
+                f0: color::WHITE,
+                roughness: *roughness,
+            });
+        }
+    }
+
+    #[test]
+    fn smooth_white_metal_reflects_most_light() {
+        for roughness in &[0.1, 0.5] {
+            let m = Microfacet {
+                f0: color::WHITE,
+                roughness: *roughness,
+            };
+            let a = albedo(&m, &wo()).red();
+            assert!(a > 0.85 && a < 1.01, "roughness {}: {}", roughness, a);
+        }
+    }
+
+    #[test]
+    fn smooth_microfacet_is_mirror_like() {
+        let m = Microfacet {
+            f0: color::WHITE,
+            roughness: 0.05,
+        };
+        let mirror = Tuple::new_vector(-0.6, 0.8, 0.0);
+        let off = Tuple::new_vector(-0.3, 0.9, 0.3).normalized();
+        assert!(m.eval(&wo(), &mirror, &n()).red() > 100.0);
+        assert!(m.eval(&wo(), &off, &n()).red() < 0.01);
+    }
+
+    #[test]
+    fn fresnel_increases_at_grazing_angles() {
+        let f0 = Color::new(0.04, 0.04, 0.04);
+        assert_eq!(schlick(&f0, 1.0), f0);
+        assert_eq!(schlick(&f0, 0.0), color::WHITE);
+        assert!(schlick(&f0, 0.2).red() > schlick(&f0, 0.8).red());
+    }
+
+    #[test]
+    fn physical_materials_conserve_energy() {
+        for metallic in &[0.0, 0.5, 1.0] {
+            for roughness in &[0.1, 0.5, 1.0] {
+                for diffuse in &[DiffuseModel::Lambertian, DiffuseModel::OrenNayar] {
+                    let p = Physical {
+                        metallic: *metallic,
+                        roughness: *roughness,
+                        diffuse: *diffuse,
+                    };
+                    let b = p.bsdf(&color::WHITE);
+                    check_sampling_consistency(&b);
+                    let a = albedo(&b, &wo()).red();
+                    assert!(a <= 1.01, "{:?}: {}", p, a);
+                    // Single scattering loses energy on rough surfaces.
+                    assert!(a > 0.25, "{:?}: {}", p, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn metals_have_colored_highlights_and_no_diffuse() {
+        let p = Physical {
+            metallic: 1.0,
+            roughness: 0.3,
+            diffuse: DiffuseModel::Lambertian,
+        };
+        let b = p.bsdf(&color::RED);
+        let mirror = Tuple::new_vector(-0.6, 0.8, 0.0);
+        let highlight = b.eval(&wo(), &mirror, &n());
+        assert!(highlight.red() > 1.0);
+        assert!(highlight.green() < highlight.red() / 10.0);
+        let b = Physical { metallic: 0.0, ..p }.bsdf(&color::RED);
+        let highlight = b.eval(&wo(), &mirror, &n());
+        assert!(highlight.green() > 0.0);
+    }
+}
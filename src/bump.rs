@@ -0,0 +1,243 @@
+//! Bump and normal mapping.
+//!
+//! Small surface details are simulated by perturbing shading normals instead
+//! of changing the geometry.  Shadows and intersections are not affected.
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::matrix::Matrix;
+use crate::noise::Perlin;
+use crate::pattern::{Pattern, Texture, UvMapping};
+use crate::shape::Object;
+use crate::transform::Transform;
+use crate::tuple::Tuple;
+
+/// Step used to estimate height gradients, in object space.
+const GRADIENT_STEP: f64 = 0.0001;
+
+/// A perturbation of the normals of a shape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bump {
+    /// Transformation of the perturbation in object space, with its inverse.
+    transform: Transform,
+    pub kind: BumpKind,
+}
+
+/// `Bump` helper storing kind-specific bits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BumpKind {
+    /// Bumps following fractal noise (see `Perlin::fbm()`).
+    Noise {
+        noise: Perlin,
+        octaves: usize,
+        /// Height of the bumps.
+        strength: f64,
+    },
+    /// Bumps following the luminance of a pattern, e.g. a grayscale height map.
+    Height {
+        pattern: Box<Pattern>,
+        /// Height of the bumps for luminance 1.
+        strength: f64,
+    },
+    /// Tangent space normals encoded as colors, as usual in normal maps: red,
+    /// green and blue in `[0, 1]` map to coordinates from -1 to 1 along the
+    /// directions of growing u, growing v and the surface normal.
+    NormalMap {
+        /// `None` selects the natural mapping of the shape.
+        mapping: Option<UvMapping>,
+        texture: Texture,
+    },
+}
+
+impl Bump {
+    pub fn new(kind: BumpKind) -> Bump {
+        Bump {
+            transform: Transform::identity(),
+            kind,
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+
+    /// See `Shape::set_transform()`.
+    pub fn set_transform(&mut self, t: impl Into<Transform>) {
+        self.transform = t.into();
+    }
+
+    /// Returns an error if the transformation of this bump, or of its height
+    /// pattern or normal map, is not invertible.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.transform.try_inverse()?;
+        match &self.kind {
+            BumpKind::Height { pattern, .. } => pattern.validate(),
+            BumpKind::NormalMap { texture, .. } => texture.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns perturbation of normal `obj_normal` at point `obj_pt`, both in
+    /// object space of a shape whose object is `object`.
+    pub fn perturbed_normal(&self, object: &Object, obj_pt: &Tuple, obj_normal: &Tuple) -> Tuple {
+        debug_assert!(obj_pt.is_point());
+        debug_assert!(obj_normal.is_vector());
+        let inv = self.transform.inverted();
+        match &self.kind {
+            BumpKind::Noise {
+                noise,
+                octaves,
+                strength,
+            } => {
+                let height = |pt: &Tuple| noise.fbm(&(inv * pt), *octaves);
+                bumped(obj_pt, obj_normal, *strength, height)
+            }
+            BumpKind::Height { pattern, strength } => {
+                let height = |pt: &Tuple| pattern.color_at(object, &(inv * pt)).luminance();
+                bumped(obj_pt, obj_normal, *strength, height)
+            }
+            BumpKind::NormalMap { mapping, texture } => {
+                let mapping = mapping.unwrap_or_else(|| object.uv_mapping());
+                let pt = inv * obj_pt;
+                let (u, v) = mapping.map(&pt);
+                let (t, b) = mapping.tangents(&pt);
+                let encoded = texture.color_at(u, v);
+                let local = |c: &Color, i| 2.0 * c.channel(i) - 1.0;
+
+                // Orthonormal frame around the normal (Gram-Schmidt).
+                let n = obj_normal.normalized();
+                let t = self.transform.matrix() * &t;
+                let t = (&t - &(&n * Tuple::dot(&t, &n))).normalized();
+                let b = self.transform.matrix() * &b;
+                let b = &(&b - &(&n * Tuple::dot(&b, &n))) - &(&t * Tuple::dot(&b, &t));
+                let b = b.normalized();
+
+                let perturbed = &(&(&t * local(&encoded, 0)) + &(&b * local(&encoded, 1)))
+                    + &(&n * local(&encoded, 2));
+                perturbed.normalized()
+            }
+        }
+    }
+}
+
+/// Returns `normal` at `pt` tilted by the slope of `strength * height`.
+fn bumped<F: Fn(&Tuple) -> f64>(pt: &Tuple, normal: &Tuple, strength: f64, height: F) -> Tuple {
+    let n = normal.normalized();
+    let axis = |x, y, z| Tuple::new_vector(x, y, z);
+    let slope = |d: Tuple| {
+        (height(&(pt + &(&d * GRADIENT_STEP))) - height(&(pt - &(&d * GRADIENT_STEP))))
+            / (2.0 * GRADIENT_STEP)
+    };
+    let gradient = Tuple::new_vector(
+        slope(axis(1.0, 0.0, 0.0)),
+        slope(axis(0.0, 1.0, 0.0)),
+        slope(axis(0.0, 0.0, 1.0)),
+    );
+    // Only the part of the gradient along the surface tilts the normal.
+    let tangential = &gradient - &(&n * Tuple::dot(&gradient, &n));
+    (&n - &(&tangential * strength)).normalized()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+    use crate::pattern::ImageTexture;
+    use crate::transform;
+    use std::sync::Arc;
+
+    fn up() -> Tuple {
+        Tuple::new_vector(0.0, 1.0, 0.0)
+    }
+
+    fn assert_nearly_equal(l: &Tuple, r: &Tuple) {
+        assert!((l - r).magnitude() < 1e-6, "{:?} != {:?}", l, r);
+    }
+
+    #[test]
+    fn height_slope_tilts_normal() {
+        // Height grows along x, so the normal tilts toward -x.
+        let b = Bump::new(BumpKind::Height {
+            pattern: Box::new(Pattern::mapped_texture(
+                UvMapping::Planar,
+                Texture::Function(|u, _| Color::new(u, u, u)),
+            )),
+            strength: 1.0,
+        });
+        let n = b.perturbed_normal(&Object::Plane, &Tuple::new_point(0.5, 0.0, 0.5), &up());
+        assert_nearly_equal(&n, &Tuple::new_vector(-1.0, 1.0, 0.0).normalized());
+    }
+
+    #[test]
+    fn flat_height_leaves_normal_unchanged() {
+        let b = Bump::new(BumpKind::Height {
+            pattern: Box::new(Pattern::texture(Texture::Function(|_, _| {
+                Color::new(0.3, 0.3, 0.3)
+            }))),
+            strength: 5.0,
+        });
+        let n = b.perturbed_normal(&Object::Plane, &Tuple::new_point(0.2, 0.0, 0.7), &up());
+        assert_nearly_equal(&n, &up());
+    }
+
+    #[test]
+    fn noise_bumps_vary_and_keep_normals_outward() {
+        let b = Bump::new(BumpKind::Noise {
+            noise: Perlin::new(0),
+            octaves: 3,
+            strength: 0.3,
+        });
+        let mut changed = false;
+        for i in 0..50 {
+            let pt = Tuple::new_point(i as f64 * 0.173, 0.0, i as f64 * 0.071);
+            let n = b.perturbed_normal(&Object::Plane, &pt, &up());
+            assert!((n.magnitude() - 1.0).abs() < 1e-9);
+            assert!(n.y() > 0.0);
+            changed |= (&n - &up()).magnitude() > 1e-3;
+        }
+        assert!(changed);
+    }
+
+    #[test]
+    fn flat_normal_map_leaves_normal_unchanged() {
+        let b = Bump::new(BumpKind::NormalMap {
+            mapping: None,
+            texture: Texture::Function(|_, _| Color::new(0.5, 0.5, 1.0)),
+        });
+        let pt = Tuple::new_point(0.0, 0.6, -0.8);
+        let normal = Tuple::new_vector(0.0, 0.6, -0.8);
+        let n = b.perturbed_normal(&Object::Sphere, &pt, &normal);
+        assert_nearly_equal(&n, &normal);
+    }
+
+    #[test]
+    fn normal_map_uses_tangent_space() {
+        // Full tilt toward growing u, i.e. +x on a plane.
+        let b = Bump::new(BumpKind::NormalMap {
+            mapping: None,
+            texture: Texture::Function(|_, _| Color::new(1.0, 0.5, 0.5)),
+        });
+        let n = b.perturbed_normal(&Object::Plane, &Tuple::new_point(0.3, 0.0, 0.3), &up());
+        assert_nearly_equal(&n, &Tuple::new_vector(1.0, 0.0, 0.0));
+
+        // Tilt toward growing v, i.e. +z on a plane, rotated with the bump.
+        let mut b = Bump::new(BumpKind::NormalMap {
+            mapping: None,
+            texture: Texture::Function(|_, _| Color::new(0.5, 1.0, 1.0)),
+        });
+        b.set_transform(transform::rotation_y(std::f64::consts::PI / 2.0));
+        let n = b.perturbed_normal(&Object::Plane, &Tuple::new_point(0.3, 0.0, 0.3), &up());
+        assert_nearly_equal(&n, &Tuple::new_vector(1.0, 1.0, 0.0).normalized());
+    }
+
+    #[test]
+    fn validating_normal_map_transformations() {
+        let mut image = ImageTexture::new(Arc::new(Canvas::new(2, 2)));
+        image.set_transform(transform::scaling(0.0, 1.0, 1.0));
+        let b = Bump::new(BumpKind::NormalMap {
+            mapping: None,
+            texture: Texture::Image(image),
+        });
+        assert_eq!(b.validate(), Err(Error::SingularMatrix));
+    }
+}
@@ -2,11 +2,68 @@
 //!
 //! See TRTC chapter 7.
 
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::aov::{Aov, Passes, SurfaceValues};
 use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::integrator::{Integrator, Radiance};
 use crate::matrix::Matrix;
 use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::shape::Shape;
+use crate::transform::Transform;
 use crate::tuple::{Tuple, ORIGIN};
-use crate::world::World;
+use crate::world::{RayHit, World};
+
+/// Settings controlling how `Camera::render_with()` renders a world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// Number of rays cast per pixel.  With more than one sample, rays are
+    /// jittered inside the pixel to anti-alias edges.  0 counts as 1.
+    pub samples: usize,
+
+    /// Number of threads rendering rows concurrently.  0 counts as 1.
+    pub threads: usize,
+
+    pub integrator: Integrator,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            samples: 1,
+            threads: 1,
+            integrator: Integrator::Phong,
+        }
+    }
+}
+
+/// How the camera projects the 3D world onto the canvas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    /// Rays diverge from the eye.  The field of view is in radians and spans
+    /// the longest side of the canvas.
+    Perspective { field_of_view: f64 },
+
+    /// Rays are parallel and cross a view volume of the given size in world units.
+    Orthographic { width: f64, height: f64 },
+
+    /// Latitude/longitude mapping of the full sphere of directions around the
+    /// eye.  The canvas horizontal axis spans 360 degrees and the vertical
+    /// axis 180 degrees, so the canvas should be twice as wide as high.
+    Equirectangular,
+
+    /// Equidistant fisheye: the angle between a ray and the view direction is
+    /// proportional to the distance of its pixel from the canvas center.  The
+    /// field of view in radians spans the shortest side of the canvas and may
+    /// exceed 180 degrees.  Pixels outside the image circle see no ray: they
+    /// stay black whatever the background of the world, like the corners of a
+    /// fisheye photograph.
+    Fisheye { field_of_view: f64 },
+}
 
 /// Parameters to map the 3D world to a 2D canvas.
 pub struct Camera {
@@ -16,26 +73,41 @@ pub struct Camera {
     /// Canvas height in pixels.
     vsize: usize,
 
-    /// In radians.
-    field_of_view: f64,
+    projection: Projection,
 
     /// Canvas half width in world units.
+    ///
+    /// Equirectangular views use radians and fisheye views use units of half
+    /// the shortest canvas side instead.
     half_width: f64,
 
-    /// Canvas half height in world units.
+    /// Canvas half height in the same units as `half_width`.
     half_height: f64,
 
-    /// Pixel width or height in world units.
+    /// Pixel width in the same units as `half_width`.
     pixel_size: f64,
 
-    pub transform: Matrix,
+    /// Pixel height in the same units as `half_width`.  Differs from
+    /// `pixel_size` for orthographic and equirectangular views whose aspect
+    /// ratio differs from the canvas one.
+    pixel_height: f64,
+
+    /// View transformation, with its inverse computed once.
+    transform: Transform,
+
+    /// Time the shutter opens.  Rays are cast at times spread over the
+    /// `[shutter_open, shutter_close]` interval so that moving shapes blur.
+    pub shutter_open: f64,
+
+    /// Time the shutter closes.
+    pub shutter_close: f64,
 }
 
 impl Camera {
     /// Constructs a new camera for canvas `(hsize, vsize)`, `field_of_view` angle and
     /// an identity view transformation.
     pub fn new(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
-        Self::with_transform(hsize, vsize, field_of_view, Matrix::new_4x4_identity())
+        Self::with_transform(hsize, vsize, field_of_view, Transform::identity())
     }
 
     /// Constructs a new camera for canvas `(hsize, vsize)`, `field_of_view` angle and
@@ -44,24 +116,98 @@ impl Camera {
         hsize: usize,
         vsize: usize,
         field_of_view: f64,
-        transform: Matrix,
+        transform: impl Into<Transform>,
     ) -> Camera {
-        let half_view = (field_of_view / 2.0).tan();
-        let aspect = hsize as f64 / vsize as f64;
-        let (half_width, half_height) = if aspect >= 1.0 {
-            (half_view, half_view / aspect)
-        } else {
-            (half_view * aspect, half_view)
+        Self::with_projection(
+            hsize,
+            vsize,
+            Projection::Perspective { field_of_view },
+            transform,
+        )
+    }
+
+    /// Constructs a new orthographic camera for canvas `(hsize, vsize)` showing a
+    /// `width` x `height` view volume seen through `transform` view transformation.
+    pub fn orthographic(
+        hsize: usize,
+        vsize: usize,
+        width: f64,
+        height: f64,
+        transform: impl Into<Transform>,
+    ) -> Camera {
+        Self::with_projection(
+            hsize,
+            vsize,
+            Projection::Orthographic { width, height },
+            transform,
+        )
+    }
+
+    /// Constructs a new camera for canvas `(hsize, vsize)`, `projection` and
+    /// `transform` view transformation.
+    pub fn with_projection(
+        hsize: usize,
+        vsize: usize,
+        projection: Projection,
+        transform: impl Into<Transform>,
+    ) -> Camera {
+        let (half_width, half_height) = match projection {
+            Projection::Perspective { field_of_view } => {
+                let half_view = (field_of_view / 2.0).tan();
+                let aspect = hsize as f64 / vsize as f64;
+                if aspect >= 1.0 {
+                    (half_view, half_view / aspect)
+                } else {
+                    (half_view * aspect, half_view)
+                }
+            }
+            Projection::Orthographic { width, height } => (width / 2.0, height / 2.0),
+            Projection::Equirectangular => (PI, PI / 2.0),
+            Projection::Fisheye { .. } => {
+                let aspect = hsize as f64 / vsize as f64;
+                if aspect >= 1.0 {
+                    (aspect, 1.0)
+                } else {
+                    (1.0, 1.0 / aspect)
+                }
+            }
         };
         let pixel_size = (half_width * 2.0) / hsize as f64;
+        let pixel_height = match projection {
+            Projection::Orthographic { .. } | Projection::Equirectangular => {
+                (half_height * 2.0) / vsize as f64
+            }
+            _ => pixel_size,
+        };
         Camera {
             hsize,
             vsize,
-            field_of_view,
-            transform,
+            projection,
+            transform: transform.into(),
             half_width,
             half_height,
             pixel_size,
+            pixel_height,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    /// Returns a copy of this camera rendering to a canvas of size `(hsize, vsize)`.
+    ///
+    /// Orthographic views keep their width and adapt their height to the new aspect ratio.
+    pub fn resized(&self, hsize: usize, vsize: usize) -> Camera {
+        let projection = match self.projection {
+            Projection::Orthographic { width, .. } => Projection::Orthographic {
+                width,
+                height: width * vsize as f64 / hsize as f64,
+            },
+            ref p => p.clone(),
+        };
+        Camera {
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
+            ..Self::with_projection(hsize, vsize, projection, self.transform.clone())
         }
     }
 
@@ -73,8 +219,30 @@ impl Camera {
         self.vsize
     }
 
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+
+    /// Sets view transformation.  See `Shape::set_transform()`.
+    pub fn set_transform(&mut self, t: impl Into<Transform>) {
+        self.transform = t.into();
+    }
+
+    /// Returns angle of view in radians.
+    ///
+    /// Panics for orthographic views, which have none.  See `projection()`.
     pub fn field_of_view(&self) -> f64 {
-        self.field_of_view
+        match self.projection {
+            Projection::Perspective { field_of_view } | Projection::Fisheye { field_of_view } => {
+                field_of_view
+            }
+            Projection::Orthographic { .. } => panic!("orthographic views have no field of view"),
+            Projection::Equirectangular => 2.0 * PI,
+        }
     }
 
     pub fn pixel_size(&self) -> f64 {
@@ -83,36 +251,213 @@ impl Camera {
 
     /// Render the view of the `world` as seen by this camera.
     pub fn render(&self, world: &World) -> Canvas {
-        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        self.render_with(world, &RenderOptions::default())
+    }
+
+    /// Render the view of the `world` as seen by this camera with the given `options`.
+    pub fn render_with(&self, world: &World, options: &RenderOptions) -> Canvas {
+        self.render_passes(world, options, &[]).beauty
+    }
+
+    /// Same as `render_with()`, also rendering each of `aovs` to its own canvas.
+    pub fn render_passes(&self, world: &World, options: &RenderOptions, aovs: &[Aov]) -> Passes {
+        let options = &RenderOptions {
+            samples: options.samples.max(1),
+            threads: options.threads.max(1),
+            ..options.clone()
+        };
 
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                canvas.set(x, y, &world.color_at(&ray));
+        let emitters = world.emitters();
+        let mut canvases = vec![Canvas::new(self.hsize, self.vsize); aovs.len() + 1];
+        let mut store = |y: usize, row: Vec<Vec<Color>>| {
+            for (x, values) in row.iter().enumerate() {
+                for (canvas, c) in canvases.iter_mut().zip(values) {
+                    canvas.set(x, y, c);
+                }
+            }
+        };
+
+        if options.threads == 1 {
+            for y in 0..self.vsize {
+                store(y, self.render_row(world, &emitters, options, aovs, y));
+            }
+        } else {
+            // Threads pick the next row to render until all rows are done.
+            let next_row = AtomicUsize::new(0);
+            let rows = thread::scope(|scope| {
+                let workers: Vec<_> = (0..options.threads)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut rows = Vec::new();
+                            loop {
+                                let y = next_row.fetch_add(1, Ordering::Relaxed);
+                                if y >= self.vsize {
+                                    break rows;
+                                }
+                                rows.push((y, self.render_row(world, &emitters, options, aovs, y)));
+                            }
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("render thread panicked"))
+                    .collect::<Vec<_>>()
+            });
+            for (y, row) in rows {
+                store(y, row);
             }
         }
 
-        canvas
+        let beauty = canvases.remove(0);
+        Passes {
+            beauty,
+            aovs: aovs.iter().copied().zip(canvases).collect(),
+        }
+    }
+
+    /// Computes colors of pixels on row `y`, each followed by its values of `aovs`.
+    fn render_row(
+        &self,
+        world: &World,
+        emitters: &[&Shape],
+        options: &RenderOptions,
+        aovs: &[Aov],
+        y: usize,
+    ) -> Vec<Vec<Color>> {
+        (0..self.hsize)
+            .map(|x| {
+                // Seed with pixel index so that renders are reproducible
+                // whatever the number of threads.
+                let mut rng = Rng::new((y * self.hsize + x) as u64);
+                let time = self.shutter_time(0.5);
+                let center = self.ray_for_pixel(x, y).map(|r| r.with_time(time));
+                let radiance = if options.samples == 1 {
+                    self.radiance_for(world, emitters, options, center.as_ref(), &mut rng)
+                } else {
+                    let mut sum = Radiance::default();
+                    for _ in 0..options.samples {
+                        let ray = self
+                            .ray_for_point(x as f64 + rng.next_f64(), y as f64 + rng.next_f64());
+                        let time = self.shutter_time(rng.next_f64());
+                        let ray = ray.map(|r| r.with_time(time));
+                        let r = self.radiance_for(world, emitters, options, ray.as_ref(), &mut rng);
+                        sum.direct = &sum.direct + &r.direct;
+                        sum.indirect = &sum.indirect + &r.indirect;
+                    }
+                    let n = options.samples as f64;
+                    Radiance {
+                        direct: &sum.direct * (1.0 / n),
+                        indirect: &sum.indirect * (1.0 / n),
+                    }
+                };
+
+                let mut values = vec![radiance.total()];
+                let surface = aovs
+                    .iter()
+                    .any(|a| !matches!(a, Aov::Direct | Aov::Indirect))
+                    .then(|| SurfaceValues::new(world, center.as_ref()));
+                values.extend(aovs.iter().map(|aov| match (aov, &surface) {
+                    (Aov::Direct, _) => radiance.direct.clone(),
+                    (Aov::Indirect, _) => radiance.indirect.clone(),
+                    (_, Some(surface)) => surface.get(*aov),
+                    (_, None) => unreachable!(),
+                }));
+                values
+            })
+            .collect()
     }
 
-    /// Computes a ray cast from the camera to `(x,y)` on canvas.
-    fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
+    /// Returns first surface of `world` seen through the center of pixel `(x, y)`
+    /// halfway through the exposure, if any.  Pixels outside the canvas see
+    /// nothing.
+    pub fn pick(&self, world: &World, x: usize, y: usize) -> Option<RayHit> {
+        if x >= self.hsize || y >= self.vsize {
+            return None;
+        }
+        let ray = self.ray_for_pixel(x, y)?.with_time(self.shutter_time(0.5));
+        world.cast_ray(&ray)
+    }
+
+    /// Maps `f` in `[0, 1]` to a time while the shutter is open.
+    fn shutter_time(&self, f: f64) -> f64 {
+        self.shutter_open + f * (self.shutter_close - self.shutter_open)
+    }
+
+    /// Computes light seen along camera `ray`, if any.
+    fn radiance_for(
+        &self,
+        world: &World,
+        emitters: &[&Shape],
+        options: &RenderOptions,
+        ray: Option<&Ray>,
+        rng: &mut Rng,
+    ) -> Radiance {
+        match ray {
+            Some(r) => options
+                .integrator
+                .radiance_with_emitters(world, emitters, r, rng),
+            None => Radiance::default(),
+        }
+    }
+
+    /// Computes a ray cast from the camera through the center of pixel `(x,y)` on canvas.
+    fn ray_for_pixel(&self, x: usize, y: usize) -> Option<Ray> {
+        self.ray_for_point(x as f64 + 0.5, y as f64 + 0.5)
+    }
+
+    /// Computes a ray cast from the camera to point `(x,y)` on canvas.
+    ///
+    /// Coordinates are in pixels from the canvas top-left corner.  Returns
+    /// `None` if the projection maps no ray to this point.
+    fn ray_for_point(&self, x: f64, y: f64) -> Option<Ray> {
         // Compute point offsets from canvas top-left in world units.
-        let x_off = (x as f64 + 0.5) * self.pixel_size;
-        let y_off = (y as f64 + 0.5) * self.pixel_size;
+        let x_off = x * self.pixel_size;
+        let y_off = y * self.pixel_height;
 
         // Compute coordinates of untransformed point in world space.
         let x_world = self.half_width - x_off;
         let y_world = self.half_height - y_off;
 
         // Compute transformed coordinates.
-        // The canvas is at z = -1.
         let t = self.transform.inverted();
-        let pixel = &t * &Tuple::new_point(x_world, y_world, -1.0);
-        let origin = &t * &ORIGIN;
-        let direction = (&pixel - &origin).normalized();
-
-        Ray::new(origin, direction)
+        match self.projection {
+            Projection::Perspective { .. } => {
+                // The canvas is at z = -1.
+                let pixel = t * &Tuple::new_point(x_world, y_world, -1.0);
+                let origin = t * &ORIGIN;
+                let direction = (&pixel - &origin).normalized();
+                Some(Ray::new(origin, direction))
+            }
+            Projection::Orthographic { .. } => {
+                // All rays start on the z = 0 plane and look toward -z.
+                let origin = t * &Tuple::new_point(x_world, y_world, 0.0);
+                let direction = (t * &Tuple::new_vector(0.0, 0.0, -1.0)).normalized();
+                Some(Ray::new(origin, direction))
+            }
+            Projection::Equirectangular => {
+                // Coordinates are longitude and latitude.  The canvas center
+                // looks toward -z.
+                let (lon, lat) = (x_world, y_world);
+                let direction =
+                    Tuple::new_vector(lon.sin() * lat.cos(), lat.sin(), -lon.cos() * lat.cos());
+                Some(Ray::new(t * &ORIGIN, (t * &direction).normalized()))
+            }
+            Projection::Fisheye { field_of_view } => {
+                let r = x_world.hypot(y_world);
+                if r > 1.0 {
+                    return None;
+                }
+                let theta = r * field_of_view / 2.0;
+                let phi = y_world.atan2(x_world);
+                let direction = Tuple::new_vector(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                );
+                Some(Ray::new(t * &ORIGIN, (t * &direction).normalized()))
+            }
+        }
     }
 }
 
@@ -121,7 +466,9 @@ mod tests {
     use std::f64::consts::PI;
 
     use super::*;
-    use crate::color::Color;
+    use crate::background::Background;
+    use crate::color::{self, Color};
+    use crate::shape::Motion;
     use crate::transform;
     use crate::util;
     use crate::world::test_util;
@@ -132,7 +479,7 @@ mod tests {
         assert_eq!(c.hsize(), 160);
         assert_eq!(c.vsize(), 120);
         assert_eq!(c.field_of_view(), PI / 2.0);
-        assert_eq!(c.transform, Matrix::new_4x4_identity());
+        assert_eq!(*c.transform(), Matrix::new_4x4_identity());
     }
 
     #[test]
@@ -152,7 +499,7 @@ mod tests {
         let c = Camera::new(201, 101, PI / 2.0);
         assert_eq!(
             c.ray_for_pixel(100, 50),
-            Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, -1.0))
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)))
         );
     }
 
@@ -161,21 +508,264 @@ mod tests {
         let c = Camera::new(201, 101, PI / 2.0);
         assert_eq!(
             c.ray_for_pixel(0, 0),
-            Ray::from_triplets((0.0, 0.0, 0.0), (0.66519, 0.33259, -0.66851))
+            Some(Ray::from_triplets(
+                (0.0, 0.0, 0.0),
+                (0.66519, 0.33259, -0.66851)
+            ))
         );
     }
 
     #[test]
     fn constructing_ray_when_camera_transformed() {
         let mut c = Camera::new(201, 101, PI / 2.0);
-        c.transform = &transform::rotation_y(PI / 4.0) * &transform::translation(0.0, -2.0, 5.0);
+        c.set_transform(
+            Transform::identity()
+                .translate(0.0, -2.0, 5.0)
+                .rotate_y(PI / 4.0),
+        );
         assert_eq!(
             c.ray_for_pixel(100, 50),
-            Ray::from_triplets(
+            Some(Ray::from_triplets(
                 (0.0, 2.0, -5.0),
                 (2_f64.sqrt() / 2.0, 0.0, -2_f64.sqrt() / 2.0)
-            )
+            ))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn orthographic_camera_has_no_field_of_view() {
+        Camera::orthographic(200, 100, 4.0, 2.0, Matrix::new_4x4_identity()).field_of_view();
+    }
+
+    #[test]
+    fn constructing_orthographic_camera() {
+        let c = Camera::orthographic(200, 100, 4.0, 2.0, Matrix::new_4x4_identity());
+        assert_eq!(
+            *c.projection(),
+            Projection::Orthographic {
+                width: 4.0,
+                height: 2.0
+            }
+        );
+        assert!(util::nearly_equal(c.pixel_size(), 0.02));
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::orthographic(201, 101, 4.0, 2.0, Matrix::new_4x4_identity());
+        assert_eq!(
+            c.ray_for_pixel(100, 50),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(0.0, 0.0),
+            Some(Ray::from_triplets((2.0, 1.0, 0.0), (0.0, 0.0, -1.0)))
+        );
+    }
+
+    #[test]
+    fn orthographic_view_may_stretch_pixels() {
+        let c = Camera::orthographic(100, 100, 4.0, 2.0, Matrix::new_4x4_identity());
+        assert_eq!(
+            c.ray_for_point(0.0, 0.0),
+            Some(Ray::from_triplets((2.0, 1.0, 0.0), (0.0, 0.0, -1.0)))
+        );
+    }
+
+    #[test]
+    fn orthographic_rays_when_camera_transformed() {
+        let c = Camera::orthographic(
+            201,
+            101,
+            4.0,
+            2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        assert_eq!(
+            c.ray_for_pixel(100, 50),
+            Some(Ray::from_triplets((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)))
+        );
+        // The camera looks toward +z, so its left is -x.
+        assert_eq!(
+            c.ray_for_point(0.0, 0.0),
+            Some(Ray::from_triplets((-2.0, 1.0, -5.0), (0.0, 0.0, 1.0)))
+        );
+    }
+
+    #[test]
+    fn resizing_orthographic_camera_keeps_width() {
+        let c =
+            Camera::orthographic(100, 100, 4.0, 4.0, Matrix::new_4x4_identity()).resized(200, 50);
+        assert_eq!(
+            *c.projection(),
+            Projection::Orthographic {
+                width: 4.0,
+                height: 1.0
+            }
+        );
+        assert!(util::nearly_equal(c.pixel_size(), 0.02));
+    }
+
+    #[test]
+    fn rendering_world_with_orthographic_camera() {
+        let w = test_util::default_world();
+        let c = Camera::orthographic(
+            11,
+            11,
+            2.5,
+            2.5,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let img = c.render(&w);
+        assert_eq!(*img.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(*img.get(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn equirectangular_rays_cover_all_directions() {
+        let c = Camera::with_projection(
+            360,
+            180,
+            Projection::Equirectangular,
+            Matrix::new_4x4_identity(),
+        );
+        assert_eq!(c.field_of_view(), 2.0 * PI);
+        assert_eq!(
+            c.ray_for_point(180.0, 90.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(90.0, 90.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(0.0, 90.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(180.0, 0.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 1.0, 0.0)))
+        );
+    }
+
+    #[test]
+    fn equirectangular_rays_when_camera_transformed() {
+        let c = Camera::with_projection(
+            360,
+            180,
+            Projection::Equirectangular,
+            transform::view(
+                &Tuple::new_point(1.0, 2.0, 3.0),
+                &Tuple::new_point(1.0, 2.0, 4.0),
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        assert_eq!(
+            c.ray_for_point(180.0, 90.0),
+            Some(Ray::from_triplets((1.0, 2.0, 3.0), (0.0, 0.0, 1.0)))
+        );
+    }
+
+    #[test]
+    fn fisheye_angle_grows_with_distance_from_center() {
+        let c = Camera::with_projection(
+            100,
+            100,
+            Projection::Fisheye { field_of_view: PI },
+            Matrix::new_4x4_identity(),
+        );
+        assert_eq!(
+            c.ray_for_point(50.0, 50.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(100.0, 50.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (-1.0, 0.0, 0.0)))
+        );
+        assert_eq!(
+            c.ray_for_point(50.0, 25.0),
+            Some(Ray::from_triplets(
+                (0.0, 0.0, 0.0),
+                (0.0, 2_f64.sqrt() / 2.0, -2_f64.sqrt() / 2.0)
+            ))
+        );
+    }
+
+    #[test]
+    fn fisheye_field_of_view_may_exceed_half_sphere() {
+        let c = Camera::with_projection(
+            200,
+            100,
+            Projection::Fisheye {
+                field_of_view: 2.0 * PI,
+            },
+            Matrix::new_4x4_identity(),
+        );
+        assert_eq!(
+            c.ray_for_point(100.0, 100.0),
+            Some(Ray::from_triplets((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)))
+        );
+    }
+
+    #[test]
+    fn fisheye_has_no_ray_outside_image_circle() {
+        let c = Camera::with_projection(
+            20,
+            20,
+            Projection::Fisheye { field_of_view: PI },
+            Matrix::new_4x4_identity(),
+        );
+        assert_eq!(c.ray_for_pixel(0, 0), None);
+        let mut w = test_util::default_world();
+        w.background = Background::Color(color::WHITE);
+        let img = c.render(&w);
+        assert_eq!(*img.get(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn moving_shapes_blur_while_shutter_is_open() {
+        let mut w = test_util::default_world();
+        w.objects.truncate(1);
+        w.objects[0].motion = Some(Motion::linear(
+            transform::translation(-2.0, 0.0, 0.0),
+            transform::translation(2.0, 0.0, 0.0),
+        ));
+        let mut c = Camera::with_transform(
+            5,
+            5,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
         );
+        let options = RenderOptions {
+            samples: 16,
+            ..RenderOptions::default()
+        };
+
+        // With the shutter open only at time 0, the sphere is on the side.
+        let still = c.render_with(&w, &options);
+        assert_eq!(*still.get(2, 2), color::BLACK);
+
+        // The sphere crosses the canvas center while the shutter is open.
+        c.shutter_close = 1.0;
+        let blurred = c.render_with(&w, &options);
+        let middle = blurred.get(2, 2);
+        assert!(middle.red() > 0.0 && middle.red() < 0.38066);
+
+        // Resizing keeps the shutter.
+        assert_eq!(c.resized(10, 10).shutter_close, 1.0);
     }
 
     #[test]
@@ -194,4 +784,206 @@ mod tests {
         let img = c.render(&w);
         assert_eq!(*img.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn rendering_from_inside_object() {
+        let mut w = test_util::default_world();
+        for o in &mut w.objects {
+            o.material.ambient = 1.0;
+            o.material.diffuse = 0.0;
+            o.material.specular = 0.0;
+        }
+        // Inside the outer sphere, looking at the inner one, with the back of
+        // the outer sphere behind the camera.
+        let c = Camera::with_transform(
+            11,
+            11,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -0.75),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let img = c.render(&w);
+        assert_eq!(*img.get(5, 5), w.objects[1].material.color);
+        assert_eq!(*img.get(0, 0), w.objects[0].material.color);
+    }
+
+    #[test]
+    fn rendering_with_several_threads_gives_same_image() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            11,
+            7,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let single = c.render(&w);
+        let multi = c.render_with(
+            &w,
+            &RenderOptions {
+                threads: 3,
+                ..RenderOptions::default()
+            },
+        );
+        for y in 0..7 {
+            for x in 0..11 {
+                assert_eq!(single.get(x, y), multi.get(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn zero_samples_and_threads_count_as_one() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            5,
+            3,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let options = RenderOptions {
+            samples: 0,
+            threads: 0,
+            ..RenderOptions::default()
+        };
+        assert!(c.render_with(&w, &options) == c.render(&w));
+    }
+
+    #[test]
+    fn supersampling_averages_jittered_rays() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            5,
+            5,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let options = RenderOptions {
+            samples: 16,
+            ..RenderOptions::default()
+        };
+        let img = c.render_with(&w, &options);
+
+        // The sphere covers the center of the middle pixel but not its corners.
+        let middle = img.get(2, 2);
+        assert_ne!(*middle, Color::new(0.38066, 0.47583, 0.2855));
+        assert!(middle.red() > 0.0);
+        assert_eq!(*img.get(0, 0), color::BLACK);
+
+        // Rendering is deterministic.
+        assert_eq!(c.render_with(&w, &options).get(2, 2), middle);
+    }
+
+    #[test]
+    fn rendering_with_path_tracing() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            5,
+            5,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let options = RenderOptions {
+            samples: 4,
+            integrator: Integrator::path_tracing(),
+            ..RenderOptions::default()
+        };
+        let img = c.render_with(&w, &options);
+        assert!(img.get(2, 2).green() > 0.0);
+        assert_ne!(*img.get(2, 2), *c.render(&w).get(2, 2));
+        assert_eq!(*img.get(0, 0), color::BLACK);
+
+        let multi = c.render_with(
+            &w,
+            &RenderOptions {
+                threads: 2,
+                ..options
+            },
+        );
+        assert_eq!(multi.get(2, 2), img.get(2, 2));
+    }
+
+    #[test]
+    fn picking_pixels() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            11,
+            11,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let hit = c.pick(&w, 5, 5).unwrap();
+        assert_eq!(hit.index, 0);
+        assert_eq!(hit.distance, 4.0);
+        assert_eq!(hit.point, Tuple::new_point(0.0, 0.0, -1.0));
+        assert_eq!(hit.normal, Tuple::new_vector(0.0, 0.0, -1.0));
+        assert_eq!(c.pick(&w, 0, 0), None);
+        assert_eq!(c.pick(&w, 11, 5), None);
+        assert_eq!(c.pick(&w, 5, 11), None);
+    }
+
+    #[test]
+    fn rendering_passes() {
+        let w = test_util::default_world();
+        let c = Camera::with_transform(
+            11,
+            11,
+            PI / 2.0,
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let options = RenderOptions {
+            samples: 4,
+            threads: 2,
+            ..RenderOptions::default()
+        };
+        let passes = c.render_passes(
+            &w,
+            &options,
+            &[Aov::ObjectId, Aov::Depth, Aov::Direct, Aov::Indirect],
+        );
+        assert!(passes.beauty == c.render_with(&w, &options));
+        assert_eq!(
+            passes.aovs.iter().map(|(a, _)| *a).collect::<Vec<_>>(),
+            vec![Aov::ObjectId, Aov::Depth, Aov::Direct, Aov::Indirect]
+        );
+        assert!(passes.get(Aov::Normal).is_none());
+
+        let depth = passes.get(Aov::Depth).unwrap();
+        assert_eq!(*depth.get(5, 5), Color::new(4.0, 4.0, 4.0));
+        assert_eq!(depth.get(0, 0).red(), f64::INFINITY);
+        let id = passes.get(Aov::ObjectId).unwrap();
+        assert_eq!(*id.get(5, 5), color::BLACK);
+        assert_eq!(*id.get(0, 0), Color::new(-1.0, -1.0, -1.0));
+        // Ambient light is indirect.
+        let direct = passes.get(Aov::Direct).unwrap().get(5, 5);
+        let indirect = passes.get(Aov::Indirect).unwrap().get(5, 5);
+        assert_eq!(*indirect, Color::new(0.08, 0.1, 0.06));
+        assert_eq!(&(direct + indirect), passes.beauty.get(5, 5));
+    }
 }
@@ -2,14 +2,16 @@
 //!
 //! TRTC chapter 2
 
-use std::io::{self, BufWriter, Write};
+use std::io::{self, BufWriter, Read, Write};
 
 use crate::color::{self, Color};
+use crate::error::Error;
 
 const PPM_MAX_COLOR_VALUE: i32 = 255;
 const PPM_MAX_CHAR_PER_LINE: usize = 70;
 
 /// A 2D grid of pixels.
+#[derive(Clone, PartialEq)]
 pub struct Canvas {
     width: usize,
     height: usize,
@@ -45,6 +47,34 @@ impl Canvas {
         self.pixels[y * self.width + x] = color.clone();
     }
 
+    /// Same as `get()`, returning an error instead of panicking if `(x, y)` is
+    /// outside this canvas.
+    pub fn try_get(&self, x: usize, y: usize) -> Result<&Color, Error> {
+        self.check_bounds(x, y)?;
+        Ok(self.get(x, y))
+    }
+
+    /// Same as `set()`, returning an error instead of panicking if `(x, y)` is
+    /// outside this canvas.
+    pub fn try_set(&mut self, x: usize, y: usize, color: &Color) -> Result<(), Error> {
+        self.check_bounds(x, y)?;
+        self.set(x, y, color);
+        Ok(())
+    }
+
+    fn check_bounds(&self, x: usize, y: usize) -> Result<(), Error> {
+        if x < self.width && y < self.height {
+            Ok(())
+        } else {
+            Err(Error::PixelOutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+
     /// Set all pixels to specified color.
     pub fn fill(&mut self, color: &Color) {
         for c in &mut self.pixels {
@@ -52,19 +82,157 @@ impl Canvas {
         }
     }
 
+    /// Import a canvas from plain (P3) or raw (P6) PPM format.
+    ///
+    /// Color values are scaled to `[0, 1]` according to the maximum color value
+    /// of the file.
+    pub fn from_ppm<R: Read>(mut reader: R) -> io::Result<Canvas> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut parser = ImageParser {
+            data: &data,
+            pos: 0,
+            invalid: invalid_ppm,
+        };
+
+        let magic = parser.token()?;
+        if magic != b"P3" && magic != b"P6" {
+            return Err(invalid_ppm("unsupported magic number"));
+        }
+        let width = parser.number()?;
+        let height = parser.number()?;
+        let max = parser.number()?;
+        if max == 0 || max > 65535 {
+            return Err(invalid_ppm("invalid maximum color value"));
+        }
+
+        if magic == b"P6" {
+            // A single whitespace separates the header from the pixels.
+            parser.pos += 1;
+        }
+        // Plain values take at least one byte.
+        let value_size = if magic == b"P6" && max >= 256 { 2 } else { 1 };
+        parser
+            .check_size(width, height, 3 * value_size)
+            .map_err(invalid_ppm)?;
+
+        let mut canvas = Canvas::new(width, height);
+        let mut channels = [0.0; 3];
+        for pixel in &mut canvas.pixels {
+            for c in &mut channels {
+                let value = if magic == b"P3" {
+                    parser.number()?
+                } else if max < 256 {
+                    parser.byte()? as usize
+                } else {
+                    (parser.byte()? as usize) << 8 | parser.byte()? as usize
+                };
+                *c = value as f64 / max as f64;
+            }
+            *pixel = Color::new(channels[0], channels[1], channels[2]);
+        }
+        Ok(canvas)
+    }
+
+    /// Import a canvas from PFM (portable float map) format, e.g. an HDR
+    /// environment map.  Color values are not clamped.
+    pub fn from_pfm<R: Read>(mut reader: R) -> io::Result<Canvas> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut parser = ImageParser {
+            data: &data,
+            pos: 0,
+            invalid: invalid_pfm,
+        };
+
+        let nb_channels = match parser.token()? {
+            b"PF" => 3,
+            b"Pf" => 1,
+            _ => return Err(invalid_pfm("unsupported magic number")),
+        };
+        let width = parser.number()?;
+        let height = parser.number()?;
+        // The sign of the scale gives the endianness.
+        let scale = std::str::from_utf8(parser.token()?)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .ok_or_else(|| invalid_pfm("invalid scale"))?;
+        parser.pos += 1;
+        parser
+            .check_size(width, height, 4 * nb_channels)
+            .map_err(invalid_pfm)?;
+
+        let mut canvas = Canvas::new(width, height);
+        let mut channels = [0.0; 3];
+        // Rows are stored from bottom to top.
+        for y in (0..height).rev() {
+            for x in 0..width {
+                for c in channels.iter_mut().take(nb_channels) {
+                    let bytes = [
+                        parser.byte()?,
+                        parser.byte()?,
+                        parser.byte()?,
+                        parser.byte()?,
+                    ];
+                    *c = if scale < 0.0 {
+                        f32::from_le_bytes(bytes)
+                    } else {
+                        f32::from_be_bytes(bytes)
+                    } as f64;
+                }
+                if nb_channels == 1 {
+                    channels = [channels[0]; 3];
+                }
+                canvas.set(x, y, &Color::new(channels[0], channels[1], channels[2]));
+            }
+        }
+        Ok(canvas)
+    }
+
     /// Export this canvas to PPM format.
     pub fn to_ppm<W: Write>(&self, writer: W) -> io::Result<()> {
         let mut bw = BufWriter::new(writer);
-        self.write_ppm_header(&mut bw)?;
+        self.write_ppm_header(&mut bw, "P3")?;
         self.write_ppm_data(&mut bw)?;
         bw.flush()
     }
 
-    fn write_ppm_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+    /// Export this canvas to binary ("raw") PPM format.
+    ///
+    /// Raw PPM files are several times smaller than plain ones.
+    pub fn to_raw_ppm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut bw = BufWriter::new(writer);
+        self.write_ppm_header(&mut bw, "P6")?;
+        for color in &self.pixels {
+            for i in 0..3 {
+                bw.write_all(&[scale_and_clamp_color(color.channel(i)) as u8])?;
+            }
+        }
+        bw.flush()
+    }
+
+    /// Export this canvas to little-endian PFM (portable float map) format.
+    /// Color values are not clamped, e.g. for HDR images or depth passes.
+    pub fn to_pfm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut bw = BufWriter::new(writer);
+        write!(bw, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // Rows are stored from bottom to top.
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.get(x, y);
+                for i in 0..3 {
+                    bw.write_all(&(color.channel(i) as f32).to_le_bytes())?;
+                }
+            }
+        }
+        bw.flush()
+    }
+
+    fn write_ppm_header<W: Write>(&self, writer: &mut W, magic: &str) -> io::Result<()> {
         write!(
             writer,
-            "P3\n{} {}\n{}\n",
-            self.width, self.height, PPM_MAX_COLOR_VALUE
+            "{}\n{} {}\n{}\n",
+            magic, self.width, self.height, PPM_MAX_COLOR_VALUE
         )
     }
 
@@ -109,16 +277,91 @@ impl Canvas {
     }
 }
 
+/// Reads PPM and PFM header fields and pixels.
+struct ImageParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Builds errors for the format being parsed.
+    invalid: fn(&str) -> io::Error,
+}
+
+impl<'a> ImageParser<'a> {
+    /// Returns next whitespace-separated token, skipping comments.
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err((self.invalid)("unexpected end of file")),
+            }
+        }
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        Ok(&self.data[start..self.pos])
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| (self.invalid)("invalid number"))
+    }
+
+    /// Fails if a `width` x `height` image is empty or cannot fit the rest of
+    /// the data with at least `pixel_size` bytes per pixel.
+    fn check_size(
+        &self,
+        width: usize,
+        height: usize,
+        pixel_size: usize,
+    ) -> Result<(), &'static str> {
+        if width == 0 || height == 0 {
+            return Err("empty image");
+        }
+        let remaining = self.data.len().saturating_sub(self.pos);
+        match width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(pixel_size))
+        {
+            Some(size) if size <= remaining => Ok(()),
+            _ => Err("image size exceeds data"),
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let b = self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| (self.invalid)("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(*b)
+    }
+}
+
+fn invalid_pfm(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid PFM: {}", msg))
+}
+
+fn invalid_ppm(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid PPM: {}", msg))
+}
+
 /// Convert a `Color` component to a PPM color value.
 fn scale_and_clamp_color(n: f64) -> i32 {
     let scaled = (n * PPM_MAX_COLOR_VALUE as f64).round() as i32;
-    if scaled < 0 {
-        0
-    } else if scaled > PPM_MAX_COLOR_VALUE {
-        PPM_MAX_COLOR_VALUE
-    } else {
-        scaled
-    }
+    scaled.clamp(0, PPM_MAX_COLOR_VALUE)
 }
 
 #[cfg(test)]
@@ -143,12 +386,28 @@ mod tests {
         assert_eq!(*canvas.get(1, 2), color::RED);
     }
 
+    #[test]
+    fn accessing_pixel_outside_canvas() {
+        let mut canvas = Canvas::new(10, 20);
+        assert_eq!(canvas.try_set(9, 19, &color::RED), Ok(()));
+        assert_eq!(canvas.try_get(9, 19), Ok(&color::RED));
+        let e = Error::PixelOutOfBounds {
+            x: 10,
+            y: 0,
+            width: 10,
+            height: 20,
+        };
+        assert_eq!(canvas.try_get(10, 0), Err(e.clone()));
+        assert_eq!(canvas.try_set(10, 0, &color::RED), Err(e));
+        assert!(canvas.try_get(0, 20).is_err());
+    }
+
     #[test]
     fn constructing_ppm_header_u8() {
         let canvas = Canvas::new(5, 3);
         let want = "P3\n5 3\n255\n";
         let mut got = Vec::new();
-        canvas.write_ppm_header(&mut got).unwrap();
+        canvas.write_ppm_header(&mut got, "P3").unwrap();
         assert_eq!(String::from_utf8(got).unwrap(), want);
     }
 
@@ -166,6 +425,18 @@ mod tests {
         assert_eq!(String::from_utf8(got).unwrap(), want);
     }
 
+    #[test]
+    fn exporting_raw_ppm() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set(0, 0, &Color::new(1.5, 0.0, 0.5));
+        canvas.set(1, 0, &Color::new(-0.5, 1.0, 0.0));
+        let mut got = Vec::new();
+        canvas.to_raw_ppm(&mut got).unwrap();
+        let mut want = b"P6\n2 1\n255\n".to_vec();
+        want.extend_from_slice(&[255, 0, 128, 0, 255, 0]);
+        assert_eq!(got, want);
+    }
+
     #[test]
     fn splitting_long_ppm_lines() {
         let mut canvas = Canvas::new(10, 2);
@@ -178,4 +449,115 @@ mod tests {
         canvas.write_ppm_data(&mut got).unwrap();
         assert_eq!(String::from_utf8(got).unwrap(), want);
     }
+
+    #[test]
+    fn reading_plain_ppm() {
+        let ppm = "P3\n# a comment\n2 2\n10\n\
+                   10 0 0  0 5 0\n\
+                   0 0 10  10 10 10\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.width(), 2);
+        assert_eq!(canvas.height(), 2);
+        assert_eq!(*canvas.get(0, 0), color::RED);
+        assert_eq!(*canvas.get(1, 0), Color::new(0.0, 0.5, 0.0));
+        assert_eq!(*canvas.get(0, 1), color::BLUE);
+        assert_eq!(*canvas.get(1, 1), color::WHITE);
+    }
+
+    #[test]
+    fn reading_raw_ppm_written_by_canvas() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set(0, 0, &Color::new(1.0, 0.2, 0.4));
+        canvas.set(2, 1, &Color::new(0.0, 0.6, 0.8));
+        let mut ppm = Vec::new();
+        canvas.to_raw_ppm(&mut ppm).unwrap();
+        assert!(Canvas::from_ppm(&ppm[..]).unwrap() == canvas);
+    }
+
+    #[test]
+    fn reading_16_bit_raw_ppm() {
+        let mut ppm = b"P6 1 1 65535\n".to_vec();
+        ppm.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        let canvas = Canvas::from_ppm(&ppm[..]).unwrap();
+        assert_eq!(*canvas.get(0, 0), Color::new(1.0, 32768.0 / 65535.0, 0.0));
+    }
+
+    #[test]
+    fn reading_invalid_ppm() {
+        for ppm in &["P2\n1 1\n255\n0\n", "P3\n1 1\n255\n0 0\n", "P3\n1 x\n255\n"] {
+            let err = Canvas::from_ppm(ppm.as_bytes()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let cases = [
+            ("P3\n0 4\n255\n", "invalid PPM: empty image"),
+            ("P3\n2 0\n255\n", "invalid PPM: empty image"),
+            (
+                "P6\n100000 100000\n255\n\0\0\0",
+                "invalid PPM: image size exceeds data",
+            ),
+            (
+                "P6\n18446744073709551615 2\n255\n",
+                "invalid PPM: image size exceeds data",
+            ),
+        ];
+        for (ppm, message) in &cases {
+            let err = Canvas::from_ppm(ppm.as_bytes()).err().unwrap();
+            assert_eq!(err.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn reading_invalid_pfm() {
+        let cases = [
+            ("PF\n0 2\n-1.0\n", "invalid PFM: empty image"),
+            (
+                "Pf\n2 2\n-1.0\n\0\0\0\0",
+                "invalid PFM: image size exceeds data",
+            ),
+            (
+                "PF\n4294967296 4294967296\n-1.0\n",
+                "invalid PFM: image size exceeds data",
+            ),
+            ("PF\n2", "invalid PFM: unexpected end of file"),
+            ("PF\n2 x\n-1.0\n", "invalid PFM: invalid number"),
+        ];
+        for (pfm, message) in &cases {
+            let err = Canvas::from_pfm(pfm.as_bytes()).err().unwrap();
+            assert_eq!(err.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn reading_pfm() {
+        let mut pfm = b"PF\n2 2\n-1.0\n".to_vec();
+        // Bottom row first.
+        for v in &[
+            0.5_f32, 0.0, 0.0, 4.0, 2.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 8.0,
+        ] {
+            pfm.extend_from_slice(&v.to_le_bytes());
+        }
+        let canvas = Canvas::from_pfm(&pfm[..]).unwrap();
+        assert_eq!(*canvas.get(0, 1), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(*canvas.get(1, 1), Color::new(4.0, 2.0, 1.0));
+        assert_eq!(*canvas.get(1, 0), Color::new(0.0, 0.0, 8.0));
+    }
+
+    #[test]
+    fn reading_big_endian_grayscale_pfm() {
+        let mut pfm = b"Pf 1 1 1.0\n".to_vec();
+        pfm.extend_from_slice(&3.0_f32.to_be_bytes());
+        let canvas = Canvas::from_pfm(&pfm[..]).unwrap();
+        assert_eq!(*canvas.get(0, 0), Color::new(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set(0, 0, &Color::new(-1.0, 0.5, 100.0));
+        canvas.set(2, 1, &Color::new(0.25, 2.0, 3.0));
+        let mut pfm = Vec::new();
+        canvas.to_pfm(&mut pfm).unwrap();
+        assert!(pfm.starts_with(b"PF\n3 2\n-1.0\n"));
+        assert!(Canvas::from_pfm(&pfm[..]).unwrap() == canvas);
+    }
 }
@@ -29,6 +29,16 @@ impl Color {
     pub fn channel(&self, i: usize) -> f64 {
         self.rgb[i]
     }
+
+    /// Returns perceived brightness of this color (Rec. 709 weights).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.rgb[0] + 0.7152 * self.rgb[1] + 0.0722 * self.rgb[2]
+    }
+
+    /// Linear interpolation between this color (`t == 0`) and `o` (`t == 1`).
+    pub fn interpolated(&self, o: &Color, t: f64) -> Color {
+        self + &(&(o - self) * t)
+    }
 }
 
 impl PartialEq for Color {
@@ -135,4 +145,18 @@ mod tests {
             Color::new(0.9, 0.2, 0.04000000000000001)
         );
     }
+
+    #[test]
+    fn interpolating_colors() {
+        let c1 = Color::new(0.2, 0.4, 1.0);
+        let c2 = Color::new(0.6, 0.0, 1.0);
+        assert_eq!(c1.interpolated(&c2, 0.25), Color::new(0.3, 0.3, 1.0));
+    }
+
+    #[test]
+    fn luminance_of_colors() {
+        assert!((crate::color::WHITE.luminance() - 1.0).abs() < 1e-12);
+        assert!(GREEN.luminance() > RED.luminance());
+        assert!(RED.luminance() > BLUE.luminance());
+    }
 }
@@ -0,0 +1,109 @@
+//! Error type of fallible operations.
+
+use std::fmt;
+
+/// Error returned by fallible variants of operations panicking on invalid
+/// input, e.g. `Matrix::try_inverted()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A matrix with determinant 0 has no inverse.
+    SingularMatrix,
+    /// Only square matrices have an inverse.
+    NotSquare { nrows: usize, ncols: usize },
+    /// A 4x4 matrix is not an affine transformation: its last row is not
+    /// `[0, 0, 0, 1]`.
+    NotAffine,
+    /// Pixel `(x, y)` is outside a `width` x `height` canvas.
+    PixelOutOfBounds {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// A motion has no keyframes.
+    NoKeyframes,
+    /// A keyframe time is infinite or NaN.
+    NonFiniteTime,
+    /// Consecutive keyframes of a motion mirror one another, so the
+    /// transformation interpolated between them is not invertible at some time.
+    MirroringMotion,
+    /// An animated shape is not in the world of `count` shapes.
+    NoSuchShape { index: usize, count: usize },
+    /// The transformation of a moving shape is animated, though its motion
+    /// overrides it.
+    TransformOverriddenByMotion,
+    /// A shape without an inside, such as a plane or a disk, is filled with a
+    /// medium.
+    OpenMedium,
+    /// Shape at `index` in `World::objects`, whose object is named `kind`,
+    /// cannot be rendered because of `error`.
+    InvalidShape {
+        index: usize,
+        kind: &'static str,
+        error: Box<Error>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::SingularMatrix => write!(f, "transformation is not invertible"),
+            Error::NotSquare { nrows, ncols } => {
+                write!(f, "{}x{} matrix is not square", nrows, ncols)
+            }
+            Error::NotAffine => write!(f, "transformation is not affine"),
+            Error::PixelOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "pixel ({}, {}) is outside {}x{} canvas",
+                x, y, width, height
+            ),
+            Error::NoKeyframes => write!(f, "motion has no keyframes"),
+            Error::NonFiniteTime => write!(f, "keyframe time is not finite"),
+            Error::MirroringMotion => write!(f, "motion mirrors the shape between keyframes"),
+            Error::NoSuchShape { index, count } => {
+                write!(f, "no shape {} in a world of {} shapes", index, count)
+            }
+            Error::TransformOverriddenByMotion => {
+                write!(f, "transformation is overridden by motion")
+            }
+            Error::OpenMedium => {
+                write!(f, "only closed shapes can be filled with a medium")
+            }
+            Error::InvalidShape { index, kind, error } => {
+                write!(f, "shape {} ({}): {}", index, kind, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_messages() {
+        let e = Error::InvalidShape {
+            index: 2,
+            kind: "torus",
+            error: Box::new(Error::SingularMatrix),
+        };
+        assert_eq!(
+            e.to_string(),
+            "shape 2 (torus): transformation is not invertible"
+        );
+        let e = Error::PixelOutOfBounds {
+            x: 3,
+            y: 10,
+            width: 4,
+            height: 10,
+        };
+        assert_eq!(e.to_string(), "pixel (3, 10) is outside 4x10 canvas");
+    }
+}
@@ -0,0 +1,786 @@
+//! Light transport algorithms computing the color seen along a ray.
+
+use std::f64::consts::PI;
+
+use crate::color::{self, Color};
+use crate::occlusion::AmbientOcclusion;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::shape::{Computations, IntersectionList, Shape};
+use crate::tuple::Tuple;
+use crate::world::{MediumSegment, World};
+
+/// Paths are never terminated by Russian roulette before this many bounces.
+const MIN_BOUNCES: usize = 3;
+
+/// How the color of a ray is computed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Integrator {
+    /// Phong model at the first hit, with a constant ambient term standing in
+    /// for indirect lighting.  See `World::color_at()`.
+    #[default]
+    Phong,
+
+    /// Grayscale ambient occlusion at the first hit: white where nothing
+    /// occludes, black where everything does.  Rays hitting nothing are white.
+    /// Uses the settings of the world if any, else the default ones.  Meant for
+    /// tuning ambient occlusion and checking geometry.
+    AmbientOcclusion,
+
+    /// Unbiased Monte Carlo path tracing: interreflections, soft lighting from
+    /// the background and emissive shapes, and no ambient term.  Noisy unless many
+    /// samples per pixel are used.
+    ///
+    /// Surfaces scatter light following `Material::bsdf()`: physical materials
+    /// use their own model, Phong ones are Lambertian with albedo
+    /// `color * diffuse`.  Point lights have no distance falloff, as in the
+    /// Phong model, and a white Lambertian surface facing a light receives the
+    /// same direct lighting as with `Phong`.
+    ///
+    /// Bounded emissive shapes are sampled as area lights, combined with BSDF
+    /// sampling by multiple importance sampling.  Unbounded ones are only
+    /// found by BSDF sampling.
+    ///
+    /// Media scatter light any number of times.  Fog only dims and tints
+    /// paths, without scattering.
+    PathTracing {
+        /// Hard limit on the number of bounces.  Russian roulette usually
+        /// terminates paths well before.
+        max_bounces: usize,
+    },
+}
+
+impl Integrator {
+    /// Creates a path tracer with a bounce limit high enough not to bias renders
+    /// of usual scenes.
+    pub fn path_tracing() -> Integrator {
+        Integrator::PathTracing { max_bounces: 64 }
+    }
+
+    /// Computes color seen along `ray` in `world`, using `rng` for random decisions.
+    pub fn color_at(&self, world: &World, ray: &Ray, rng: &mut Rng) -> Color {
+        self.radiance_at(world, ray, rng).total()
+    }
+
+    /// Same as `color_at()`, split into direct and indirect lighting.
+    pub fn radiance_at(&self, world: &World, ray: &Ray, rng: &mut Rng) -> Radiance {
+        self.radiance_with_emitters(world, &world.emitters(), ray, rng)
+    }
+
+    /// Same as `radiance_at()` with `world.emitters()` computed beforehand, to
+    /// share it between the rays of a render.
+    pub(crate) fn radiance_with_emitters(
+        &self,
+        world: &World,
+        emitters: &[&Shape],
+        ray: &Ray,
+        rng: &mut Rng,
+    ) -> Radiance {
+        match self {
+            Integrator::Phong => world.radiance_at(ray, rng),
+            Integrator::AmbientOcclusion => Radiance {
+                direct: ambient_occlusion(world, ray, rng),
+                indirect: color::BLACK,
+            },
+            Integrator::PathTracing { max_bounces } => {
+                path_trace(world, emitters, ray, rng, *max_bounces)
+            }
+        }
+    }
+}
+
+/// Light arriving along a ray, split by the number of times it was scattered
+/// by surfaces or media on its way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Radiance {
+    /// Light emitted toward the eye (including the background and fog), or
+    /// scattered once toward it.
+    pub direct: Color,
+    /// Light scattered several times, including ambient lighting.
+    pub indirect: Color,
+}
+
+impl Default for Radiance {
+    fn default() -> Self {
+        Radiance {
+            direct: color::BLACK,
+            indirect: color::BLACK,
+        }
+    }
+}
+
+impl Radiance {
+    pub fn total(&self) -> Color {
+        &self.direct + &self.indirect
+    }
+
+    /// Adds light `c` scattered `scatterings` times.
+    pub(crate) fn add(&mut self, scatterings: usize, c: &Color) {
+        if scatterings <= 1 {
+            self.direct = &self.direct + c;
+        } else {
+            self.indirect = &self.indirect + c;
+        }
+    }
+}
+
+fn ambient_occlusion(world: &World, ray: &Ray, rng: &mut Rng) -> Color {
+    let xs = world.intersects(ray);
+    let hit = match xs.hit() {
+        Some(hit) => hit,
+        None => return color::WHITE,
+    };
+    let comps = hit.prepare_computations(ray);
+    let default = AmbientOcclusion::default();
+    let ao = world.ambient_occlusion.as_ref().unwrap_or(&default);
+    let v = ao.visibility(world, &comps.over_point, &comps.normal_vec, comps.time, rng);
+    Color::new(v, v, v)
+}
+
+fn path_trace(
+    world: &World,
+    emitters: &[&Shape],
+    ray: &Ray,
+    rng: &mut Rng,
+    max_bounces: usize,
+) -> Radiance {
+    let mut radiance = Radiance::default();
+    let mut throughput = color::WHITE;
+    let mut ray = ray.clone();
+    // Solid angle density of the BSDF sample `ray` follows, if any.
+    let mut bsdf_pdf = None;
+
+    for bounce in 0..=max_bounces {
+        let xs = world.intersects(&ray);
+        let distance = xs.hit().map_or(f64::INFINITY, |h| h.distance);
+        let scattering = sample_scattering(world, &ray, distance, rng);
+        if let Some(fog) = &world.fog {
+            // Fog absorbs light and scatters its color toward the eye.
+            let traveled = scattering.as_ref().map_or(distance, |s| s.0);
+            let speed = ray.direction().magnitude();
+            let dir = ray.direction().normalized();
+            let t = fog.transmittance(ray.origin(), &dir, traveled * speed);
+            radiance.add(bounce, &(&(&throughput * &fog.color) * (1.0 - t)));
+            throughput = &throughput * t;
+        }
+
+        // Scattering inside a medium.  With distance sampling, the
+        // transmittance over the pdf leaves the albedo of the medium.
+        if let Some((t, segment)) = scattering {
+            let pt = ray.position(t);
+            throughput = &(&throughput * &segment.shape.material.color) * segment.medium.albedo();
+            let to_light = world.transmittance(&pt, &world.light.position, ray.time());
+            // Isotropic phase function 1 / (4 pi) times irradiance pi * intensity.
+            let direct = &world.light.intensity * (to_light / 4.0);
+            radiance.add(bounce + 1, &(&throughput * &direct));
+            if bounce == max_bounces || !russian_roulette(&mut throughput, bounce, rng) {
+                break;
+            }
+            // Isotropic scattering: the phase function over the pdf is 1.
+            bsdf_pdf = None;
+            ray = Ray::new(pt, uniform_sample_sphere(rng)).with_time(ray.time());
+            continue;
+        }
+
+        let hit = match xs.hit() {
+            Some(hit) => hit,
+            None => {
+                let background = world.background.color_at(ray.direction());
+                radiance.add(bounce, &(&throughput * &background));
+                break;
+            }
+        };
+        let comps = hit.prepare_computations(&ray);
+        let m = &comps.object.material;
+
+        // Emitters found by BSDF sampling, weighted against light sampling.
+        if m.is_emissive() {
+            let weight = match bsdf_pdf {
+                Some(bsdf_pdf) if comps.object.object.is_bounded() => {
+                    let light_pdf = emitter_pdf(emitters, &comps, ray.origin());
+                    power_heuristic(bsdf_pdf, light_pdf)
+                }
+                _ => 1.0,
+            };
+            radiance.add(bounce, &(&(&throughput * &m.emission) * weight));
+        }
+
+        let color = comps.object.color_at(&comps.over_point, comps.time);
+        let bsdf = m.bsdf(&color);
+        let wo = comps.eye_vec.normalized();
+
+        // Next event estimation: direct lighting from the point light.
+        let light_vec = (&world.light.position - &comps.over_point).normalized();
+        let cos = Tuple::dot(&light_vec, &comps.normal_vec);
+        if cos > 0.0 {
+            let to_light =
+                world.transmittance(&comps.over_point, &world.light.position, comps.time);
+            let f = bsdf.eval(&wo, &light_vec, &comps.normal_vec);
+            let direct = &(&f * &world.light.intensity) * (PI * cos * to_light);
+            radiance.add(bounce + 1, &(&throughput * &direct));
+        }
+
+        // Next event estimation: direct lighting from a point on an emitter.
+        if let Some(light) = sample_emitter(world, emitters, &comps, rng) {
+            let cos = Tuple::dot(&light.wi, &comps.normal_vec);
+            let f = bsdf.eval(&wo, &light.wi, &comps.normal_vec);
+            let weight = power_heuristic(light.pdf, bsdf.pdf(&wo, &light.wi, &comps.normal_vec));
+            let direct = &(&f * &light.radiance) * (cos * weight / light.pdf);
+            radiance.add(bounce + 1, &(&throughput * &direct));
+        }
+
+        if bounce == max_bounces {
+            break;
+        }
+
+        // Indirect lighting.
+        let sample = match bsdf.sample(&wo, &comps.normal_vec, rng) {
+            Some(sample) => sample,
+            None => break,
+        };
+        throughput = &throughput * &sample.weight(&comps.normal_vec);
+        if !russian_roulette(&mut throughput, bounce, rng) {
+            break;
+        }
+        bsdf_pdf = Some(sample.pdf);
+        ray = Ray::new(comps.over_point.clone(), sample.wi).with_time(comps.time);
+    }
+
+    radiance
+}
+
+/// Randomly terminates paths carrying little light after `MIN_BOUNCES`,
+/// boosting the others to compensate.  Returns whether the path goes on.
+fn russian_roulette(throughput: &mut Color, bounce: usize, rng: &mut Rng) -> bool {
+    if bounce < MIN_BOUNCES {
+        return true;
+    }
+    let survival = max_channel(throughput).min(1.0);
+    if rng.next_f64() >= survival {
+        return false;
+    }
+    *throughput = &*throughput * (1.0 / survival);
+    true
+}
+
+/// Picks where `ray` is scattered by a medium before reaching `distance`, with
+/// a probability density proportional to the transmittance.  Returns `None` if
+/// the ray goes through.
+fn sample_scattering<'a>(
+    world: &'a World,
+    ray: &Ray,
+    distance: f64,
+    rng: &mut Rng,
+) -> Option<(f64, MediumSegment<'a>)> {
+    let speed = ray.direction().magnitude();
+    for segment in world.medium_segments(ray, distance) {
+        let extinction = segment.medium.extinction();
+        if extinction <= 0.0 {
+            continue;
+        }
+        let free_flight = -(1.0 - rng.next_f64()).ln() / extinction;
+        let t = segment.start + free_flight / speed;
+        if t < segment.end {
+            return Some((t, segment));
+        }
+    }
+    None
+}
+
+/// Light arriving at a point from a point picked on an emitter.
+struct EmitterSample {
+    /// Unit vector toward the emitter.
+    wi: Tuple,
+    radiance: Color,
+    /// Probability density per unit solid angle of picking `wi`.
+    pdf: f64,
+}
+
+/// Picks a point on one of `emitters` visible from the point described by
+/// `comps` and facing its normal.
+fn sample_emitter(
+    world: &World,
+    emitters: &[&Shape],
+    comps: &Computations,
+    rng: &mut Rng,
+) -> Option<EmitterSample> {
+    if emitters.is_empty() {
+        return None;
+    }
+    let i = ((rng.next_f64() * emitters.len() as f64) as usize).min(emitters.len() - 1);
+    let emitter = emitters[i];
+    let sample = emitter.sample_surface(comps.time, rng)?;
+    let to_light = &sample.point - &comps.over_point;
+    let distance = to_light.magnitude();
+    let wi = &to_light * (1.0 / distance);
+    let cos_light = Tuple::dot(&sample.normal, &wi).abs();
+    if cos_light <= 0.0 || Tuple::dot(&wi, &comps.normal_vec) <= 0.0 {
+        return None;
+    }
+    let to_light = world.transmittance(&comps.over_point, &sample.point, comps.time);
+    if to_light <= 0.0 {
+        return None;
+    }
+    Some(EmitterSample {
+        wi,
+        radiance: &emitter.material.emission * to_light,
+        pdf: sample.pdf * distance * distance / (cos_light * emitters.len() as f64),
+    })
+}
+
+/// Returns probability density per unit solid angle of `sample_emitter()`
+/// picking the point described by `comps` from `origin`.
+fn emitter_pdf(emitters: &[&Shape], comps: &Computations, origin: &Tuple) -> f64 {
+    let to_light = &comps.point - origin;
+    let distance2 = Tuple::dot(&to_light, &to_light);
+    let normal = comps.object.normal_at_time(&comps.point, comps.time);
+    let cos_light = Tuple::dot(&normal, &to_light.normalized()).abs();
+    if cos_light <= 0.0 {
+        return 0.0;
+    }
+    let area_pdf = comps.object.surface_pdf(&comps.point, comps.time);
+    area_pdf * distance2 / (cos_light * emitters.len() as f64)
+}
+
+/// Returns multiple importance sampling weight of a sample picked with density
+/// `pdf` that could also have been picked with density `other_pdf`.
+///
+/// See Veach, "Robust Monte Carlo Methods for Light Transport Simulation"
+/// (1997), section 9.2.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+fn max_channel(c: &Color) -> f64 {
+    c.red().max(c.green()).max(c.blue())
+}
+
+/// Returns a random unit vector in the hemisphere around unit vector `normal`,
+/// with a probability density proportional to the cosine of its angle with `normal`.
+pub fn cosine_sample_hemisphere(normal: &Tuple, rng: &mut Rng) -> Tuple {
+    let (t, b) = orthonormal_basis(normal);
+    // Uniform point on the unit disk, projected up to the hemisphere.
+    let phi = 2.0 * PI * rng.next_f64();
+    let r2 = rng.next_f64();
+    let r = r2.sqrt();
+    let z = (1.0 - r2).max(0.0).sqrt();
+    let dir = &(&(&t * (r * phi.cos())) + &(&b * (r * phi.sin()))) + &(normal * z);
+    dir.normalized()
+}
+
+/// Returns a random unit vector, all directions being equally likely.
+pub fn uniform_sample_sphere(rng: &mut Rng) -> Tuple {
+    let z = 1.0 - 2.0 * rng.next_f64();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_f64();
+    Tuple::new_vector(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Returns two unit vectors forming with unit vector `n` an orthonormal basis.
+///
+/// See Duff et al., "Building an Orthonormal Basis, Revisited" (2017).
+pub fn orthonormal_basis(n: &Tuple) -> (Tuple, Tuple) {
+    let sign = 1_f64.copysign(n.z());
+    let a = -1.0 / (sign + n.z());
+    let b = n.x() * n.y() * a;
+    (
+        Tuple::new_vector(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x()),
+        Tuple::new_vector(b, sign + n.y() * n.y() * a, -n.y()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::Background;
+    use crate::bsdf::Physical;
+    use crate::light::PointLight;
+    use crate::medium::{Fog, Medium};
+    use crate::shape::Object;
+    use crate::transform;
+    use crate::tuple::ORIGIN;
+    use crate::world::test_util;
+
+    fn average<F: FnMut(&mut Rng) -> Color>(n: usize, mut f: F) -> Color {
+        let mut rng = Rng::new(0);
+        let mut sum = color::BLACK;
+        for _ in 0..n {
+            sum = &sum + &f(&mut rng);
+        }
+        &sum * (1.0 / n as f64)
+    }
+
+    #[test]
+    fn building_orthonormal_basis() {
+        for n in &[
+            Tuple::new_vector(0.0, 0.0, 1.0),
+            Tuple::new_vector(0.0, 0.0, -1.0),
+            Tuple::new_vector(1.0, 2.0, 3.0).normalized(),
+            Tuple::new_vector(-0.5, 0.1, -0.2).normalized(),
+        ] {
+            let (t, b) = orthonormal_basis(n);
+            assert!((t.magnitude() - 1.0).abs() < 1e-9);
+            assert!((b.magnitude() - 1.0).abs() < 1e-9);
+            assert!(Tuple::dot(&t, &b).abs() < 1e-9);
+            assert!(Tuple::dot(&t, n).abs() < 1e-9);
+            assert!(Tuple::dot(&b, n).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn cosine_sampling_stays_in_hemisphere() {
+        let n = Tuple::new_vector(1.0, -1.0, 0.5).normalized();
+        let mut rng = Rng::new(1);
+        let mut mean_cos = 0.0;
+        for _ in 0..10000 {
+            let d = cosine_sample_hemisphere(&n, &mut rng);
+            assert!((d.magnitude() - 1.0).abs() < 1e-9);
+            let cos = Tuple::dot(&d, &n);
+            assert!(cos >= 0.0);
+            mean_cos += cos / 10000.0;
+        }
+        // The mean cosine of a cosine-weighted distribution is 2/3.
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn escaping_ray_sees_background() {
+        let mut w = test_util::default_world();
+        w.background = Background::Color(color::RED);
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 1.0, 0.0),
+        );
+        let c = Integrator::path_tracing().color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(c, color::RED);
+    }
+
+    #[test]
+    fn phong_integrator_is_world_color() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(
+            Integrator::Phong.color_at(&w, &r, &mut Rng::new(0)),
+            w.color_at(&r)
+        );
+    }
+
+    /// Returns a world with a single sphere of albedo `albedo` in a uniform
+    /// white environment, without light.
+    fn furnace(albedo: f64) -> World {
+        let mut sphere = Shape::new(Object::Sphere);
+        sphere.material.diffuse = albedo;
+        let mut w = World::new(PointLight::new(color::BLACK, ORIGIN), vec![sphere]);
+        w.background = Background::Color(color::WHITE);
+        w
+    }
+
+    #[test]
+    fn white_furnace_conserves_energy() {
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let w = furnace(1.0);
+        let c = average(100, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert_eq!(c, color::WHITE);
+        let w = furnace(0.5);
+        let c = average(100, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn escaping_secondary_rays_see_background() {
+        // Bounces off a white floor under a gradient sky see y = 2/3 on
+        // average (cosine-weighted), i.e. 5/6 of the way to the top color.
+        let mut floor = Shape::new(Object::Plane);
+        floor.material.diffuse = 1.0;
+        let mut w = World::new(PointLight::new(color::BLACK, ORIGIN), vec![floor]);
+        w.background = Background::Gradient {
+            top: color::RED,
+            bottom: color::BLACK,
+        };
+        let r = Ray::new(
+            Tuple::new_point(0.0, 1.0, 0.0),
+            Tuple::new_vector(0.0, -1.0, 0.0),
+        );
+        let c = Integrator::PathTracing { max_bounces: 1 }.color_at(&w, &r, &mut Rng::new(0));
+        assert!(c.red() >= 0.5 && c.green() == 0.0, "{:?}", c);
+        let c = average(1000, |rng| {
+            Integrator::PathTracing { max_bounces: 1 }.color_at(&w, &r, rng)
+        });
+        assert!((c.red() - 5.0 / 6.0).abs() < 0.01, "{:?}", c);
+    }
+
+    #[test]
+    fn direct_lighting_matches_phong_diffuse() {
+        let mut plane = Shape::new(Object::Plane);
+        plane.material.diffuse = 0.8;
+        let w = World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(0.0, 10.0, 0.0)),
+            vec![plane],
+        );
+        let r = Ray::new(
+            Tuple::new_point(0.0, 1.0, 0.0),
+            Tuple::new_vector(0.0, -1.0, 0.0),
+        );
+        let c = average(10, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert_eq!(c, Color::new(0.8, 0.8, 0.8));
+    }
+
+    #[test]
+    fn indirect_lighting_reaches_shadowed_points() {
+        // A point on the floor under a sphere, lit only by light bounced off
+        // a wall.
+        let wall = Shape::with_transform(
+            Object::Plane,
+            &transform::translation(0.0, 0.0, 2.0) * &transform::rotation_x(PI / 2.0),
+        );
+        let blocker = Shape::with_transform(Object::Sphere, transform::translation(0.0, 2.0, 0.0));
+        let w = World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(0.0, 10.0, 0.0)),
+            vec![Shape::new(Object::Plane), wall, blocker],
+        );
+        let r = Ray::new(
+            Tuple::new_point(0.0, 1.0, -1.5),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        let phong = w.color_at(&r);
+        assert_eq!(phong, Color::new(0.1, 0.1, 0.1));
+        let c = average(200, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert!(c.red() > 0.05);
+    }
+
+    #[test]
+    fn physical_materials_in_furnace_do_not_create_energy() {
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        for metallic in &[0.0, 1.0] {
+            let mut w = furnace(1.0);
+            w.objects[0].material.physical = Some(Physical {
+                metallic: *metallic,
+                roughness: 0.2,
+                ..Physical::default()
+            });
+            let c = average(500, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+            assert!(c.red() > 0.8 && c.red() < 1.02, "{:?}", c);
+        }
+    }
+
+    /// Returns a world with a floor of albedo 0.8 lit only by a black
+    /// spherical emitter of radius 1 and radiance 1 at 4 units above the origin.
+    fn floor_under_emitter() -> World {
+        let mut floor = Shape::new(Object::Plane);
+        floor.material.diffuse = 0.8;
+        let mut emitter =
+            Shape::with_transform(Object::Sphere, transform::translation(0.0, 4.0, 0.0));
+        emitter.material.color = color::BLACK;
+        emitter.material.emission = color::WHITE;
+        World::new(PointLight::new(color::BLACK, ORIGIN), vec![floor, emitter])
+    }
+
+    #[test]
+    fn emitters_are_seen_directly() {
+        let w = floor_under_emitter();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 4.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let c = Integrator::path_tracing().color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(c, color::WHITE);
+    }
+
+    #[test]
+    fn emitters_light_other_surfaces() {
+        // A sphere of radiance L subtending angle asin(r / d) lights a facing
+        // surface with irradiance pi * L * (r / d)^2.
+        let w = floor_under_emitter();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.5, -0.5),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        let c = average(2000, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        let expected = 0.8 / 16.0;
+        assert!((c.red() - expected).abs() < 0.02 * expected, "{:?}", c);
+    }
+
+    #[test]
+    fn disks_light_other_surfaces() {
+        // A disk of radiance L and radius r at distance d lights a parallel
+        // surface under its center with irradiance pi * L * r^2 / (d^2 + r^2).
+        let mut w = floor_under_emitter();
+        w.objects[1].object = Object::Disk { inner_radius: 0.0 };
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.5, -0.5),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        let c = average(2000, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        let expected = 0.8 / 17.0;
+        assert!((c.red() - expected).abs() < 0.02 * expected, "{:?}", c);
+    }
+
+    #[test]
+    fn emitter_sampling_density_matches_emitter_pdf() {
+        let w = floor_under_emitter();
+        let emitters = w.emitters();
+        assert_eq!(emitters, [&w.objects[1]]);
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.5, -0.5),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        let xs = w.intersects(&r);
+        let comps = xs.hit().unwrap().prepare_computations(&r);
+        let light = sample_emitter(&w, &emitters, &comps, &mut Rng::new(0)).unwrap();
+        let to_light = Ray::new(comps.over_point.clone(), light.wi.clone());
+        let xs = w.intersects(&to_light);
+        let light_comps = xs.hit().unwrap().prepare_computations(&to_light);
+        let pdf = emitter_pdf(&emitters, &light_comps, &comps.over_point);
+        assert!((light.pdf - pdf).abs() < 1e-6 * pdf);
+    }
+
+    #[test]
+    fn unbounded_emitters_are_found_by_bsdf_sampling() {
+        let mut floor = Shape::new(Object::Plane);
+        floor.material.diffuse = 0.8;
+        let mut ceiling =
+            Shape::with_transform(Object::Plane, transform::translation(0.0, 2.0, 0.0));
+        ceiling.material.color = color::BLACK;
+        ceiling.material.emission = color::WHITE;
+        let w = World::new(PointLight::new(color::BLACK, ORIGIN), vec![floor, ceiling]);
+        assert!(w.emitters().is_empty());
+        let r = Ray::new(
+            Tuple::new_point(0.0, 1.0, -1.0),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        let c = average(10, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert_eq!(c, Color::new(0.8, 0.8, 0.8));
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert!((power_heuristic(2.0, 3.0) + power_heuristic(3.0, 2.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ambient_occlusion_integrator_outputs_visibility() {
+        let mut w = World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(0.0, 10.0, 0.0)),
+            vec![
+                Shape::new(Object::Plane),
+                Shape::with_transform(Object::Plane, transform::translation(0.0, 0.5, 0.0)),
+            ],
+        );
+        w.ambient_occlusion = Some(AmbientOcclusion {
+            samples: 4000,
+            radius: 1.0,
+        });
+        let ao = |r: &Ray| Integrator::AmbientOcclusion.color_at(&w, r, &mut Rng::new(0));
+        let down = Ray::new(
+            Tuple::new_point(0.0, 0.25, 0.0),
+            Tuple::new_vector(0.0, -1.0, 0.0),
+        );
+        // See `occlusion::tests::point_under_ceiling_is_occluded_within_radius()`.
+        assert!((ao(&down).red() - 0.25).abs() < 0.03);
+        let sideways = Ray::new(
+            Tuple::new_point(0.0, 0.25, 0.0),
+            Tuple::new_vector(1.0, 0.0, 0.0),
+        );
+        assert_eq!(ao(&sideways), color::WHITE);
+    }
+
+    fn ray_through_medium() -> Ray {
+        Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn absorbing_medium_attenuates_light() {
+        let w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.5,
+                scattering: 0.0,
+            },
+            color::BLACK,
+            color::WHITE,
+        );
+        let r = ray_through_medium();
+        let c = average(4000, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert!((c.red() - (-1.0_f64).exp()).abs() < 0.02, "{:?}", c);
+    }
+
+    #[test]
+    fn scattering_medium_conserves_energy() {
+        let w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.0,
+                scattering: 2.0,
+            },
+            color::BLACK,
+            color::WHITE,
+        );
+        let r = ray_through_medium();
+        let c = average(100, |rng| Integrator::path_tracing().color_at(&w, &r, rng));
+        assert_eq!(c, color::WHITE);
+    }
+
+    #[test]
+    fn thin_medium_single_scattering_matches_phong() {
+        let w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.0,
+                scattering: 0.1,
+            },
+            color::WHITE,
+            color::BLACK,
+        );
+        let r = ray_through_medium();
+        let phong = average(100, |rng| Integrator::Phong.color_at(&w, &r, rng));
+        let path = average(20000, |rng| {
+            Integrator::path_tracing().color_at(&w, &r, rng)
+        });
+        assert!(phong.red() > 0.0);
+        assert!(
+            (path.red() - phong.red()).abs() < 0.1 * phong.red(),
+            "{:?} {:?}",
+            path,
+            phong
+        );
+    }
+
+    #[test]
+    fn fog_is_seen_along_paths() {
+        let mut w = World::new(PointLight::new(color::BLACK, ORIGIN), vec![]);
+        w.fog = Some(Fog {
+            color: Color::new(0.5, 0.5, 0.5),
+            density: 0.1,
+            height_falloff: 0.0,
+        });
+        let c = Integrator::path_tracing().color_at(&w, &ray_through_medium(), &mut Rng::new(0));
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn bounce_limit_truncates_paths() {
+        let w = furnace(1.0);
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let c = Integrator::PathTracing { max_bounces: 0 }.color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(c, color::BLACK);
+    }
+}
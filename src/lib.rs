@@ -5,15 +5,30 @@
 
 // TODO: public or crate internal?
 // TODO: re-export instead some entities?
+pub mod animation;
+pub mod aov;
+pub mod background;
+pub mod bsdf;
+pub mod bump;
 pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod error;
+pub mod integrator;
 pub mod light;
 pub mod matrix;
+pub mod medium;
+pub mod noise;
+pub mod occlusion;
+pub mod pattern;
+pub mod quaternion;
 pub mod ray;
+pub mod rng;
+pub mod scene;
 pub mod shape;
 pub mod transform;
 pub mod tuple;
 pub mod world;
 
 mod util;
+mod yaml;
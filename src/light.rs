@@ -2,7 +2,13 @@
 //!
 //! See TRTC chapter 6.
 
+use std::f64::consts::PI;
+
+use crate::bsdf::{Bsdf, Lambertian, Physical};
+use crate::bump::Bump;
 use crate::color::{self, Color};
+use crate::medium::Medium;
+use crate::pattern::Pattern;
 use crate::tuple::Tuple;
 
 /// A sizeless light source.
@@ -30,6 +36,25 @@ pub struct Material {
     pub diffuse: f64,
     pub specular: f64,
     pub shininess: f64,
+
+    /// Overrides `color` when set.
+    pub pattern: Option<Pattern>,
+
+    /// Perturbs shading normals when set.
+    pub bump: Option<Bump>,
+
+    /// Light emitted by the surface, in all directions and on both sides.
+    /// Emitters are seen by all integrators but only light other surfaces when
+    /// path tracing.
+    pub emission: Color,
+
+    /// Makes the shape a volume filled with this medium when set: its surface is
+    /// invisible and light scattered inside is tinted by `color`.
+    pub medium: Option<Medium>,
+
+    /// Replaces `diffuse`, `specular` and `shininess` with a physically based
+    /// model when set.  `color` (or `pattern`) is then the base color.
+    pub physical: Option<Physical>,
 }
 
 impl Default for Material {
@@ -40,6 +65,29 @@ impl Default for Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            pattern: None,
+            bump: None,
+            emission: color::BLACK,
+            medium: None,
+            physical: None,
+        }
+    }
+}
+
+impl Material {
+    pub fn is_emissive(&self) -> bool {
+        self.emission != color::BLACK
+    }
+
+    /// Returns scattering model of this material for surface color `color`.
+    ///
+    /// Phong materials are approximated as Lambertian with albedo `color * diffuse`.
+    pub fn bsdf(&self, color: &Color) -> Box<dyn Bsdf> {
+        match &self.physical {
+            Some(physical) => Box::new(physical.bsdf(color)),
+            None => Box::new(Lambertian {
+                albedo: color * self.diffuse,
+            }),
         }
     }
 }
@@ -65,24 +113,69 @@ pub fn lighting(
     eye_vec: &Tuple,
     normal_vec: &Tuple,
     status: PointStatus,
+) -> Color {
+    lighting_with_color(m, &m.color, light, pt, eye_vec, normal_vec, status)
+}
+
+/// Same as `lighting()` with `color` instead of the color of `m`, e.g. sampled
+/// from the pattern of `m`.
+pub fn lighting_with_color(
+    m: &Material,
+    color: &Color,
+    light: &PointLight,
+    pt: &Tuple,
+    eye_vec: &Tuple,
+    normal_vec: &Tuple,
+    status: PointStatus,
+) -> Color {
+    &ambient_lighting(m, color, light)
+        + &direct_lighting(m, color, light, pt, eye_vec, normal_vec, status)
+}
+
+/// Returns ambient term of `lighting_with_color()`.
+pub fn ambient_lighting(m: &Material, color: &Color, light: &PointLight) -> Color {
+    &(color * &light.intensity) * m.ambient
+}
+
+/// Returns diffuse and specular terms of `lighting_with_color()`.
+///
+/// Physical materials are lit by evaluating their BSDF, scaled so that white
+/// Lambertian surfaces receive the same diffuse lighting as with Phong.
+pub fn direct_lighting(
+    m: &Material,
+    color: &Color,
+    light: &PointLight,
+    pt: &Tuple,
+    eye_vec: &Tuple,
+    normal_vec: &Tuple,
+    status: PointStatus,
 ) -> Color {
     debug_assert!(pt.is_point());
     debug_assert!(eye_vec.is_vector());
     debug_assert!(normal_vec.is_vector());
 
-    let effective_color = &m.color * &light.intensity;
-    let ambient = &effective_color * m.ambient;
+    let effective_color = color * &light.intensity;
+
+    if let Some(physical) = &m.physical {
+        let light_vec = (&light.position - pt).normalized();
+        let cos = Tuple::dot(&light_vec, normal_vec);
+        if status == PointStatus::InShadow || cos <= 0.0 {
+            return color::BLACK;
+        }
+        let f = physical.bsdf(color).eval(eye_vec, &light_vec, normal_vec);
+        return &(&f * &light.intensity) * (PI * cos);
+    }
 
     let mut diffuse = color::BLACK;
     let mut specular = color::BLACK;
     match status {
         PointStatus::InLight => {
             let light_vec = (&light.position - pt).normalized();
-            let light_dot_normal = Tuple::dot(&light_vec, &normal_vec);
+            let light_dot_normal = Tuple::dot(&light_vec, normal_vec);
             if light_dot_normal >= 0.0 {
                 diffuse = &effective_color * (m.diffuse * light_dot_normal);
-                let reflect_vec = Tuple::reflected(&-&light_vec, &normal_vec);
-                let reflect_dot_eye = Tuple::dot(&reflect_vec, &eye_vec);
+                let reflect_vec = Tuple::reflected(&-&light_vec, normal_vec);
+                let reflect_dot_eye = Tuple::dot(&reflect_vec, eye_vec);
 
                 if reflect_dot_eye >= 0.0 {
                     let factor = reflect_dot_eye.powf(m.shininess);
@@ -95,11 +188,11 @@ pub fn lighting(
             }
         }
         PointStatus::InShadow => {
-            // Take into account ambient component only.
+            // Only the ambient component, see `ambient_lighting()`.
         }
     }
 
-    &(&ambient + &diffuse) + &specular
+    &diffuse + &specular
 }
 
 #[cfg(test)]
@@ -209,4 +302,77 @@ mod tests {
         );
         assert_eq!(res, Color::new(0.1, 0.1, 0.1));
     }
+
+    fn physical_material(metallic: f64) -> Material {
+        Material {
+            physical: Some(Physical {
+                metallic,
+                roughness: 0.3,
+                ..Physical::default()
+            }),
+            ..Material::default()
+        }
+    }
+
+    #[test]
+    fn lighting_physical_material_in_shadow() {
+        let eye_vec = Tuple::new_vector(0.0, 0.0, -1.0);
+        let normal_vec = Tuple::new_vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(color::WHITE, Tuple::new_point(0.0, 0.0, -10.0));
+        let res = lighting(
+            &physical_material(0.0),
+            &light,
+            &ORIGIN,
+            &eye_vec,
+            &normal_vec,
+            PointStatus::InShadow,
+        );
+        assert_eq!(res, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn lighting_physical_metal_reflects_toward_mirror_direction() {
+        let normal_vec = Tuple::new_vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(color::WHITE, Tuple::new_point(0.0, 10.0, -10.0));
+        let lit = |eye_vec: Tuple| {
+            lighting(
+                &physical_material(1.0),
+                &light,
+                &ORIGIN,
+                &eye_vec,
+                &normal_vec,
+                PointStatus::InLight,
+            )
+        };
+        let mirror = lit(Tuple::new_vector(
+            0.0,
+            -2_f64.sqrt() / 2.0,
+            -2_f64.sqrt() / 2.0,
+        ));
+        let head_on = lit(Tuple::new_vector(0.0, 0.0, -1.0));
+        assert!(mirror.red() > 1.0);
+        assert!(head_on.red() < 0.2);
+    }
+
+    #[test]
+    fn lighting_physical_dielectric_is_mostly_diffuse() {
+        let eye_vec = Tuple::new_vector(0.0, 0.0, -1.0);
+        let normal_vec = Tuple::new_vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(color::WHITE, Tuple::new_point(0.0, 10.0, -10.0));
+        let m = Material {
+            color: color::RED,
+            ..physical_material(0.0)
+        };
+        let res = lighting(
+            &m,
+            &light,
+            &ORIGIN,
+            &eye_vec,
+            &normal_vec,
+            PointStatus::InLight,
+        );
+        // Ambient plus diffuse lighting, dimmed a bit by the specular coat.
+        assert!(res.red() > 0.6 && res.red() < 0.1 + 2_f64.sqrt() / 2.0);
+        assert!(res.green() < 0.05);
+    }
 }
@@ -0,0 +1,250 @@
+//! Command-line renderer.
+//!
+//! Renders a scene description file (see `rustytracer::scene`) to an image.
+
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use rustytracer::aov::Aov;
+use rustytracer::camera::{Camera, RenderOptions};
+use rustytracer::canvas::Canvas;
+use rustytracer::integrator::Integrator;
+use rustytracer::scene;
+
+const USAGE: &str = "\
+Usage: rustytracer [OPTIONS] SCENE OUTPUT
+
+Renders scene description file SCENE to image file OUTPUT.
+
+Options:
+  -f, --format FORMAT  Output format: ppm (plain, default), ppm-raw or pfm
+                       (floating point, unclamped)
+  -W, --width N        Canvas width in pixels (default: from scene)
+  -H, --height N       Canvas height in pixels (default: from scene)
+  -s, --samples N      Rays per pixel (default: 1)
+  -j, --threads N      Render threads (default: number of CPUs)
+  -i, --integrator I   Light transport: phong (default), path (path tracing,
+                       use many samples) or ao (ambient occlusion only)
+  -a, --aov NAME=FILE  Also write output variable NAME to image file FILE, in
+                       the output format: depth, normal, albedo, object-id,
+                       direct or indirect (may be repeated, best with pfm)
+  -h, --help           Print this help
+
+When only one of width and height is given, the other one is computed to
+preserve the aspect ratio of the scene camera.
+
+Exit status:
+  0  success
+  1  invalid command line
+  2  invalid scene description
+  3  rendering failed
+  4  input/output error";
+
+/// Process exit codes.
+const EXIT_USAGE: i32 = 1;
+const EXIT_PARSE: i32 = 2;
+const EXIT_RENDER: i32 = 3;
+const EXIT_IO: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    PlainPpm,
+    RawPpm,
+    Pfm,
+}
+
+struct Args {
+    scene: String,
+    output: String,
+    format: Format,
+    width: Option<usize>,
+    height: Option<usize>,
+    options: RenderOptions,
+    /// Output variables to write, with their files.
+    aovs: Vec<(Aov, String)>,
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("rustytracer: {}\n\n{}", msg, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let Err((code, msg)) = run(&args) {
+        eprintln!("rustytracer: {}", msg);
+        process::exit(code);
+    }
+}
+
+fn run(args: &Args) -> Result<(), (i32, String)> {
+    let start = Instant::now();
+    let src = fs::read_to_string(&args.scene)
+        .map_err(|e| (EXIT_IO, format!("cannot read {}: {}", args.scene, e)))?;
+    let dir = Path::new(&args.scene)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let scene = scene::parse_in_dir(&src, dir)
+        .map_err(|e| (EXIT_PARSE, format!("{}: {}", args.scene, e)))?;
+    println!(
+        "parsed {} in {:.3}s",
+        args.scene,
+        start.elapsed().as_secs_f64()
+    );
+
+    scene
+        .world
+        .validate()
+        .map_err(|e| (EXIT_RENDER, format!("{}: {}", args.scene, e)))?;
+
+    let camera = resized_camera(&scene.camera, args.width, args.height);
+    let start = Instant::now();
+    // Validation catches known failures.  As a last resort, a panic while
+    // rendering must not abort the process with the exit code reserved for
+    // other failures, nor print a backtrace.
+    let aovs: Vec<_> = args.aovs.iter().map(|(aov, _)| *aov).collect();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let passes = panic::catch_unwind(AssertUnwindSafe(|| {
+        camera.render_passes(&scene.world, &args.options, &aovs)
+    }));
+    panic::set_hook(default_hook);
+    let passes = passes.map_err(|_| (EXIT_RENDER, "rendering failed".to_string()))?;
+    println!(
+        "rendered {}x{} ({} samples/pixel, {} threads) in {:.3}s",
+        camera.hsize(),
+        camera.vsize(),
+        args.options.samples,
+        args.options.threads,
+        start.elapsed().as_secs_f64()
+    );
+
+    let start = Instant::now();
+    write(&passes.beauty, &args.output, args.format)?;
+    for ((_, path), (_, canvas)) in args.aovs.iter().zip(&passes.aovs) {
+        write(canvas, path, args.format)?;
+    }
+    println!(
+        "wrote {} in {:.3}s",
+        args.output,
+        start.elapsed().as_secs_f64()
+    );
+
+    Ok(())
+}
+
+/// Writes `canvas` to file `path` in `format`.
+fn write(canvas: &Canvas, path: &str, format: Format) -> Result<(), (i32, String)> {
+    let file =
+        File::create(path).map_err(|e| (EXIT_IO, format!("cannot create {}: {}", path, e)))?;
+    match format {
+        Format::PlainPpm => canvas.to_ppm(file),
+        Format::RawPpm => canvas.to_raw_ppm(file),
+        Format::Pfm => canvas.to_pfm(file),
+    }
+    .map_err(|e| (EXIT_IO, format!("cannot write {}: {}", path, e)))
+}
+
+/// Returns copy of `camera` rendering to a canvas of the requested size.
+fn resized_camera(camera: &Camera, width: Option<usize>, height: Option<usize>) -> Camera {
+    let aspect = camera.hsize() as f64 / camera.vsize() as f64;
+    let (hsize, vsize) = match (width, height) {
+        (None, None) => (camera.hsize(), camera.vsize()),
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, ((w as f64 / aspect).round() as usize).max(1)),
+        (None, Some(h)) => (((h as f64 * aspect).round() as usize).max(1), h),
+    };
+    camera.resized(hsize, vsize)
+}
+
+/// Parses command-line arguments.  Returns `None` if help was requested.
+fn parse_args<I: Iterator<Item = String>>(mut it: I) -> Result<Option<Args>, String> {
+    let mut positional = Vec::new();
+    let mut format = Format::PlainPpm;
+    let mut width = None;
+    let mut height = None;
+    let mut options = RenderOptions {
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        ..RenderOptions::default()
+    };
+    let mut aovs = Vec::new();
+
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| {
+            it.next()
+                .ok_or_else(|| format!("missing value for option {}", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => {
+                format = match value(&arg)?.as_str() {
+                    "ppm" => Format::PlainPpm,
+                    "ppm-raw" => Format::RawPpm,
+                    "pfm" => Format::Pfm,
+                    other => return Err(format!("unknown output format '{}'", other)),
+                }
+            }
+            "-W" | "--width" => width = Some(positive(&arg, &value(&arg)?)?),
+            "-H" | "--height" => height = Some(positive(&arg, &value(&arg)?)?),
+            "-s" | "--samples" => options.samples = positive(&arg, &value(&arg)?)?,
+            "-j" | "--threads" => options.threads = positive(&arg, &value(&arg)?)?,
+            "-i" | "--integrator" => {
+                options.integrator = match value(&arg)?.as_str() {
+                    "phong" => Integrator::Phong,
+                    "path" => Integrator::path_tracing(),
+                    "ao" => Integrator::AmbientOcclusion,
+                    other => return Err(format!("unknown integrator '{}'", other)),
+                }
+            }
+            "-a" | "--aov" => {
+                let v = value(&arg)?;
+                match v.split_once('=') {
+                    Some((name, path)) if !path.is_empty() => match Aov::from_name(name) {
+                        Some(aov) => aovs.push((aov, path.to_string())),
+                        None => return Err(format!("unknown output variable '{}'", name)),
+                    },
+                    _ => return Err(format!("{} expects NAME=FILE, found '{}'", arg, v)),
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option {}", arg))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    match <[String; 2]>::try_from(positional) {
+        Ok([scene, output]) => Ok(Some(Args {
+            scene,
+            output,
+            format,
+            width,
+            height,
+            options,
+            aovs,
+        })),
+        Err(_) => Err("expected SCENE and OUTPUT arguments".to_string()),
+    }
+}
+
+/// Parses value `s` of option `name` as a positive integer.
+fn positive(name: &str, s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} expects a positive integer, found '{}'",
+            name, s
+        )),
+    }
+}
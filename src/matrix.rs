@@ -2,6 +2,7 @@
 //!
 //! TRTC chapter 3.
 
+use crate::error::Error;
 use crate::tuple::Tuple;
 use crate::util;
 
@@ -36,13 +37,30 @@ impl Matrix {
     }
 
     pub fn new_4x4_identity() -> Matrix {
-        let mut m = Matrix::new(4, 4);
-        for i in 0..4 {
+        Matrix::identity(4)
+    }
+
+    /// Create a new `n` x `n` identity matrix.
+    pub fn identity(n: usize) -> Matrix {
+        let mut m = Matrix::new(n, n);
+        for i in 0..n {
             m.set(i, i, 1.0)
         }
         m
     }
 
+    /// Create a new matrix of any size from its rows, which must have the same
+    /// length.
+    pub fn from_rows(rows: &[&[f64]]) -> Matrix {
+        let ncols = rows.first().map_or(0, |r| r.len());
+        assert!(rows.iter().all(|r| r.len() == ncols));
+        Matrix {
+            nrows: rows.len(),
+            ncols,
+            cells: rows.concat(),
+        }
+    }
+
     /// Create a new 3x3 matrix containing the given values.
     pub fn new_3x3(values: &[[f64; 3]; 3]) -> Matrix {
         let cells = values.iter().flatten().cloned().collect::<Vec<f64>>();
@@ -63,18 +81,46 @@ impl Matrix {
         }
     }
 
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    fn is_square(&self) -> bool {
+        self.nrows == self.ncols
+    }
+
     /// Return the value at the specified row and column.
     pub fn get(&self, r: usize, c: usize) -> f64 {
         assert!(r < self.nrows);
         assert!(c < self.ncols);
-        self.cells[r * self.nrows + c]
+        self.cells[r * self.ncols + c]
     }
 
     /// Change the cell at the specified row and column to the specified value.
     pub fn set(&mut self, r: usize, c: usize, v: f64) {
         assert!(r < self.nrows);
         assert!(c < self.ncols);
-        self.cells[r * self.nrows + c] = v;
+        self.cells[r * self.ncols + c] = v;
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        if a != b {
+            for c in 0..self.ncols {
+                self.cells.swap(a * self.ncols + c, b * self.ncols + c);
+            }
+        }
+    }
+
+    /// Returns row at or below `k` with the largest absolute value in column
+    /// `k`, the best pivot for Gaussian elimination.
+    fn pivot_row(&self, k: usize) -> usize {
+        (k..self.nrows)
+            .max_by(|&a, &b| self.get(a, k).abs().total_cmp(&self.get(b, k).abs()))
+            .expect("no row to pivot")
     }
 
     /// Return transposition of this matrix.
@@ -89,19 +135,33 @@ impl Matrix {
         m
     }
 
-    /// Returns determinant of this matrix.
+    /// Returns determinant of this square matrix.
+    ///
+    /// Computed by Gaussian elimination with partial pivoting, in O(n^3).
     pub fn determinant(&self) -> f64 {
-        debug_assert!(self.nrows >= 2 && self.ncols >= 2);
-        if self.nrows == 2 && self.ncols == 2 {
-            self.get(0, 0) * self.get(1, 1) - self.get(0, 1) * self.get(1, 0)
-        } else {
-            let mut det = 0.0;
-            for i in 0..self.ncols {
-                // TODO: avoid creation of temporary submatrix in cofactor()
-                det += self.cells[i] * self.cofactor(0, i);
+        assert!(self.is_square());
+        let n = self.nrows;
+        let mut m = self.clone();
+        let mut det = 1.0;
+        for k in 0..n {
+            let p = m.pivot_row(k);
+            if m.get(p, k) == 0.0 {
+                return 0.0;
+            }
+            if p != k {
+                m.swap_rows(p, k);
+                det = -det;
+            }
+            let pivot = m.get(k, k);
+            det *= pivot;
+            for r in k + 1..n {
+                let f = m.get(r, k) / pivot;
+                for c in k + 1..n {
+                    m.set(r, c, m.get(r, c) - f * m.get(k, c));
+                }
             }
-            det
         }
+        det
     }
 
     /// Return copy of this matrix without specified row and column.
@@ -144,29 +204,95 @@ impl Matrix {
         f * self.minor(row, col)
     }
 
-    /// Returns true if this matrix is invertible.
+    /// Returns element-wise linear interpolation between this matrix (`t == 0`)
+    /// and `o` (`t == 1`).
+    pub fn interpolated(&self, o: &Matrix, t: f64) -> Matrix {
+        assert!(self.nrows == o.nrows && self.ncols == o.ncols);
+        Matrix {
+            nrows: self.nrows,
+            ncols: self.ncols,
+            cells: self
+                .cells
+                .iter()
+                .zip(&o.cells)
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+        }
+    }
+
+    /// Returns true if this square matrix is invertible.
     pub fn invertible(&self) -> bool {
         self.determinant() != 0.0
     }
 
     /// Returns inverted version of this matrix.
+    ///
+    /// Panics if this matrix is not invertible.  See `try_inverted()`.
     pub fn inverted(&self) -> Matrix {
-        assert!(self.invertible());
-
-        // TODO: useless init
-        let mut im = Matrix::new(self.nrows, self.ncols);
-
-        // TODO: memoize it?
-        let det = self.determinant();
-
-        for r in 0..self.nrows {
-            for c in 0..self.ncols {
-                let cf = self.cofactor(r, c);
-                im.set(c, r, cf / det);
+        self.try_inverted().expect("matrix is not invertible")
+    }
+
+    /// Returns inverted version of this matrix, or an error if it is not square
+    /// or not invertible.
+    ///
+    /// Computed by Gauss-Jordan elimination with partial pivoting, in O(n^3).
+    pub fn try_inverted(&self) -> Result<Matrix, Error> {
+        if !self.is_square() {
+            return Err(Error::NotSquare {
+                nrows: self.nrows,
+                ncols: self.ncols,
+            });
+        }
+        let n = self.nrows;
+        let mut m = self.clone();
+        let mut im = Matrix::identity(n);
+        for k in 0..n {
+            let p = m.pivot_row(k);
+            let pivot = m.get(p, k);
+            if pivot == 0.0 || !pivot.is_finite() {
+                return Err(Error::SingularMatrix);
+            }
+            m.swap_rows(p, k);
+            im.swap_rows(p, k);
+            for c in 0..n {
+                m.set(k, c, m.get(k, c) / pivot);
+                im.set(k, c, im.get(k, c) / pivot);
+            }
+            for r in (0..n).filter(|&r| r != k) {
+                let f = m.get(r, k);
+                if f != 0.0 {
+                    for c in 0..n {
+                        m.set(r, c, m.get(r, c) - f * m.get(k, c));
+                        im.set(r, c, im.get(r, c) - f * im.get(k, c));
+                    }
+                }
             }
         }
+        if im.cells.iter().all(|v| v.is_finite()) {
+            Ok(im)
+        } else {
+            Err(Error::SingularMatrix)
+        }
+    }
+
+    /// Returns condition number of this square matrix in the maximum row sum
+    /// norm, or infinity if it is not invertible.
+    ///
+    /// Relative errors in vectors multiplied by the inverse of this matrix may
+    /// grow by this factor: values far above 1 lose precision.
+    pub fn condition_number(&self) -> f64 {
+        match self.try_inverted() {
+            Ok(im) => self.norm() * im.norm(),
+            Err(_) => f64::INFINITY,
+        }
+    }
 
-        im
+    /// Returns maximum row sum norm of this matrix.
+    fn norm(&self) -> f64 {
+        self.cells
+            .chunks(self.ncols.max(1))
+            .map(|row| row.iter().map(|v| v.abs()).sum::<f64>())
+            .fold(0.0, f64::max)
     }
 }
 
@@ -189,15 +315,16 @@ impl PartialEq for Matrix {
 impl std::ops::Mul for &Matrix {
     type Output = Matrix;
 
-    /// Multiply given matrices.
+    /// Multiply given matrices.  The number of columns of the left one must
+    /// match the number of rows of the right one.
     /// TODO: naive algorithm
     fn mul(self, o: Self) -> Self::Output {
-        assert_eq!(self.nrows, o.ncols);
+        assert_eq!(self.ncols, o.nrows);
         let mut res = Matrix::new(self.nrows, o.ncols);
         for r in 0..self.nrows {
-            for c in 0..self.ncols {
+            for c in 0..o.ncols {
                 let mut dot = 0.0;
-                for i in 0..self.nrows {
+                for i in 0..self.ncols {
                     dot += self.get(r, i) * o.get(i, c);
                 }
                 res.set(r, c, dot);
@@ -347,6 +474,18 @@ mod tests {
         assert_eq!(&m * &Matrix::new_4x4_identity(), m);
     }
 
+    #[test]
+    fn interpolating_matrices() {
+        let l = Matrix::new_2x2(&[[0.0, 2.0], [4.0, -2.0]]);
+        let r = Matrix::new_2x2(&[[1.0, 4.0], [0.0, 2.0]]);
+        assert_eq!(l.interpolated(&r, 0.0), l);
+        assert_eq!(l.interpolated(&r, 1.0), r);
+        assert_eq!(
+            l.interpolated(&r, 0.25),
+            Matrix::new_2x2(&[[0.25, 2.5], [3.0, -1.0]])
+        );
+    }
+
     #[test]
     fn transposing_matrix() {
         let m = Matrix::new_4x4(&[
@@ -443,6 +582,17 @@ mod tests {
         ]);
         assert_eq!(m.determinant(), 0.0);
         assert!(!m.invertible());
+        assert_eq!(m.try_inverted().unwrap_err(), Error::SingularMatrix);
+    }
+
+    #[test]
+    fn inverting_non_square_matrix_fails() {
+        let m = Matrix::from_rows(&[&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]]);
+        assert_eq!(
+            m.try_inverted().unwrap_err(),
+            Error::NotSquare { nrows: 2, ncols: 3 }
+        );
+        assert_eq!(m.condition_number(), f64::INFINITY);
     }
 
     #[test]
@@ -453,13 +603,14 @@ mod tests {
             [7.0, 7.0, -6.0, -7.0],
             [1.0, -3.0, 7.0, 4.0],
         ]);
-        assert_eq!(m.determinant(), 532.0);
-        assert_eq!(m.cofactor(2, 3), -160.0);
-        assert_eq!(m.cofactor(3, 2), 105.0);
+        // Elimination may round the last bits.
+        assert!(util::nearly_equal(m.determinant(), 532.0));
+        assert!(util::nearly_equal(m.cofactor(2, 3), -160.0));
+        assert!(util::nearly_equal(m.cofactor(3, 2), 105.0));
 
         let im = m.inverted();
-        assert_eq!(im.get(3, 2), -160.0 / 532.0);
-        assert_eq!(im.get(2, 3), 105.0 / 532.0);
+        assert!(util::nearly_equal(im.get(3, 2), -160.0 / 532.0));
+        assert!(util::nearly_equal(im.get(2, 3), 105.0 / 532.0));
         assert_eq!(
             im,
             Matrix::new_4x4(&[
@@ -522,4 +673,69 @@ mod tests {
         let prod = &l * &r;
         assert_eq!(&prod * &r.inverted(), l);
     }
+
+    #[test]
+    fn setting_and_getting_cells_of_non_square_matrix() {
+        let mut m = Matrix::new(2, 3);
+        m.set(0, 2, 1.0);
+        m.set(1, 0, 2.0);
+        assert_eq!(m.get(0, 2), 1.0);
+        assert_eq!(m.get(1, 0), 2.0);
+        assert_eq!(m, Matrix::from_rows(&[&[0.0, 0.0, 1.0], &[2.0, 0.0, 0.0]]));
+    }
+
+    #[test]
+    fn multiplying_non_square_matrices() {
+        let l = Matrix::from_rows(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+        let r = Matrix::from_rows(&[&[7.0, 8.0], &[9.0, 10.0], &[11.0, 12.0]]);
+        assert_eq!(
+            &l * &r,
+            Matrix::from_rows(&[&[58.0, 64.0], &[139.0, 154.0]])
+        );
+        let p = &r * &l;
+        assert_eq!((p.nrows(), p.ncols()), (3, 3));
+        assert_eq!(p.get(2, 1), 11.0 * 2.0 + 12.0 * 5.0);
+
+        // Row vector times column vector.
+        let row = Matrix::from_rows(&[&[1.0, 2.0, 3.0]]);
+        assert_eq!(&row * &row.transposed(), Matrix::from_rows(&[&[14.0]]));
+        assert_eq!((row.transposed().nrows(), row.transposed().ncols()), (3, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn multiplying_mismatched_matrices() {
+        let l = Matrix::from_rows(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+        let _ = &l * &l;
+    }
+
+    #[test]
+    fn inverting_large_matrix() {
+        // Needs pivoting: the first diagonal cell is 0.
+        let m = Matrix::from_rows(&[
+            &[0.0, 2.0, 1.0, 0.0, 3.0, 1.0],
+            &[1.0, 0.0, 0.0, 4.0, 1.0, 2.0],
+            &[3.0, 1.0, 2.0, 0.0, 0.0, 1.0],
+            &[0.0, 5.0, 1.0, 1.0, 2.0, 0.0],
+            &[2.0, 0.0, 3.0, 1.0, 1.0, 4.0],
+            &[1.0, 1.0, 0.0, 2.0, 0.0, 3.0],
+        ]);
+        let im = m.inverted();
+        assert_eq!(&m * &im, Matrix::identity(6));
+        assert_eq!(&im * &m, Matrix::identity(6));
+        assert!(util::nearly_equal(m.determinant() * im.determinant(), 1.0));
+        // Same as cofactor expansion.
+        let expanded: f64 = (0..6).map(|c| m.get(0, c) * m.cofactor(0, c)).sum();
+        assert!((m.determinant() - expanded).abs() < 1e-9 * expanded.abs());
+    }
+
+    #[test]
+    fn computing_condition_number() {
+        assert_eq!(Matrix::identity(4).condition_number(), 1.0);
+        let m = Matrix::new_3x3(&[[1.0, 0.0, 0.0], [0.0, 1e-6, 0.0], [0.0, 0.0, 1.0]]);
+        assert!(util::nearly_equal(m.condition_number(), 1e6));
+        let singular = Matrix::new_2x2(&[[1.0, 2.0], [2.0, 4.0]]);
+        assert_eq!(singular.condition_number(), f64::INFINITY);
+        assert!(!singular.invertible());
+    }
 }
@@ -0,0 +1,175 @@
+//! Participating media: fog filling the world and volumes filling shapes.
+//!
+//! Light traveling through a medium is absorbed and scattered away by its
+//! particles, and scattered toward the eye from light sources.
+
+use crate::color::Color;
+use crate::tuple::Tuple;
+
+/// Below this, the height falloff of fog is ignored to avoid dividing by zero.
+const MIN_FALLOFF: f64 = 1e-9;
+
+/// Exponential height fog filling the whole world: thick at ground level and
+/// thinning with altitude.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    /// Extinction coefficient at height 0, i.e. fraction of light removed per
+    /// unit of distance.  Not negative.
+    pub density: f64,
+    /// How fast density decreases with height: it is divided by e every
+    /// `1 / height_falloff` units.  0 is uniform fog.
+    pub height_falloff: f64,
+}
+
+impl Fog {
+    /// Returns fraction of light transmitted from `origin` over `distance`
+    /// (possibly infinite) along unit vector `dir`.
+    pub fn transmittance(&self, origin: &Tuple, dir: &Tuple, distance: f64) -> f64 {
+        debug_assert!(origin.is_point());
+        debug_assert!(dir.is_vector());
+        let k = self.height_falloff;
+        let base = self.density * (-k * origin.y()).exp();
+        if base == 0.0 || distance == 0.0 {
+            // No fog, even infinitely far: avoid 0 * infinity.
+            return 1.0;
+        }
+        // Integral of the density along the ray.
+        let rate = k * dir.y();
+        let optical_depth = if distance.is_infinite() {
+            if rate >= MIN_FALLOFF {
+                base / rate
+            } else {
+                f64::INFINITY
+            }
+        } else if rate.abs() < MIN_FALLOFF {
+            base * distance
+        } else {
+            base * (1.0 - (-rate * distance).exp()) / rate
+        };
+        (-optical_depth).exp()
+    }
+
+    /// Returns `color` seen from `origin` through the fog when it comes from
+    /// `distance` away along unit vector `dir`.
+    pub fn apply(&self, color: &Color, origin: &Tuple, dir: &Tuple, distance: f64) -> Color {
+        let t = self.transmittance(origin, dir, distance);
+        color.interpolated(&self.color, 1.0 - t)
+    }
+}
+
+/// Homogeneous medium, e.g. smoke or murky water, filling the inside of a
+/// shape.  Scattered light is tinted by the color of the material of the shape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Medium {
+    /// Fraction of light absorbed per unit of distance.
+    pub absorption: f64,
+    /// Fraction of light scattered per unit of distance.
+    pub scattering: f64,
+}
+
+impl Medium {
+    /// Returns fraction of light removed per unit of distance.
+    pub fn extinction(&self) -> f64 {
+        self.absorption + self.scattering
+    }
+
+    /// Returns fraction of light transmitted over `distance`.
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        (-self.extinction() * distance).exp()
+    }
+
+    /// Returns fraction of extinguished light that is scattered rather than
+    /// absorbed.
+    pub fn albedo(&self) -> f64 {
+        let extinction = self.extinction();
+        if extinction > 0.0 {
+            self.scattering / extinction
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::tuple::ORIGIN;
+
+    fn fog(height_falloff: f64) -> Fog {
+        Fog {
+            color: color::WHITE,
+            density: 0.5,
+            height_falloff,
+        }
+    }
+
+    #[test]
+    fn uniform_fog_decays_exponentially() {
+        let dir = Tuple::new_vector(1.0, 0.0, 0.0);
+        let f = fog(0.0);
+        assert_eq!(f.transmittance(&ORIGIN, &dir, 0.0), 1.0);
+        assert!((f.transmittance(&ORIGIN, &dir, 2.0) - (-1.0_f64).exp()).abs() < 1e-12);
+        assert_eq!(f.transmittance(&ORIGIN, &dir, f64::INFINITY), 0.0);
+        assert_eq!(
+            f.apply(&color::BLACK, &ORIGIN, &dir, f64::INFINITY),
+            color::WHITE
+        );
+    }
+
+    #[test]
+    fn height_fog_thins_with_altitude() {
+        let f = fog(1.0);
+        let dir = Tuple::new_vector(1.0, 0.0, 0.0);
+        let low = f.transmittance(&ORIGIN, &dir, 1.0);
+        let high = f.transmittance(&Tuple::new_point(0.0, 3.0, 0.0), &dir, 1.0);
+        assert!(high > low);
+        // Looking up, the fog ends: the optical depth is density / falloff.
+        let up = Tuple::new_vector(0.0, 1.0, 0.0);
+        let t = f.transmittance(&ORIGIN, &up, f64::INFINITY);
+        assert!((t - (-0.5_f64).exp()).abs() < 1e-12);
+        // Same as integrating numerically.
+        let n = 100000;
+        let depth: f64 = (0..n)
+            .map(|i| 0.5 * (-(i as f64 + 0.5) * 2.0 / n as f64).exp() * 2.0 / n as f64)
+            .sum();
+        let t = f.transmittance(&ORIGIN, &up, 2.0);
+        assert!((t - (-depth).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fog_without_density_transmits_everything() {
+        let down = Tuple::new_vector(0.0, -1.0, 0.0);
+        for falloff in &[0.0, 1.0] {
+            let f = Fog {
+                density: 0.0,
+                ..fog(*falloff)
+            };
+            assert_eq!(f.transmittance(&ORIGIN, &down, f64::INFINITY), 1.0);
+            assert_eq!(
+                f.apply(&color::BLACK, &ORIGIN, &down, f64::INFINITY),
+                color::BLACK
+            );
+        }
+        // Looking down, height fog thickens without limit.
+        assert_eq!(fog(1.0).transmittance(&ORIGIN, &down, f64::INFINITY), 0.0);
+    }
+
+    #[test]
+    fn medium_coefficients() {
+        let m = Medium {
+            absorption: 0.1,
+            scattering: 0.3,
+        };
+        assert!((m.extinction() - 0.4).abs() < 1e-12);
+        assert!((m.albedo() - 0.75).abs() < 1e-12);
+        assert!((m.transmittance(2.5) - (-1.0_f64).exp()).abs() < 1e-12);
+        let vacuum = Medium {
+            absorption: 0.0,
+            scattering: 0.0,
+        };
+        assert_eq!(vacuum.albedo(), 0.0);
+        assert_eq!(vacuum.transmittance(10.0), 1.0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Gradient noise.
+//!
+//! Ken Perlin's "improved noise" (2002), with a permutation table shuffled from
+//! a seed so that different surfaces can use uncorrelated noise while renders
+//! stay reproducible.
+
+use crate::rng::Rng;
+use crate::tuple::Tuple;
+
+/// Seeded 3D Perlin noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+    /// Permutation of 0..=255, repeated twice to avoid wrapping indices.
+    perm: Vec<u8>,
+}
+
+impl Perlin {
+    /// Creates a noise function fully determined by `seed`.
+    pub fn new(seed: u64) -> Perlin {
+        let mut p: Vec<u8> = (0..=255).collect();
+        let mut rng = Rng::new(seed);
+        // Fisher-Yates shuffle.
+        for i in (1..p.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            p.swap(i, j);
+        }
+        let mut perm = p.clone();
+        perm.extend_from_slice(&p);
+        Perlin { perm }
+    }
+
+    /// Returns noise value at `pt`, roughly in `[-1, 1]`.
+    ///
+    /// The value is 0 at integer coordinates and varies smoothly in between.
+    pub fn noise(&self, pt: &Tuple) -> f64 {
+        let (x, y, z) = (pt.x(), pt.y(), pt.z());
+        let (xf, yf, zf) = (x.floor(), y.floor(), z.floor());
+        // Unit cube containing the point.
+        let xi = (xf as i64 & 255) as usize;
+        let yi = (yf as i64 & 255) as usize;
+        let zi = (zf as i64 & 255) as usize;
+        // Position in the cube.
+        let (x, y, z) = (x - xf, y - yf, z - zf);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.perm;
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Returns fractal noise at `pt`, roughly in `[-1, 1]`: the sum of `octaves`
+    /// noise layers, each with twice the frequency and half the amplitude of the
+    /// previous one.
+    pub fn fbm(&self, pt: &Tuple, octaves: usize) -> f64 {
+        self.octaves(pt, octaves, |n| n)
+    }
+
+    /// Returns turbulence at `pt`, in `[0, 1]`: same as `fbm()` with the
+    /// absolute value of each layer, which creates sharp creases.
+    pub fn turbulence(&self, pt: &Tuple, octaves: usize) -> f64 {
+        self.octaves(pt, octaves, f64::abs)
+    }
+
+    fn octaves(&self, pt: &Tuple, octaves: usize, f: impl Fn(f64) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * f(self.noise(&(pt * frequency)));
+            total += amplitude;
+            amplitude /= 2.0;
+            frequency *= 2.0;
+        }
+        sum / total
+    }
+}
+
+/// Smoothstep with zero first and second derivatives at 0 and 1.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Returns dot product of (x, y, z) with one of 12 gradient directions picked by `hash`.
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Tuple> {
+        (0..200).map(|i| {
+            let f = i as f64;
+            Tuple::new_point(f * 0.37 - 20.0, f * 0.11 + 3.3, -f * 0.23)
+        })
+    }
+
+    #[test]
+    fn noise_is_zero_at_lattice_points() {
+        let n = Perlin::new(0);
+        assert_eq!(n.noise(&Tuple::new_point(1.0, 2.0, -3.0)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_bounded_and_varies() {
+        let n = Perlin::new(0);
+        let values: Vec<f64> = sample_points().map(|p| n.noise(&p)).collect();
+        assert!(values.iter().all(|v| (-1.1..=1.1).contains(v)));
+        assert!(values.iter().any(|v| *v > 0.2));
+        assert!(values.iter().any(|v| *v < -0.2));
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let n = Perlin::new(3);
+        for p in sample_points() {
+            let q = &p + &Tuple::new_vector(1e-6, 1e-6, 1e-6);
+            assert!((n.noise(&p) - n.noise(&q)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn noise_depends_on_seed_only() {
+        let pt = Tuple::new_point(0.5, 1.25, 2.75);
+        assert_eq!(Perlin::new(7).noise(&pt), Perlin::new(7).noise(&pt));
+        assert_ne!(Perlin::new(7).noise(&pt), Perlin::new(8).noise(&pt));
+    }
+
+    #[test]
+    fn fractal_noise_and_turbulence_ranges() {
+        let n = Perlin::new(1);
+        for p in sample_points() {
+            assert!((-1.1..=1.1).contains(&n.fbm(&p, 4)));
+            assert!((0.0..=1.1).contains(&n.turbulence(&p, 4)));
+        }
+        let p = Tuple::new_point(0.3, 0.6, 0.9);
+        assert_eq!(n.fbm(&p, 1), n.noise(&p));
+        assert_eq!(n.turbulence(&p, 1), n.noise(&p).abs());
+    }
+}
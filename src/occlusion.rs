@@ -0,0 +1,130 @@
+//! Ambient occlusion.
+//!
+//! A constant ambient term lights crevices and corners as much as open
+//! surfaces.  Ambient occlusion dims it where nearby geometry hides part of
+//! the sky.
+
+use crate::integrator;
+use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::tuple::Tuple;
+use crate::world::World;
+
+/// Ambient occlusion settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    /// Rays cast from each shaded point.  More rays mean less noise.
+    pub samples: usize,
+    /// Objects farther than this from a point do not occlude it.
+    pub radius: f64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            samples: 16,
+            radius: 1.0,
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    /// Returns fraction of the hemisphere around unit vector `normal` at point
+    /// `pt` of `world` not occluded at `time`, in `[0, 1]`.
+    ///
+    /// Directions are weighted by their cosine with `normal`, as light arriving
+    /// at grazing angles contributes less to lighting.
+    pub fn visibility(
+        &self,
+        world: &World,
+        pt: &Tuple,
+        normal: &Tuple,
+        time: f64,
+        rng: &mut Rng,
+    ) -> f64 {
+        debug_assert!(pt.is_point());
+        debug_assert!(normal.is_vector());
+        if self.samples == 0 {
+            return 1.0;
+        }
+        let occluded = (0..self.samples)
+            .filter(|_| {
+                let dir = integrator::cosine_sample_hemisphere(normal, rng);
+                let ray = Ray::new(pt.clone(), dir)
+                    .with_time(time)
+                    .with_interval(0.0, self.radius);
+                !world.intersects(&ray).is_empty()
+            })
+            .count();
+        1.0 - occluded as f64 / self.samples as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::light::PointLight;
+    use crate::shape::{Object, Shape};
+    use crate::transform;
+    use crate::tuple::ORIGIN;
+
+    fn up() -> Tuple {
+        Tuple::new_vector(0.0, 1.0, 0.0)
+    }
+
+    #[test]
+    fn open_point_is_not_occluded() {
+        let w = World::new(PointLight::new(color::WHITE, ORIGIN), vec![]);
+        let ao = AmbientOcclusion::default();
+        assert_eq!(
+            ao.visibility(&w, &ORIGIN, &up(), 0.0, &mut Rng::new(0)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn point_under_ceiling_is_occluded_within_radius() {
+        let ceiling = Shape::with_transform(Object::Plane, transform::translation(0.0, 0.5, 0.0));
+        let w = World::new(PointLight::new(color::WHITE, ORIGIN), vec![ceiling]);
+        let ao = AmbientOcclusion {
+            samples: 64,
+            radius: 1000.0,
+        };
+        assert_eq!(
+            ao.visibility(&w, &ORIGIN, &up(), 0.0, &mut Rng::new(0)),
+            0.0
+        );
+
+        // Only rays within about 60 degrees of the normal hit the ceiling
+        // within radius 1, i.e. 3/4 of cosine-weighted rays.
+        let ao = AmbientOcclusion {
+            samples: 4000,
+            radius: 1.0,
+        };
+        let v = ao.visibility(&w, &ORIGIN, &up(), 0.0, &mut Rng::new(0));
+        assert!((v - 0.25).abs() < 0.03, "{}", v);
+    }
+
+    #[test]
+    fn corner_is_more_occluded_than_wall_middle() {
+        let floor = Shape::new(Object::Plane);
+        let wall = Shape::with_transform(
+            Object::Plane,
+            &transform::translation(0.0, 0.0, 1.0)
+                * &transform::rotation_x(std::f64::consts::PI / 2.0),
+        );
+        let w = World::new(PointLight::new(color::WHITE, ORIGIN), vec![floor, wall]);
+        let ao = AmbientOcclusion {
+            samples: 500,
+            radius: 0.5,
+        };
+        let corner = Tuple::new_point(0.0, 0.00001, 0.9);
+        let open = Tuple::new_point(0.0, 0.00001, -5.0);
+        let mut rng = Rng::new(0);
+        let v_corner = ao.visibility(&w, &corner, &up(), 0.0, &mut rng);
+        let v_open = ao.visibility(&w, &open, &up(), 0.0, &mut rng);
+        assert!(v_corner < 0.9);
+        assert_eq!(v_open, 1.0);
+    }
+}
@@ -0,0 +1,970 @@
+//! Surface patterns and texture mapping.
+//!
+//! TRTC chapter 10 and bonus chapter "Texture mapping".
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::error::Error;
+use crate::matrix::Matrix;
+use crate::noise::Perlin;
+use crate::shape::Object;
+use crate::transform::Transform;
+use crate::tuple::{Tuple, ORIGIN};
+use crate::util;
+
+/// A color varying over the surface of a shape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    /// Transformation of the pattern in object space, with its inverse.
+    transform: Transform,
+    pub kind: PatternKind,
+}
+
+/// `Pattern` helper storing kind-specific bits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// A 2D texture wrapped around the shape.
+    Texture {
+        /// Maps pattern space points to texture coordinates.  `None` selects
+        /// the natural mapping of the shape (see `Object::uv_mapping()`).
+        mapping: Option<UvMapping>,
+        texture: Texture,
+    },
+    /// Another pattern sampled at points jittered by noise.
+    Perturbed {
+        pattern: Box<Pattern>,
+        noise: Perlin,
+        /// Maximum jitter distance, roughly.
+        scale: f64,
+        /// See `Perlin::fbm()`.
+        octaves: usize,
+    },
+    /// Veins of color `b` in color `a`, parallel to the yz plane and
+    /// twisted by turbulence.
+    Marble {
+        a: Color,
+        b: Color,
+        noise: Perlin,
+        /// Strength of the twisting.
+        turbulence: f64,
+        /// See `Perlin::turbulence()`.
+        octaves: usize,
+    },
+    /// Rings of colors from `a` to `b` around the y axis, one per unit,
+    /// distorted by noise.
+    Wood {
+        a: Color,
+        b: Color,
+        noise: Perlin,
+        /// Strength of the distortion.
+        turbulence: f64,
+        /// See `Perlin::fbm()`.
+        octaves: usize,
+    },
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind) -> Pattern {
+        Pattern {
+            transform: Transform::identity(),
+            kind,
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+
+    /// See `Shape::set_transform()`.
+    pub fn set_transform(&mut self, t: impl Into<Transform>) {
+        self.transform = t.into();
+    }
+
+    /// Returns an error if a transformation of this pattern, or of patterns and
+    /// textures it is made of, is not invertible.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.transform.try_inverse()?;
+        match &self.kind {
+            PatternKind::Perturbed { pattern, .. } => pattern.validate(),
+            PatternKind::Texture { texture, .. } => texture.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates a pattern mapping `texture` with the natural mapping of the shape.
+    pub fn texture(texture: Texture) -> Pattern {
+        Pattern::new(PatternKind::Texture {
+            mapping: None,
+            texture,
+        })
+    }
+
+    /// Creates a pattern mapping `texture` with `mapping`.
+    pub fn mapped_texture(mapping: UvMapping, texture: Texture) -> Pattern {
+        Pattern::new(PatternKind::Texture {
+            mapping: Some(mapping),
+            texture,
+        })
+    }
+
+    /// Returns color at point `obj_pt` in object space of a shape whose object is `object`.
+    pub fn color_at(&self, object: &Object, obj_pt: &Tuple) -> Color {
+        debug_assert!(obj_pt.is_point());
+        let pt = self.transform.inverted() * obj_pt;
+        match &self.kind {
+            PatternKind::Texture { mapping, texture } => {
+                let (u, v) = mapping.unwrap_or_else(|| object.uv_mapping()).map(&pt);
+                texture.color_at(u, v)
+            }
+            PatternKind::Perturbed {
+                pattern,
+                noise,
+                scale,
+                octaves,
+            } => {
+                // Offset samples so that the three components are uncorrelated.
+                let jitter = Tuple::new_vector(
+                    noise.fbm(&pt, *octaves),
+                    noise.fbm(&(&pt + &Tuple::new_vector(31.4, 15.9, 26.5)), *octaves),
+                    noise.fbm(&(&pt + &Tuple::new_vector(-35.8, 97.9, -32.3)), *octaves),
+                );
+                pattern.color_at(object, &(&pt + &(&jitter * *scale)))
+            }
+            PatternKind::Marble {
+                a,
+                b,
+                noise,
+                turbulence,
+                octaves,
+            } => {
+                let phase = pt.x() + turbulence * noise.turbulence(&pt, *octaves);
+                a.interpolated(b, 0.5 - 0.5 * (phase * PI).cos())
+            }
+            PatternKind::Wood {
+                a,
+                b,
+                noise,
+                turbulence,
+                octaves,
+            } => {
+                let radius = pt.x().hypot(pt.z()) + turbulence * noise.fbm(&pt, *octaves);
+                a.interpolated(b, radius.rem_euclid(1.0))
+            }
+        }
+    }
+}
+
+/// A function from 3D points to 2D texture coordinates (u, v) in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UvMapping {
+    /// Longitude and latitude on a sphere centered at the origin.  u grows
+    /// from -z toward +x and v grows upward.
+    Spherical,
+    /// x and z coordinates, repeated every unit.
+    Planar,
+    /// Angle around the y axis and y coordinate, repeated every unit.
+    Cylindrical,
+    /// Projection on the faces of the cube from -1 to 1.
+    ///
+    /// Each face covers a cell of a 4x3 grid laid out as a cross:
+    ///
+    /// ```text
+    ///         +-----+
+    ///         | up  |
+    /// +-------+-----+-------+------+
+    /// | left  |front| right | back |
+    /// +-------+-----+-------+------+
+    ///         |down |
+    ///         +-----+
+    /// ```
+    Cube,
+    /// Angle around the y axis and angle around the center circle of a torus
+    /// (see `Object::Torus`), from the outer equator and growing upward.
+    Toroidal { major_radius: f64 },
+    /// Angle around the y axis and distance from the y axis, from
+    /// `inner_radius` to 1, as on an annulus (see `Object::Disk`).
+    Polar { inner_radius: f64 },
+    /// x and z coordinates from -1 to 1, as on a `Object::Rectangle`.
+    Rectangular,
+}
+
+impl UvMapping {
+    /// Returns texture coordinates of `pt`.
+    pub fn map(&self, pt: &Tuple) -> (f64, f64) {
+        debug_assert!(pt.is_point());
+        match self {
+            UvMapping::Spherical => {
+                let radius = (pt - &ORIGIN).magnitude();
+                let phi = (pt.y() / radius).acos();
+                (azimuth(pt), 1.0 - phi / PI)
+            }
+            UvMapping::Planar => (pt.x().rem_euclid(1.0), pt.z().rem_euclid(1.0)),
+            UvMapping::Cylindrical => (azimuth(pt), pt.y().rem_euclid(1.0)),
+            UvMapping::Cube => {
+                let (face, u, v) = cube_face_uv(pt);
+                let (col, row) = match face {
+                    CubeFace::Left => (0.0, 1.0),
+                    CubeFace::Front => (1.0, 1.0),
+                    CubeFace::Right => (2.0, 1.0),
+                    CubeFace::Back => (3.0, 1.0),
+                    CubeFace::Up => (1.0, 2.0),
+                    CubeFace::Down => (1.0, 0.0),
+                };
+                ((col + u) / 4.0, (row + v) / 3.0)
+            }
+            UvMapping::Toroidal { major_radius } => {
+                let theta = pt.y().atan2(pt.x().hypot(pt.z()) - major_radius);
+                (azimuth(pt), (theta / (2.0 * PI)).rem_euclid(1.0))
+            }
+            UvMapping::Polar { inner_radius } => {
+                let radius = pt.x().hypot(pt.z());
+                let v = (radius - inner_radius) / (1.0 - inner_radius);
+                (azimuth(pt), v.clamp(0.0, 1.0))
+            }
+            UvMapping::Rectangular => (
+                ((pt.x() + 1.0) / 2.0).clamp(0.0, 1.0),
+                ((pt.z() + 1.0) / 2.0).clamp(0.0, 1.0),
+            ),
+        }
+    }
+
+    /// Returns directions (not normalized) in which u and v grow at `pt`.
+    pub fn tangents(&self, pt: &Tuple) -> (Tuple, Tuple) {
+        debug_assert!(pt.is_point());
+        let up = Tuple::new_vector(0.0, 1.0, 0.0);
+        match self {
+            UvMapping::Spherical | UvMapping::Cylindrical => {
+                let around = Tuple::new_vector(-pt.z(), 0.0, pt.x());
+                if around.magnitude() < util::EPSILON {
+                    // On the y axis, any horizontal direction will do.
+                    (Tuple::new_vector(1.0, 0.0, 0.0), up)
+                } else {
+                    (around, up)
+                }
+            }
+            UvMapping::Planar | UvMapping::Rectangular => (
+                Tuple::new_vector(1.0, 0.0, 0.0),
+                Tuple::new_vector(0.0, 0.0, 1.0),
+            ),
+            UvMapping::Cube => match cube_face_uv(pt).0 {
+                CubeFace::Front => (Tuple::new_vector(1.0, 0.0, 0.0), up),
+                CubeFace::Back => (Tuple::new_vector(-1.0, 0.0, 0.0), up),
+                CubeFace::Right => (Tuple::new_vector(0.0, 0.0, -1.0), up),
+                CubeFace::Left => (Tuple::new_vector(0.0, 0.0, 1.0), up),
+                CubeFace::Up => (
+                    Tuple::new_vector(1.0, 0.0, 0.0),
+                    Tuple::new_vector(0.0, 0.0, -1.0),
+                ),
+                CubeFace::Down => (
+                    Tuple::new_vector(1.0, 0.0, 0.0),
+                    Tuple::new_vector(0.0, 0.0, 1.0),
+                ),
+            },
+            UvMapping::Toroidal { major_radius } => {
+                let around = Tuple::new_vector(-pt.z(), 0.0, pt.x());
+                let rho = around.magnitude();
+                if rho < util::EPSILON {
+                    return (Tuple::new_vector(1.0, 0.0, 0.0), up);
+                }
+                // Around the center circle, in the plane of the y axis.
+                let outward = Tuple::new_vector(pt.x() / rho, 0.0, pt.z() / rho);
+                let across = &(&outward * -pt.y()) + &(&up * (rho - major_radius));
+                if across.magnitude() < util::EPSILON {
+                    (around, up)
+                } else {
+                    (around, across)
+                }
+            }
+            UvMapping::Polar { .. } => {
+                let around = Tuple::new_vector(-pt.z(), 0.0, pt.x());
+                if around.magnitude() < util::EPSILON {
+                    // At the center, any horizontal directions will do.
+                    (
+                        Tuple::new_vector(1.0, 0.0, 0.0),
+                        Tuple::new_vector(0.0, 0.0, 1.0),
+                    )
+                } else {
+                    (around, Tuple::new_vector(pt.x(), 0.0, pt.z()))
+                }
+            }
+        }
+    }
+}
+
+/// Returns angle of `pt` around the y axis, scaled to `[0, 1]`.
+fn azimuth(pt: &Tuple) -> f64 {
+    let theta = pt.x().atan2(pt.z());
+    1.0 - (theta / (2.0 * PI) + 0.5)
+}
+
+/// A face of the cube from -1 to 1.  Front is +z, right is +x and up is +y.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+/// Returns face of the cube `pt` projects to and texture coordinates on this face.
+///
+/// Faces are unfolded around the y axis so that neighboring faces share edges.
+pub fn cube_face_uv(pt: &Tuple) -> (CubeFace, f64, f64) {
+    let (x, y, z) = (pt.x(), pt.y(), pt.z());
+    let coord = x.abs().max(y.abs()).max(z.abs());
+    let uv = |u: f64, v: f64| ((u + 1.0) / 2.0, (v + 1.0) / 2.0);
+    let (face, (u, v)) = if coord == x {
+        (CubeFace::Right, uv(-z / coord, y / coord))
+    } else if coord == -x {
+        (CubeFace::Left, uv(z / coord, y / coord))
+    } else if coord == y {
+        (CubeFace::Up, uv(x / coord, -z / coord))
+    } else if coord == -y {
+        (CubeFace::Down, uv(x / coord, z / coord))
+    } else if coord == z {
+        (CubeFace::Front, uv(x / coord, y / coord))
+    } else {
+        (CubeFace::Back, uv(-x / coord, y / coord))
+    };
+    (face, u, v)
+}
+
+/// A 2D color function over texture coordinates (u, v) in `[0, 1]`.
+#[derive(Clone)]
+pub enum Texture {
+    /// Arbitrary function of (u, v).
+    Function(fn(f64, f64) -> Color),
+    /// Alternating squares of colors `a` and `b`, `width` squares along u and
+    /// `height` squares along v.
+    Checkers {
+        width: usize,
+        height: usize,
+        a: Color,
+        b: Color,
+    },
+    /// Image sampled at (u, v).
+    Image(ImageTexture),
+}
+
+impl Texture {
+    /// Returns an error if the transformation of this texture's image is not
+    /// invertible.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Texture::Image(image) = self {
+            image.transform.try_inverse()?;
+        }
+        Ok(())
+    }
+
+    /// Returns color at texture coordinates (u, v).
+    pub fn color_at(&self, u: f64, v: f64) -> Color {
+        match self {
+            Texture::Function(f) => f(u, v),
+            Texture::Checkers {
+                width,
+                height,
+                a,
+                b,
+            } => {
+                let u2 = (u * *width as f64).floor() as i64;
+                let v2 = (v * *height as f64).floor() as i64;
+                if (u2 + v2) % 2 == 0 {
+                    a.clone()
+                } else {
+                    b.clone()
+                }
+            }
+            Texture::Image(image) => image.color_at(u, v),
+        }
+    }
+}
+
+impl PartialEq for Texture {
+    fn eq(&self, o: &Self) -> bool {
+        match (self, o) {
+            // Identical functions may have different addresses, so this is
+            // only a best effort.
+            (Texture::Function(l), Texture::Function(r)) => *l as usize == *r as usize,
+            (
+                Texture::Checkers {
+                    width: lw,
+                    height: lh,
+                    a: la,
+                    b: lb,
+                },
+                Texture::Checkers {
+                    width: rw,
+                    height: rh,
+                    a: ra,
+                    b: rb,
+                },
+            ) => lw == rw && lh == rh && la == ra && lb == rb,
+            (Texture::Image(l), Texture::Image(r)) => l == r,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Texture {
+    // Do not dump image pixels.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Texture::Function(_) => f.write_str("Function"),
+            Texture::Checkers {
+                width,
+                height,
+                a,
+                b,
+            } => f
+                .debug_struct("Checkers")
+                .field("width", width)
+                .field("height", height)
+                .field("a", a)
+                .field("b", b)
+                .finish(),
+            Texture::Image(image) => image.fmt(f),
+        }
+    }
+}
+
+/// A texture backed by a canvas, e.g. loaded with `Canvas::from_ppm()`.
+///
+/// The canvas covers texture coordinates from (0, 0) at its bottom left
+/// corner to (1, 1) at its top right corner.
+#[derive(Clone)]
+pub struct ImageTexture {
+    /// Shared by all textures using the same image.
+    pub canvas: Arc<Canvas>,
+    pub filter: Filter,
+    pub addressing: Addressing,
+
+    /// Transformation of the image in texture space, applied to points (u, v, 0),
+    /// with its inverse.
+    transform: Transform,
+}
+
+/// How colors are reconstructed between pixel centers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Color of the nearest pixel.
+    Nearest,
+    /// Blend of the four nearest pixels.
+    Bilinear,
+}
+
+/// How texture coordinates outside of the image are handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Addressing {
+    /// The image repeats.
+    Wrap,
+    /// The edge pixels extend indefinitely.
+    Clamp,
+    /// The image repeats, flipped every other time.
+    Mirror,
+}
+
+impl Addressing {
+    /// Maps pixel index `i` to a valid index in `0..n`.
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            Addressing::Wrap => i.rem_euclid(n),
+            Addressing::Clamp => i.clamp(0, n - 1),
+            Addressing::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+impl ImageTexture {
+    /// Creates a texture with bilinear filtering and wrapping addressing.
+    pub fn new(canvas: Arc<Canvas>) -> ImageTexture {
+        ImageTexture {
+            canvas,
+            filter: Filter::Bilinear,
+            addressing: Addressing::Wrap,
+            transform: Transform::identity(),
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+
+    /// See `Shape::set_transform()`.
+    pub fn set_transform(&mut self, t: impl Into<Transform>) {
+        self.transform = t.into();
+    }
+
+    /// Returns color at texture coordinates (u, v).
+    pub fn color_at(&self, u: f64, v: f64) -> Color {
+        let pt = self.transform.inverted() * &Tuple::new_point(u, v, 0.0);
+        // Continuous pixel coordinates, with pixel centers at half integers.
+        let x = pt.x() * self.canvas.width() as f64;
+        let y = (1.0 - pt.y()) * self.canvas.height() as f64;
+        match self.filter {
+            Filter::Nearest => self.pixel(x.floor() as i64, y.floor() as i64).clone(),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.pixel(x0, y0).interpolated(self.pixel(x0 + 1, y0), tx);
+                let bottom = self
+                    .pixel(x0, y0 + 1)
+                    .interpolated(self.pixel(x0 + 1, y0 + 1), tx);
+                top.interpolated(&bottom, ty)
+            }
+        }
+    }
+
+    /// Returns pixel at possibly out of bounds coordinates.
+    fn pixel(&self, x: i64, y: i64) -> &Color {
+        self.canvas.get(
+            self.addressing.apply(x, self.canvas.width()),
+            self.addressing.apply(y, self.canvas.height()),
+        )
+    }
+}
+
+impl PartialEq for ImageTexture {
+    fn eq(&self, o: &Self) -> bool {
+        (Arc::ptr_eq(&self.canvas, &o.canvas) || self.canvas == o.canvas)
+            && self.filter == o.filter
+            && self.addressing == o.addressing
+            && self.transform == o.transform
+    }
+}
+
+impl fmt::Debug for ImageTexture {
+    // Do not dump image pixels.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field(
+                "canvas",
+                &format_args!("{}x{}", self.canvas.width(), self.canvas.height()),
+            )
+            .field("filter", &self.filter)
+            .field("addressing", &self.addressing)
+            .field("transform", &self.transform)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::transform;
+
+    fn checkers(width: usize, height: usize) -> Texture {
+        Texture::Checkers {
+            width,
+            height,
+            a: color::BLACK,
+            b: color::WHITE,
+        }
+    }
+
+    #[test]
+    fn checkers_texture() {
+        let t = checkers(2, 2);
+        assert_eq!(t.color_at(0.0, 0.0), color::BLACK);
+        assert_eq!(t.color_at(0.5, 0.0), color::WHITE);
+        assert_eq!(t.color_at(0.0, 0.5), color::WHITE);
+        assert_eq!(t.color_at(0.5, 0.5), color::BLACK);
+        assert_eq!(t.color_at(1.0, 1.0), color::BLACK);
+    }
+
+    #[test]
+    fn function_texture() {
+        let t = Texture::Function(|u, v| Color::new(u, v, 0.0));
+        assert_eq!(t.color_at(0.25, 0.75), Color::new(0.25, 0.75, 0.0));
+    }
+
+    /// Returns nearest-filtered texture of a 2x2 image with red at the bottom
+    /// left and green at the top right.
+    fn image() -> ImageTexture {
+        let mut c = Canvas::new(2, 2);
+        c.set(0, 1, &color::RED);
+        c.set(1, 0, &color::GREEN);
+        ImageTexture {
+            filter: Filter::Nearest,
+            ..ImageTexture::new(Arc::new(c))
+        }
+    }
+
+    #[test]
+    fn validating_image_texture_transformations() {
+        let mut t = image();
+        let p = Pattern::texture(Texture::Image(t.clone()));
+        assert_eq!(p.validate(), Ok(()));
+        t.set_transform(transform::scaling(0.0, 1.0, 1.0));
+        let p = Pattern::texture(Texture::Image(t));
+        assert_eq!(p.validate(), Err(Error::SingularMatrix));
+    }
+
+    #[test]
+    fn image_texture_has_v_growing_upward() {
+        let t = Texture::Image(image());
+        assert_eq!(t.color_at(0.1, 0.1), color::RED);
+        assert_eq!(t.color_at(0.9, 0.9), color::GREEN);
+        assert_eq!(t.color_at(0.0, 0.9), color::BLACK);
+    }
+
+    #[test]
+    fn bilinear_filtering() {
+        let t = ImageTexture {
+            filter: Filter::Bilinear,
+            addressing: Addressing::Clamp,
+            ..image()
+        };
+        // Pixel centers.
+        assert_eq!(t.color_at(0.25, 0.25), color::RED);
+        assert_eq!(t.color_at(0.75, 0.75), color::GREEN);
+        // Halfway between centers.
+        assert_eq!(t.color_at(0.5, 0.25), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(t.color_at(0.5, 0.5), Color::new(0.25, 0.25, 0.0));
+        // Clamped beyond centers of edge pixels.
+        assert_eq!(t.color_at(0.0, 0.0), color::RED);
+    }
+
+    #[test]
+    fn bilinear_filtering_wraps_at_edges() {
+        let t = ImageTexture {
+            filter: Filter::Bilinear,
+            ..image()
+        };
+        assert_eq!(t.color_at(0.0, 0.25), Color::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn addressing_modes() {
+        let mut t = image();
+        assert_eq!(t.color_at(1.1, 0.1), color::RED);
+        assert_eq!(t.color_at(-0.9, 0.1), color::RED);
+        t.addressing = Addressing::Clamp;
+        assert_eq!(t.color_at(1.1, 0.1), color::BLACK);
+        assert_eq!(t.color_at(-0.9, 0.1), color::RED);
+        t.addressing = Addressing::Mirror;
+        assert_eq!(t.color_at(1.1, 0.1), color::BLACK);
+        assert_eq!(t.color_at(1.6, 0.1), color::RED);
+        assert_eq!(t.color_at(-0.1, 0.1), color::RED);
+        assert_eq!(t.color_at(-0.6, 0.1), color::BLACK);
+    }
+
+    #[test]
+    fn texture_space_transformation() {
+        let mut t = image();
+        t.set_transform(transform::scaling(0.5, 0.5, 1.0));
+        assert_eq!(t.color_at(0.1, 0.1), color::RED);
+        assert_eq!(t.color_at(0.3, 0.1), color::BLACK);
+        assert_eq!(t.color_at(0.6, 0.1), color::RED);
+        t.set_transform(transform::translation(0.5, 0.0, 0.0));
+        assert_eq!(t.color_at(0.6, 0.1), color::RED);
+        assert_eq!(t.color_at(0.1, 0.1), color::BLACK);
+    }
+
+    #[test]
+    fn spherical_mapping() {
+        let s2 = 2_f64.sqrt() / 2.0;
+        let cases = [
+            (Tuple::new_point(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (Tuple::new_point(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (Tuple::new_point(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (Tuple::new_point(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (Tuple::new_point(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Tuple::new_point(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (Tuple::new_point(s2, s2, 0.0), (0.25, 0.75)),
+        ];
+        for (pt, (u, v)) in &cases {
+            let (mu, mv) = UvMapping::Spherical.map(pt);
+            assert!((mu - u).abs() < 1e-9, "{:?}", pt);
+            assert!((mv - v).abs() < 1e-9, "{:?}", pt);
+        }
+    }
+
+    #[test]
+    fn planar_mapping() {
+        let cases = [
+            (Tuple::new_point(0.25, 0.0, 0.5), (0.25, 0.5)),
+            (Tuple::new_point(0.25, 0.0, -0.25), (0.25, 0.75)),
+            (Tuple::new_point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (Tuple::new_point(1.25, 0.0, 0.5), (0.25, 0.5)),
+            (Tuple::new_point(-0.25, 0.0, 1.0), (0.75, 0.0)),
+        ];
+        for (pt, uv) in &cases {
+            assert_eq!(UvMapping::Planar.map(pt), *uv);
+        }
+    }
+
+    #[test]
+    fn cylindrical_mapping() {
+        let cases = [
+            (Tuple::new_point(0.0, 0.0, -1.0), (0.0, 0.0)),
+            (Tuple::new_point(0.0, 0.5, -1.0), (0.0, 0.5)),
+            (Tuple::new_point(0.0, 1.0, -1.0), (0.0, 0.0)),
+            (Tuple::new_point(1.0, 0.25, 0.0), (0.25, 0.25)),
+            (Tuple::new_point(0.0, -0.25, 1.0), (0.5, 0.75)),
+            (Tuple::new_point(-1.0, 1.25, 0.0), (0.75, 0.25)),
+        ];
+        for (pt, uv) in &cases {
+            assert_eq!(UvMapping::Cylindrical.map(pt), *uv);
+        }
+    }
+
+    #[test]
+    fn toroidal_mapping() {
+        let mapping = UvMapping::Toroidal { major_radius: 2.0 };
+        let cases = [
+            (Tuple::new_point(0.0, 0.0, -2.5), (0.0, 0.0)),
+            (Tuple::new_point(0.0, 0.5, -2.0), (0.0, 0.25)),
+            (Tuple::new_point(0.0, 0.0, -1.5), (0.0, 0.5)),
+            (Tuple::new_point(0.0, -0.5, -2.0), (0.0, 0.75)),
+            (Tuple::new_point(2.5, 0.0, 0.0), (0.25, 0.0)),
+            (Tuple::new_point(-2.0, 0.5, 0.0), (0.75, 0.25)),
+        ];
+        for (pt, uv) in &cases {
+            assert_eq!(mapping.map(pt), *uv, "{:?}", pt);
+        }
+    }
+
+    #[test]
+    fn polar_and_rectangular_mappings() {
+        let polar = UvMapping::Polar { inner_radius: 0.5 };
+        let cases = [
+            (Tuple::new_point(0.0, 0.0, -0.5), (0.0, 0.0)),
+            (Tuple::new_point(0.75, 0.0, 0.0), (0.25, 0.5)),
+            (Tuple::new_point(0.0, 0.0, 1.0), (0.5, 1.0)),
+            (Tuple::new_point(0.0, 0.0, -0.1), (0.0, 0.0)),
+        ];
+        for (pt, uv) in &cases {
+            assert_eq!(polar.map(pt), *uv, "{:?}", pt);
+        }
+        let cases = [
+            (Tuple::new_point(-1.0, 0.0, -1.0), (0.0, 0.0)),
+            (Tuple::new_point(0.0, 0.0, 0.5), (0.5, 0.75)),
+            (Tuple::new_point(1.0, 0.0, 1.0), (1.0, 1.0)),
+        ];
+        for (pt, uv) in &cases {
+            assert_eq!(UvMapping::Rectangular.map(pt), *uv, "{:?}", pt);
+        }
+    }
+
+    #[test]
+    fn identifying_cube_faces() {
+        let cases = [
+            (Tuple::new_point(-1.0, 0.5, -0.25), CubeFace::Left),
+            (Tuple::new_point(1.1, -0.75, 0.8), CubeFace::Right),
+            (Tuple::new_point(0.1, 0.6, 0.9), CubeFace::Front),
+            (Tuple::new_point(-0.7, 0.0, -2.0), CubeFace::Back),
+            (Tuple::new_point(0.5, 1.0, 0.9), CubeFace::Up),
+            (Tuple::new_point(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+        for (pt, face) in &cases {
+            assert_eq!(cube_face_uv(pt).0, *face, "{:?}", pt);
+        }
+    }
+
+    #[test]
+    fn uv_on_cube_faces() {
+        let cases = [
+            (
+                Tuple::new_point(-0.5, 0.5, 1.0),
+                CubeFace::Front,
+                (0.25, 0.75),
+            ),
+            (
+                Tuple::new_point(0.5, -0.5, 1.0),
+                CubeFace::Front,
+                (0.75, 0.25),
+            ),
+            (
+                Tuple::new_point(0.5, 0.5, -1.0),
+                CubeFace::Back,
+                (0.25, 0.75),
+            ),
+            (
+                Tuple::new_point(-1.0, 0.5, -0.5),
+                CubeFace::Left,
+                (0.25, 0.75),
+            ),
+            (
+                Tuple::new_point(1.0, 0.5, 0.5),
+                CubeFace::Right,
+                (0.25, 0.75),
+            ),
+            (
+                Tuple::new_point(-0.5, 1.0, -0.5),
+                CubeFace::Up,
+                (0.25, 0.75),
+            ),
+            (
+                Tuple::new_point(-0.5, -1.0, 0.5),
+                CubeFace::Down,
+                (0.25, 0.75),
+            ),
+        ];
+        for (pt, face, (u, v)) in &cases {
+            assert_eq!(cube_face_uv(pt), (*face, *u, *v), "{:?}", pt);
+        }
+    }
+
+    #[test]
+    fn cube_mapping_lays_faces_out_as_cross() {
+        let (u, v) = UvMapping::Cube.map(&Tuple::new_point(0.0, 0.0, 1.0));
+        assert_eq!((u, v), (0.375, 0.5));
+        let (u, v) = UvMapping::Cube.map(&Tuple::new_point(0.0, 1.0, 0.0));
+        assert_eq!((u, v), (0.375, 2.5 / 3.0));
+        let (u, v) = UvMapping::Cube.map(&Tuple::new_point(0.0, 0.0, -1.0));
+        assert_eq!((u, v), (0.875, 0.5));
+    }
+
+    #[test]
+    fn tangents_follow_growing_coordinates() {
+        let toroidal = UvMapping::Toroidal { major_radius: 1.0 };
+        let polar = UvMapping::Polar { inner_radius: 0.2 };
+        let cases = [
+            (UvMapping::Spherical, Tuple::new_point(0.0, 0.0, -1.0)),
+            (UvMapping::Spherical, Tuple::new_point(0.6, 0.0, 0.8)),
+            (UvMapping::Spherical, Tuple::new_point(0.0, 0.6, 0.8)),
+            (UvMapping::Cylindrical, Tuple::new_point(-1.0, 0.3, 0.0)),
+            (UvMapping::Planar, Tuple::new_point(0.3, 0.0, 0.4)),
+            (UvMapping::Cube, Tuple::new_point(0.3, 0.2, 1.0)),
+            (UvMapping::Cube, Tuple::new_point(0.3, 0.2, -1.0)),
+            (UvMapping::Cube, Tuple::new_point(1.0, 0.3, 0.2)),
+            (UvMapping::Cube, Tuple::new_point(-1.0, 0.3, 0.2)),
+            (UvMapping::Cube, Tuple::new_point(0.3, 1.0, 0.2)),
+            (UvMapping::Cube, Tuple::new_point(0.3, -1.0, 0.2)),
+            (toroidal, Tuple::new_point(0.0, 0.0, -1.25)),
+            (toroidal, Tuple::new_point(0.6, 0.2, 0.8)),
+            (toroidal, Tuple::new_point(0.6, -0.2, 0.6)),
+            (polar, Tuple::new_point(0.4, 0.0, -0.4)),
+            (polar, Tuple::new_point(-0.5, 0.0, 0.1)),
+            (UvMapping::Rectangular, Tuple::new_point(0.3, 0.0, -0.4)),
+        ];
+        for (mapping, pt) in &cases {
+            let (t, b) = mapping.tangents(pt);
+            let (u, v) = mapping.map(pt);
+            let (u1, _) = mapping.map(&(pt + &(&t.normalized() * 0.001)));
+            let (_, v1) = mapping.map(&(pt + &(&b.normalized() * 0.001)));
+            assert!(u1 > u, "{:?} {:?}", mapping, pt);
+            assert!(v1 > v, "{:?} {:?}", mapping, pt);
+        }
+    }
+
+    #[test]
+    fn pattern_uses_natural_mapping_of_object() {
+        let p = Pattern::texture(Texture::Function(|u, v| Color::new(u, v, 0.0)));
+        let pt = Tuple::new_point(1.0, 0.0, 0.0);
+        assert_eq!(p.color_at(&Object::Sphere, &pt), Color::new(0.25, 0.5, 0.0));
+        let pt = Tuple::new_point(0.25, 0.0, 0.5);
+        assert_eq!(p.color_at(&Object::Plane, &pt), Color::new(0.25, 0.5, 0.0));
+    }
+
+    fn stripes() -> Pattern {
+        Pattern::mapped_texture(UvMapping::Planar, checkers(2, 1))
+    }
+
+    #[test]
+    fn perturbed_pattern_jitters_sample_points() {
+        let p = Pattern::new(PatternKind::Perturbed {
+            pattern: Box::new(stripes()),
+            noise: Perlin::new(0),
+            scale: 0.5,
+            octaves: 2,
+        });
+        let points: Vec<Tuple> = (0..100)
+            .map(|i| Tuple::new_point(i as f64 * 0.0371, 0.3, 0.7))
+            .collect();
+        let changed = points
+            .iter()
+            .filter(|pt| p.color_at(&Object::Sphere, pt) != stripes().color_at(&Object::Sphere, pt))
+            .count();
+        assert!(changed > 0 && changed < 100);
+    }
+
+    #[test]
+    fn perturbed_pattern_without_jitter() {
+        let p = Pattern::new(PatternKind::Perturbed {
+            pattern: Box::new(stripes()),
+            noise: Perlin::new(0),
+            scale: 0.0,
+            octaves: 2,
+        });
+        let pt = Tuple::new_point(0.75, 0.3, 0.7);
+        assert_eq!(p.color_at(&Object::Sphere, &pt), color::WHITE);
+    }
+
+    #[test]
+    fn marble_pattern_without_turbulence_is_striped() {
+        let p = Pattern::new(PatternKind::Marble {
+            a: color::BLACK,
+            b: color::WHITE,
+            noise: Perlin::new(0),
+            turbulence: 0.0,
+            octaves: 4,
+        });
+        let color_at = |x: f64| p.color_at(&Object::Sphere, &Tuple::new_point(x, 0.4, 0.2));
+        assert_eq!(color_at(0.0), color::BLACK);
+        assert_eq!(color_at(1.0), color::WHITE);
+        assert_eq!(color_at(0.5), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(color_at(2.0), color::BLACK);
+    }
+
+    #[test]
+    fn wood_pattern_without_turbulence_has_rings() {
+        let p = Pattern::new(PatternKind::Wood {
+            a: color::BLACK,
+            b: color::WHITE,
+            noise: Perlin::new(0),
+            turbulence: 0.0,
+            octaves: 4,
+        });
+        let color_at = |x: f64, z: f64| p.color_at(&Object::Plane, &Tuple::new_point(x, 7.0, z));
+        assert_eq!(color_at(0.0, 0.0), color::BLACK);
+        assert_eq!(color_at(0.0, 1.25), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(color_at(0.6, 0.8), color::BLACK);
+        assert_eq!(color_at(-1.5, 0.0), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn turbulence_distorts_marble() {
+        let p = Pattern::new(PatternKind::Marble {
+            a: color::BLACK,
+            b: color::WHITE,
+            noise: Perlin::new(0),
+            turbulence: 2.0,
+            octaves: 4,
+        });
+        let pt = Tuple::new_point(0.0, 0.4, 0.2);
+        assert_ne!(p.color_at(&Object::Sphere, &pt), color::BLACK);
+    }
+
+    #[test]
+    fn pattern_transformation() {
+        let mut p = Pattern::mapped_texture(UvMapping::Planar, checkers(2, 2));
+        assert_eq!(
+            p.color_at(&Object::Sphere, &Tuple::new_point(0.75, 0.0, 0.25)),
+            color::WHITE
+        );
+        p.set_transform(transform::scaling(2.0, 2.0, 2.0));
+        assert_eq!(
+            p.color_at(&Object::Sphere, &Tuple::new_point(0.75, 0.0, 0.25)),
+            color::BLACK
+        );
+    }
+}
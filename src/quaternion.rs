@@ -0,0 +1,271 @@
+//! Quaternions representing 3D rotations.
+//!
+//! Unlike Euler angles and rotation matrices, unit quaternions interpolate
+//! smoothly from one orientation to another (see `Quaternion::slerp()`).
+
+use crate::matrix::Matrix;
+use crate::tuple::Tuple;
+use crate::util;
+
+/// Above this cosine of the angle between two quaternions, `slerp()` falls
+/// back to linear interpolation to avoid dividing by a tiny sine.
+const SLERP_LINEAR_THRESHOLD: f64 = 0.9995;
+
+/// A quaternion `w + xi + yj + zk`.  Rotations are unit quaternions.
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quaternion {
+    /// The rotation leaving everything in place.
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    /// Returns rotation of `angle` radians around vector `axis`, clockwise
+    /// when looking along `axis` like `transform::rotation_x()` and friends.
+    pub fn from_axis_angle(axis: &Tuple, angle: f64) -> Quaternion {
+        debug_assert!(axis.is_vector());
+        let a = axis.normalized();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quaternion::new(cos, a.x() * sin, a.y() * sin, a.z() * sin)
+    }
+
+    /// Returns rotation encoded in the upper left 3x3 part of `m`, which must
+    /// be a rotation matrix, i.e. orthonormal with determinant 1.
+    pub fn from_matrix(m: &Matrix) -> Quaternion {
+        let e = |r, c| m.get(r, c);
+        let trace = e(0, 0) + e(1, 1) + e(2, 2);
+        // Divide by the largest of the four candidates for accuracy.
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (e(2, 1) - e(1, 2)) / s,
+                (e(0, 2) - e(2, 0)) / s,
+                (e(1, 0) - e(0, 1)) / s,
+            )
+        } else if e(0, 0) > e(1, 1) && e(0, 0) > e(2, 2) {
+            let s = (1.0 + e(0, 0) - e(1, 1) - e(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (e(2, 1) - e(1, 2)) / s,
+                s / 4.0,
+                (e(0, 1) + e(1, 0)) / s,
+                (e(0, 2) + e(2, 0)) / s,
+            )
+        } else if e(1, 1) > e(2, 2) {
+            let s = (1.0 + e(1, 1) - e(0, 0) - e(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (e(0, 2) - e(2, 0)) / s,
+                (e(0, 1) + e(1, 0)) / s,
+                s / 4.0,
+                (e(1, 2) + e(2, 1)) / s,
+            )
+        } else {
+            let s = (1.0 + e(2, 2) - e(0, 0) - e(1, 1)).sqrt() * 2.0;
+            Quaternion::new(
+                (e(1, 0) - e(0, 1)) / s,
+                (e(0, 2) + e(2, 0)) / s,
+                (e(1, 2) + e(2, 1)) / s,
+                s / 4.0,
+            )
+        };
+        q.normalized()
+    }
+
+    pub fn w(&self) -> f64 {
+        self.w
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    pub fn z(&self) -> f64 {
+        self.z
+    }
+
+    pub fn dot(&self, o: &Quaternion) -> f64 {
+        self.w * o.w + self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let m = self.magnitude();
+        Quaternion::new(self.w / m, self.x / m, self.y / m, self.z / m)
+    }
+
+    /// Returns the 4x4 transformation matrix encoding this rotation, which must
+    /// be a unit quaternion.
+    pub fn to_matrix(&self) -> Matrix {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix::new_4x4(&[
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns rotation between this one (`t == 0`) and `o` (`t == 1`), turning
+    /// at constant speed along the shortest path (spherical linear
+    /// interpolation).
+    pub fn slerp(&self, o: &Quaternion, t: f64) -> Quaternion {
+        // q and -q are the same rotation: pick the closest one.
+        let mut cos = self.dot(o);
+        let o = if cos < 0.0 {
+            cos = -cos;
+            Quaternion::new(-o.w, -o.x, -o.y, -o.z)
+        } else {
+            *o
+        };
+        let (a, b) = if cos > SLERP_LINEAR_THRESHOLD {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion::new(
+            a * self.w + b * o.w,
+            a * self.x + b * o.x,
+            a * self.y + b * o.y,
+            a * self.z + b * o.z,
+        )
+        .normalized()
+    }
+}
+
+impl PartialEq for Quaternion {
+    /// Return true if arguments are approximately equal.
+    fn eq(&self, o: &Quaternion) -> bool {
+        util::nearly_equal(self.w, o.w)
+            && util::nearly_equal(self.x, o.x)
+            && util::nearly_equal(self.y, o.y)
+            && util::nearly_equal(self.z, o.z)
+    }
+}
+
+impl std::ops::Mul for &Quaternion {
+    type Output = Quaternion;
+
+    /// Returns Hamilton product: as rotations, `o` followed by `self`, like
+    /// the product of their matrices.
+    fn mul(self, o: &Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn axis_angle_rotations_match_euler_rotations() {
+        let x = Tuple::new_vector(1.0, 0.0, 0.0);
+        let y = Tuple::new_vector(0.0, 2.0, 0.0);
+        let z = Tuple::new_vector(0.0, 0.0, -1.0);
+        assert_eq!(
+            Quaternion::from_axis_angle(&x, 0.3).to_matrix(),
+            transform::rotation_x(0.3)
+        );
+        assert_eq!(
+            Quaternion::from_axis_angle(&y, 1.2).to_matrix(),
+            transform::rotation_y(1.2)
+        );
+        assert_eq!(
+            Quaternion::from_axis_angle(&z, 2.0).to_matrix(),
+            transform::rotation_z(-2.0)
+        );
+        assert_eq!(Quaternion::IDENTITY.to_matrix(), Matrix::new_4x4_identity());
+    }
+
+    #[test]
+    fn converting_to_matrix_and_back() {
+        let axis = Tuple::new_vector(1.0, -2.0, 0.5);
+        // Angles covering all branches of the conversion.
+        for angle in &[0.1, 1.0, 2.5, PI, -3.0] {
+            let q = Quaternion::from_axis_angle(&axis, *angle);
+            let back = Quaternion::from_matrix(&q.to_matrix());
+            assert!(back == q || back == Quaternion::new(-q.w, -q.x, -q.y, -q.z));
+        }
+        for m in &[
+            transform::rotation_x(PI),
+            transform::rotation_y(PI),
+            transform::rotation_z(PI),
+        ] {
+            assert_eq!(Quaternion::from_matrix(m).to_matrix(), *m);
+        }
+    }
+
+    #[test]
+    fn multiplying_quaternions_composes_rotations() {
+        let a = Quaternion::from_axis_angle(&Tuple::new_vector(0.0, 1.0, 0.0), 0.7);
+        let b = Quaternion::from_axis_angle(&Tuple::new_vector(1.0, 1.0, 0.0), -1.1);
+        assert_eq!((&a * &b).to_matrix(), &a.to_matrix() * &b.to_matrix());
+    }
+
+    #[test]
+    fn slerp_turns_at_constant_speed() {
+        let axis = Tuple::new_vector(0.0, 0.0, 1.0);
+        let a = Quaternion::from_axis_angle(&axis, 0.2);
+        let b = Quaternion::from_axis_angle(&axis, 1.8);
+        assert_eq!(a.slerp(&b, 0.0), a);
+        assert_eq!(a.slerp(&b, 1.0), b);
+        assert_eq!(a.slerp(&b, 0.25), Quaternion::from_axis_angle(&axis, 0.6));
+
+        // The negated quaternion is the same rotation: no detour.
+        let minus_b = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+        assert_eq!(
+            a.slerp(&minus_b, 0.5).to_matrix(),
+            transform::rotation_z(1.0)
+        );
+
+        // Nearly identical rotations.
+        let c = Quaternion::from_axis_angle(&axis, 0.2001);
+        assert_eq!(
+            a.slerp(&c, 0.5),
+            Quaternion::from_axis_angle(&axis, 0.20005)
+        );
+    }
+}
@@ -6,17 +6,31 @@ use crate::matrix::Matrix;
 use crate::tuple::Tuple;
 
 /// An immutable ray.
-#[derive(Debug, PartialEq)]
+///
+/// Rays also carry the instant they are cast at so that moving shapes can be
+/// intersected where they are at that time, and the interval of distances from
+/// their origin where intersections count.
+#[derive(Debug, PartialEq, Clone)]
 pub struct Ray {
     origin: Tuple,
     direction: Tuple,
+    time: f64,
+    t_min: f64,
+    t_max: f64,
 }
 
 impl Ray {
+    /// Creates a ray cast at time 0, intersecting at any distance.
     pub fn new(origin: Tuple, direction: Tuple) -> Ray {
         debug_assert!(origin.is_point());
         debug_assert!(direction.is_vector());
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+            t_min: f64::NEG_INFINITY,
+            t_max: f64::INFINITY,
+        }
     }
 
     /// Creates a ray from language tuples rather than `Tuple` values.
@@ -26,6 +40,25 @@ impl Ray {
         Ray {
             origin: Tuple::new_point(x, y, z),
             direction: Tuple::new_vector(dx, dy, dz),
+            time: 0.0,
+            t_min: f64::NEG_INFINITY,
+            t_max: f64::INFINITY,
+        }
+    }
+
+    /// Returns this ray cast at `time` instead.
+    pub fn with_time(self, time: f64) -> Ray {
+        Ray { time, ..self }
+    }
+
+    /// Returns this ray intersecting only at distances in `[t_min, t_max]` from
+    /// its origin, in units of its direction vector.
+    pub fn with_interval(self, t_min: f64, t_max: f64) -> Ray {
+        debug_assert!(t_min <= t_max);
+        Ray {
+            t_min,
+            t_max,
+            ..self
         }
     }
 
@@ -37,16 +70,37 @@ impl Ray {
         &self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn t_min(&self) -> f64 {
+        self.t_min
+    }
+
+    pub fn t_max(&self) -> f64 {
+        self.t_max
+    }
+
+    /// Returns whether distance `t` is in the interval of this ray.
+    pub fn contains(&self, t: f64) -> bool {
+        self.t_min <= t && t <= self.t_max
+    }
+
     /// Returns coordinates of point on this ray at distance `t` from origin.
     pub fn position(&self, t: f64) -> Tuple {
         &self.origin + &(&self.direction * t)
     }
 
     /// Apply transformation encoded in `m` to this ray and return resulting ray.
+    ///
+    /// Distances along the ray, hence its interval, are preserved.
     pub fn transformed(&self, m: &Matrix) -> Ray {
         let o = m * &self.origin;
         let d = m * &self.direction;
         Ray::new(o, d)
+            .with_time(self.time)
+            .with_interval(self.t_min, self.t_max)
     }
 }
 
@@ -72,6 +126,35 @@ mod tests {
         assert_eq!(*r.direction(), Tuple::new_vector(4.0, 5.0, 6.0));
     }
 
+    #[test]
+    fn rays_are_cast_at_time_zero_by_default() {
+        let r = Ray::from_triplets((1.0, 2.0, 3.0), (4.0, 5.0, 6.0));
+        assert_eq!(r.time(), 0.0);
+        assert_eq!(r.with_time(0.5).time(), 0.5);
+    }
+
+    #[test]
+    fn transforming_ray_preserves_time() {
+        let r = Ray::from_triplets((1.0, 2.0, 3.0), (0.0, 1.0, 0.0)).with_time(0.25);
+        let tr = r.transformed(&transform::translation(3.0, 4.0, 5.0));
+        assert_eq!(tr.time(), 0.25);
+    }
+
+    #[test]
+    fn rays_intersect_at_any_distance_by_default() {
+        let r = Ray::from_triplets((1.0, 2.0, 3.0), (4.0, 5.0, 6.0));
+        assert!(r.contains(-1e300) && r.contains(0.0) && r.contains(1e300));
+        let r = r.with_interval(0.0, 2.0);
+        assert!(!r.contains(-0.1) && r.contains(0.0) && r.contains(2.0) && !r.contains(2.1));
+    }
+
+    #[test]
+    fn transforming_ray_preserves_interval() {
+        let r = Ray::from_triplets((1.0, 2.0, 3.0), (0.0, 1.0, 0.0)).with_interval(0.5, 4.0);
+        let tr = r.transformed(&transform::scaling(2.0, 3.0, 4.0));
+        assert_eq!((tr.t_min(), tr.t_max()), (0.5, 4.0));
+    }
+
     #[test]
     fn computing_point_from_distance() {
         let r = Ray::new(
@@ -0,0 +1,1633 @@
+//! Scene description files.
+//!
+//! Scenes are described with the YAML dialect used by the TRTC test scenes:
+//!
+//! ```yaml
+//! - add: camera
+//!   width: 100
+//!   height: 50
+//!   field-of-view: 1.047
+//!   from: [0, 1.5, -5]
+//!   to: [0, 1, 0]
+//!   up: [0, 1, 0]
+//!
+//! - add: light
+//!   at: [-10, 10, -10]
+//!   intensity: [1, 1, 1]
+//!
+//! - define: green
+//!   value:
+//!     color: [0.1, 1, 0.5]
+//!     diffuse: 0.7
+//!
+//! - define: pale-green
+//!   extend: green
+//!   value:
+//!     specular: 0.3
+//!
+//! - add: sphere
+//!   material: pale-green
+//!   transform:
+//!     - [scale, 0.5, 0.5, 0.5]
+//!     - [translate, 1.5, 0.5, -0.5]
+//! ```
+//!
+//! Cameras use a perspective projection unless they specify another
+//! `projection`: `fisheye` (with a `field-of-view`), `equirectangular` (no
+//! field of view) or `orthographic` along with `view-width` (and optionally
+//! `view-height`) in place of `field-of-view`.
+//!
+//! A camera `shutter: [open, close]` interval and a shape `motion` (a
+//! sequence of `{ time, transform }` keyframes overriding its `transform`)
+//! produce motion blur.
+//!
+//! A material `pattern` maps a texture on the shape with the TRTC syntax:
+//!
+//! ```yaml
+//! pattern:
+//!   type: map
+//!   mapping: spherical  # or planar, cylindrical, cube; default: natural mapping of the shape
+//!   uv_pattern:
+//!     type: checkers
+//!     width: 16
+//!     height: 8
+//!     colors: [[0, 0.5, 0], [1, 1, 1]]
+//!   transform: [[rotate-y, 0.5]]
+//! ```
+//!
+//! Procedural `marble` and `wood` patterns take two `colors`, a `turbulence`
+//! strength and optionally the number of noise `octaves` and a noise `seed`.
+//! A `perturbed` pattern jitters another `pattern` by up to about `scale`:
+//!
+//! ```yaml
+//! pattern:
+//!   type: perturbed
+//!   scale: 0.2
+//!   pattern: { type: wood, colors: [[0.6, 0.4, 0.2], [0.4, 0.2, 0.1]], turbulence: 0.3 }
+//! ```
+//!
+//! A material `bump` perturbs normals with `noise` (with `strength`, `octaves`
+//! and `seed`), the luminance of a `height` pattern (with `strength`) or a
+//! `normal-map` texture (with `uv_pattern` and `mapping` as above):
+//!
+//! ```yaml
+//! bump: { type: noise, strength: 0.1, transform: [[scale, 0.2, 0.2, 0.2]] }
+//! ```
+//!
+//! An `image` texture reads a PPM `file` (relative to the scene description),
+//! with optional `filter` (`nearest` or `bilinear`), `addressing` (`wrap`,
+//! `clamp` or `mirror`) and texture space `transform`.
+//!
+//! Materials with any of `metallic`, `roughness` (both in `[0, 1]`) or
+//! `diffuse-model` (`lambertian` or `oren-nayar`) are physically based: their
+//! `color` is the base color and `diffuse`, `specular` and `shininess` are ignored.
+//!
+//! A material `emission` color makes a shape glow.  With path tracing, it
+//! lights other shapes too.
+//!
+//! The `background` seen by rays hitting nothing is black unless specified:
+//!
+//! ```yaml
+//! - add: background
+//!   type: gradient          # or color (with a `color`)
+//!   top: [0.5, 0.7, 1]
+//!   bottom: [1, 1, 1]
+//!
+//! - add: background
+//!   type: equirectangular   # or cube (6 faces laid out as a cross)
+//!   file: sky.pfm
+//! ```
+//!
+//! Ambient occlusion dims the ambient lighting of points with objects closer
+//! than `radius` (default 1), estimated with `samples` rays (default 16):
+//!
+//! ```yaml
+//! - add: ambient-occlusion
+//!   samples: 32
+//!   radius: 2
+//! ```
+//!
+//! Exponential height `fog` fills the world.  Its `density` is divided by e
+//! every `1 / height-falloff` units up (default falloff: 0, uniform fog):
+//!
+//! ```yaml
+//! - add: fog
+//!   color: [0.7, 0.75, 0.8]
+//!   density: 0.05
+//!   height-falloff: 0.5
+//! ```
+//!
+//! A material `medium` fills a closed shape (sphere or torus) with smoke-like
+//! matter, whose `absorption` and `scattering` coefficients (default 0) give
+//! the fractions of light absorbed and scattered per unit of distance.  The
+//! surface of the shape is invisible and its `color` tints scattered light:
+//!
+//! ```yaml
+//! - add: sphere
+//!   material: { color: [0.8, 0.8, 0.9], medium: { absorption: 0.05, scattering: 0.4 } }
+//! ```
+//!
+//! A `torus` lies in the xz plane around the y axis.  Its tube of
+//! `minor-radius` (default 0.25) follows a circle of `major-radius` (default
+//! 1):
+//!
+//! ```yaml
+//! - add: torus
+//!   major-radius: 2
+//!   minor-radius: 0.5
+//! ```
+//!
+//! A `disk` of radius 1 and a `rectangle` from -1 to 1 in x and z also lie in
+//! the xz plane.  They bound table tops, wall panels and area lights.  A disk
+//! with an `inner-radius` (default 0) is an annulus:
+//!
+//! ```yaml
+//! - add: disk
+//!   inner-radius: 0.5
+//!   material: { emission: [4, 4, 4] }
+//!   transform: [[scale, 2, 1, 2], [translate, 0, 5, 0]]
+//! ```
+//!
+//! Transformations are applied in the order they are listed.  `define` entries
+//! name reusable materials, patterns, textures (mappings) and transformations
+//! (sequences) that later entries refer to by name.  A definition can `extend` another one.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::bsdf::{DiffuseModel, Physical};
+use crate::bump::{Bump, BumpKind};
+use crate::camera::{Camera, Projection};
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::error::Error;
+use crate::light::{Material, PointLight};
+use crate::medium::{Fog, Medium};
+use crate::noise::Perlin;
+use crate::occlusion::AmbientOcclusion;
+use crate::pattern::{Addressing, Filter, ImageTexture, Pattern, PatternKind, Texture, UvMapping};
+use crate::shape::{Motion, Object, Shape};
+use crate::transform::{self, Transform};
+use crate::tuple::Tuple;
+use crate::world::World;
+use crate::yaml::{self, Node, Value};
+
+/// A world and the camera to render it with.
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+}
+
+/// Error found while parsing a scene description.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    /// Line (1-based) of the offending part of the description.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses scene description `src`.  Files it refers to are relative to the
+/// current directory.
+pub fn parse(src: &str) -> Result<Scene, ParseError> {
+    parse_in_dir(src, Path::new("."))
+}
+
+/// Parses scene description `src`.  Files it refers to are relative to `dir`.
+pub fn parse_in_dir(src: &str, dir: &Path) -> Result<Scene, ParseError> {
+    let root = yaml::parse(src)?;
+    let mut builder = Builder {
+        dir: dir.to_path_buf(),
+        ..Builder::default()
+    };
+    for item in root.as_sequence()? {
+        builder.add_item(item)?;
+    }
+    builder.build(&root)
+}
+
+#[derive(Default)]
+struct Builder {
+    defines: HashMap<String, Node>,
+    camera: Option<Camera>,
+    light: Option<PointLight>,
+    background: Option<Background>,
+    ambient_occlusion: Option<AmbientOcclusion>,
+    fog: Option<Fog>,
+    objects: Vec<Shape>,
+
+    /// Directory of the scene description.
+    dir: PathBuf,
+
+    /// Images loaded so far, by path.
+    images: RefCell<HashMap<PathBuf, Arc<Canvas>>>,
+
+    /// Names of the transformation definitions being expanded, to detect
+    /// definitions referring to themselves.
+    expanding: RefCell<Vec<String>>,
+}
+
+impl Builder {
+    fn add_item(&mut self, item: &Node) -> Result<(), ParseError> {
+        if let Some(name) = item.get("define") {
+            self.define(item, name.as_scalar()?)
+        } else if let Some(kind) = item.get("add") {
+            match kind.as_scalar()? {
+                "camera" => self.add_camera(item),
+                "light" => self.add_light(item),
+                "background" => self.add_background(item),
+                "ambient-occlusion" => self.add_ambient_occlusion(item),
+                "fog" => self.add_fog(item),
+                _ => self.add_shape(item, kind),
+            }
+        } else {
+            Err(item.error("expected 'add' or 'define' entry"))
+        }
+    }
+
+    fn build(self, root: &Node) -> Result<Scene, ParseError> {
+        let camera = self
+            .camera
+            .ok_or_else(|| root.error("scene has no camera"))?;
+        let light = self.light.ok_or_else(|| root.error("scene has no light"))?;
+        Ok(Scene {
+            world: World {
+                background: self.background.unwrap_or_default(),
+                ambient_occlusion: self.ambient_occlusion,
+                fog: self.fog,
+                ..World::new(light, self.objects)
+            },
+            camera,
+        })
+    }
+
+    fn define(&mut self, item: &Node, name: &str) -> Result<(), ParseError> {
+        check_keys(item, &["define", "extend", "value"])?;
+        let value = required(item, "value")?;
+        let value = match item.get("extend") {
+            Some(base) => {
+                let base = self.lookup(base)?;
+                match (&base.value, &value.value) {
+                    (Value::Mapping(b), Value::Mapping(v)) => {
+                        let mut entries: Vec<(String, Node)> = b
+                            .iter()
+                            .filter(|(k, _)| v.iter().all(|(vk, _)| vk != k))
+                            .cloned()
+                            .collect();
+                        entries.extend(v.iter().cloned());
+                        Node {
+                            line: value.line,
+                            value: Value::Mapping(entries),
+                        }
+                    }
+                    (Value::Sequence(b), Value::Sequence(v)) => Node {
+                        line: value.line,
+                        value: Value::Sequence(b.iter().chain(v.iter()).cloned().collect()),
+                    },
+                    _ => return Err(value.error("cannot extend a definition of a different kind")),
+                }
+            }
+            None => value.clone(),
+        };
+        self.defines.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Returns the definition named by scalar `node`.
+    fn lookup(&self, node: &Node) -> Result<&Node, ParseError> {
+        let name = node.as_scalar()?;
+        self.defines
+            .get(name)
+            .ok_or_else(|| node.error(format!("undefined name '{}'", name)))
+    }
+
+    /// Returns definition named `node` if it is a scalar, otherwise `node`.
+    fn resolve<'a>(&'a self, node: &'a Node) -> Result<&'a Node, ParseError> {
+        match node.value {
+            Value::Scalar(_) => self.lookup(node),
+            _ => Ok(node),
+        }
+    }
+
+    fn add_camera(&mut self, item: &Node) -> Result<(), ParseError> {
+        check_keys(
+            item,
+            &[
+                "add",
+                "width",
+                "height",
+                "projection",
+                "field-of-view",
+                "view-width",
+                "view-height",
+                "from",
+                "to",
+                "up",
+                "shutter",
+            ],
+        )?;
+        if self.camera.is_some() {
+            return Err(item.error("scene has more than one camera"));
+        }
+        let width = required(item, "width")?.as_usize()?;
+        let height = required(item, "height")?.as_usize()?;
+        if width == 0 || height == 0 {
+            return Err(item.error("camera width and height must be positive"));
+        }
+        let projection = match item.get("projection") {
+            None => "perspective",
+            Some(p) => p.as_scalar()?,
+        };
+        let projection = match projection {
+            "perspective" => Projection::Perspective {
+                field_of_view: required(item, "field-of-view")?.as_f64()?,
+            },
+            "equirectangular" => Projection::Equirectangular,
+            "fisheye" => Projection::Fisheye {
+                field_of_view: required(item, "field-of-view")?.as_f64()?,
+            },
+            "orthographic" => {
+                let view_width = required(item, "view-width")?.as_f64()?;
+                let view_height = match item.get("view-height") {
+                    Some(h) => h.as_f64()?,
+                    None => view_width * height as f64 / width as f64,
+                };
+                Projection::Orthographic {
+                    width: view_width,
+                    height: view_height,
+                }
+            }
+            other => {
+                let node = required(item, "projection")?;
+                return Err(node.error(format!("unknown projection '{}'", other)));
+            }
+        };
+        let from = point(required(item, "from")?)?;
+        let to = point(required(item, "to")?)?;
+        let up = vector(required(item, "up")?)?;
+        let view = Transform::from(transform::view(&from, &to, &up));
+        if view.inverse().is_none() {
+            return Err(item.error(
+                "degenerate camera view: 'from' and 'to' must differ, 'up' must not be \
+                 parallel to the view direction",
+            ));
+        }
+        let mut camera = Camera::with_projection(width, height, projection, view);
+        if let Some(shutter) = item.get("shutter") {
+            match shutter.as_sequence()? {
+                [open, close] => {
+                    camera.shutter_open = open.as_f64()?;
+                    camera.shutter_close = close.as_f64()?;
+                }
+                _ => return Err(shutter.error("expected shutter open and close times")),
+            }
+        }
+        self.camera = Some(camera);
+        Ok(())
+    }
+
+    fn add_light(&mut self, item: &Node) -> Result<(), ParseError> {
+        check_keys(item, &["add", "at", "intensity"])?;
+        if self.light.is_some() {
+            return Err(item.error("only one light is supported"));
+        }
+        let position = point(required(item, "at")?)?;
+        let intensity = color(required(item, "intensity")?)?;
+        self.light = Some(PointLight::new(intensity, position));
+        Ok(())
+    }
+
+    fn add_background(&mut self, item: &Node) -> Result<(), ParseError> {
+        if self.background.is_some() {
+            return Err(item.error("only one background is supported"));
+        }
+        let kind = required(item, "type")?;
+        let background = match kind.as_scalar()? {
+            "color" => {
+                check_keys(item, &["add", "type", "color"])?;
+                Background::Color(color(required(item, "color")?)?)
+            }
+            "gradient" => {
+                check_keys(item, &["add", "type", "top", "bottom"])?;
+                Background::Gradient {
+                    top: color(required(item, "top")?)?,
+                    bottom: color(required(item, "bottom")?)?,
+                }
+            }
+            "equirectangular" | "cube" => {
+                check_keys(item, &["add", "type", "file"])?;
+                let texture =
+                    Texture::Image(ImageTexture::new(self.image(required(item, "file")?)?));
+                if kind.as_scalar()? == "cube" {
+                    Background::Cube(texture)
+                } else {
+                    Background::Equirectangular(texture)
+                }
+            }
+            other => return Err(kind.error(format!("unknown background '{}'", other))),
+        };
+        self.background = Some(background);
+        Ok(())
+    }
+
+    fn add_ambient_occlusion(&mut self, item: &Node) -> Result<(), ParseError> {
+        if self.ambient_occlusion.is_some() {
+            return Err(item.error("only one ambient occlusion is supported"));
+        }
+        check_keys(item, &["add", "samples", "radius"])?;
+        let mut ao = AmbientOcclusion::default();
+        if let Some(samples) = item.get("samples") {
+            ao.samples = samples.as_usize()?;
+        }
+        if let Some(radius) = item.get("radius") {
+            ao.radius = radius.as_f64()?;
+        }
+        self.ambient_occlusion = Some(ao);
+        Ok(())
+    }
+
+    fn add_fog(&mut self, item: &Node) -> Result<(), ParseError> {
+        if self.fog.is_some() {
+            return Err(item.error("only one fog is supported"));
+        }
+        check_keys(item, &["add", "color", "density", "height-falloff"])?;
+        let density = required(item, "density")?;
+        if density.as_f64()? < 0.0 {
+            return Err(density.error("fog density must not be negative"));
+        }
+        self.fog = Some(Fog {
+            color: color(required(item, "color")?)?,
+            density: density.as_f64()?,
+            height_falloff: match item.get("height-falloff") {
+                Some(f) => f.as_f64()?,
+                None => 0.0,
+            },
+        });
+        Ok(())
+    }
+
+    fn add_shape(&mut self, item: &Node, kind: &Node) -> Result<(), ParseError> {
+        let (object, keys): (_, &[&str]) = match kind.as_scalar()? {
+            "sphere" => (Object::Sphere, &[]),
+            "plane" => (Object::Plane, &[]),
+            "torus" => (torus(item)?, &["major-radius", "minor-radius"]),
+            "disk" => (disk(item)?, &["inner-radius"]),
+            "rectangle" => (Object::Rectangle, &[]),
+            other => return Err(kind.error(format!("unknown shape '{}'", other))),
+        };
+        check_keys(
+            item,
+            &[&["add", "material", "transform", "motion"][..], keys].concat(),
+        )?;
+        let transform = match item.get("transform") {
+            Some(t) => self.transform(t)?,
+            None => Transform::identity(),
+        };
+        let material = match item.get("material") {
+            Some(m) => self.material(m)?,
+            None => Material::default(),
+        };
+        let mut shape = Shape::with_transform_and_material(object, transform, material);
+        if let Some(motion) = item.get("motion") {
+            shape.motion = Some(self.motion(motion)?);
+        }
+        shape.validate().map_err(|e| {
+            let e = Error::InvalidShape {
+                index: self.objects.len(),
+                kind: shape.object.name(),
+                error: Box::new(e),
+            };
+            kind.error(e.to_string())
+        })?;
+        self.objects.push(shape);
+        Ok(())
+    }
+
+    /// Returns motion described by a sequence of `{ time, transform }` keyframes.
+    fn motion(&self, node: &Node) -> Result<Motion, ParseError> {
+        let mut keyframes = Vec::new();
+        for key in node.as_sequence()? {
+            check_keys(key, &["time", "transform"])?;
+            let time = required(key, "time")?;
+            let t = time.as_f64()?;
+            if !t.is_finite() {
+                return Err(time.error(Error::NonFiniteTime.to_string()));
+            }
+            keyframes.push((t, self.transform(required(key, "transform")?)?.into()));
+        }
+        Motion::new(keyframes).map_err(|e| node.error(e.to_string()))
+    }
+
+    fn material(&self, node: &Node) -> Result<Material, ParseError> {
+        let node = self.resolve(node)?;
+        let mut m = Material::default();
+        for (key, value) in node.as_mapping()? {
+            match key.as_str() {
+                "color" => m.color = color(value)?,
+                "ambient" => m.ambient = value.as_f64()?,
+                "diffuse" => m.diffuse = value.as_f64()?,
+                "specular" => m.specular = value.as_f64()?,
+                "shininess" => m.shininess = value.as_f64()?,
+                "pattern" => m.pattern = Some(self.pattern(value)?),
+                "bump" => m.bump = Some(self.bump(value)?),
+                "emission" => m.emission = color(value)?,
+                "medium" => m.medium = Some(medium(value)?),
+                "metallic" => physical(&mut m).metallic = fraction(value, key)?,
+                "roughness" => physical(&mut m).roughness = fraction(value, key)?,
+                "diffuse-model" => physical(&mut m).diffuse = diffuse_model(value)?,
+                _ => return Err(value.error(format!("unsupported material attribute '{}'", key))),
+            }
+        }
+        Ok(m)
+    }
+
+    fn pattern(&self, node: &Node) -> Result<Pattern, ParseError> {
+        let node = self.resolve(node)?;
+        let kind = required(node, "type")?;
+        let optional = |key: &str, default: f64| match node.get(key) {
+            Some(v) => v.as_f64(),
+            None => Ok(default),
+        };
+        let mut pattern = match kind.as_scalar()? {
+            "map" => {
+                check_keys(node, &["type", "mapping", "uv_pattern", "transform"])?;
+                let texture = self.texture(required(node, "uv_pattern")?)?;
+                match node.get("mapping") {
+                    Some(m) => Pattern::mapped_texture(uv_mapping(m)?, texture),
+                    None => Pattern::texture(texture),
+                }
+            }
+            "perturbed" => {
+                check_keys(
+                    node,
+                    &["type", "pattern", "scale", "octaves", "seed", "transform"],
+                )?;
+                let (noise, octaves) = noise(node)?;
+                Pattern::new(PatternKind::Perturbed {
+                    pattern: Box::new(self.pattern(required(node, "pattern")?)?),
+                    noise,
+                    scale: optional("scale", 0.2)?,
+                    octaves,
+                })
+            }
+            "marble" | "wood" => {
+                check_keys(
+                    node,
+                    &[
+                        "type",
+                        "colors",
+                        "turbulence",
+                        "octaves",
+                        "seed",
+                        "transform",
+                    ],
+                )?;
+                let (a, b) = color_pair(required(node, "colors")?)?;
+                let (noise, octaves) = noise(node)?;
+                let turbulence = optional("turbulence", 1.0)?;
+                Pattern::new(if kind.as_scalar()? == "marble" {
+                    PatternKind::Marble {
+                        a,
+                        b,
+                        noise,
+                        turbulence,
+                        octaves,
+                    }
+                } else {
+                    PatternKind::Wood {
+                        a,
+                        b,
+                        noise,
+                        turbulence,
+                        octaves,
+                    }
+                })
+            }
+            other => return Err(kind.error(format!("unknown pattern '{}'", other))),
+        };
+        if let Some(t) = node.get("transform") {
+            pattern.set_transform(self.transform(t)?);
+        }
+        Ok(pattern)
+    }
+
+    fn bump(&self, node: &Node) -> Result<Bump, ParseError> {
+        let node = self.resolve(node)?;
+        let kind = required(node, "type")?;
+        let strength = || match node.get("strength") {
+            Some(v) => v.as_f64(),
+            None => Ok(1.0),
+        };
+        let mut bump = Bump::new(match kind.as_scalar()? {
+            "noise" => {
+                check_keys(node, &["type", "strength", "octaves", "seed", "transform"])?;
+                let (noise, octaves) = noise(node)?;
+                BumpKind::Noise {
+                    noise,
+                    octaves,
+                    strength: strength()?,
+                }
+            }
+            "height" => {
+                check_keys(node, &["type", "pattern", "strength", "transform"])?;
+                BumpKind::Height {
+                    pattern: Box::new(self.pattern(required(node, "pattern")?)?),
+                    strength: strength()?,
+                }
+            }
+            "normal-map" => {
+                check_keys(node, &["type", "mapping", "uv_pattern", "transform"])?;
+                BumpKind::NormalMap {
+                    mapping: node.get("mapping").map(uv_mapping).transpose()?,
+                    texture: self.texture(required(node, "uv_pattern")?)?,
+                }
+            }
+            other => return Err(kind.error(format!("unknown bump '{}'", other))),
+        });
+        if let Some(t) = node.get("transform") {
+            bump.set_transform(self.transform(t)?);
+        }
+        Ok(bump)
+    }
+
+    fn texture(&self, node: &Node) -> Result<Texture, ParseError> {
+        let node = self.resolve(node)?;
+        let kind = required(node, "type")?;
+        match kind.as_scalar()? {
+            "checkers" => {
+                check_keys(node, &["type", "width", "height", "colors"])?;
+                let (a, b) = color_pair(required(node, "colors")?)?;
+                Ok(Texture::Checkers {
+                    width: required(node, "width")?.as_usize()?,
+                    height: required(node, "height")?.as_usize()?,
+                    a,
+                    b,
+                })
+            }
+            "image" => {
+                check_keys(node, &["type", "file", "filter", "addressing", "transform"])?;
+                let mut image = ImageTexture::new(self.image(required(node, "file")?)?);
+                if let Some(f) = node.get("filter") {
+                    image.filter = match f.as_scalar()? {
+                        "nearest" => Filter::Nearest,
+                        "bilinear" => Filter::Bilinear,
+                        other => return Err(f.error(format!("unknown filter '{}'", other))),
+                    };
+                }
+                if let Some(a) = node.get("addressing") {
+                    image.addressing = match a.as_scalar()? {
+                        "wrap" => Addressing::Wrap,
+                        "clamp" => Addressing::Clamp,
+                        "mirror" => Addressing::Mirror,
+                        other => return Err(a.error(format!("unknown addressing '{}'", other))),
+                    };
+                }
+                if let Some(t) = node.get("transform") {
+                    image.set_transform(self.transform(t)?);
+                }
+                Ok(Texture::Image(image))
+            }
+            other => Err(kind.error(format!("unknown texture '{}'", other))),
+        }
+    }
+
+    /// Returns canvas loaded from PPM or PFM (if named `*.pfm`) file `node`,
+    /// relative to the scene directory.
+    fn image(&self, node: &Node) -> Result<Arc<Canvas>, ParseError> {
+        let path = self.dir.join(node.as_scalar()?);
+        if let Some(canvas) = self.images.borrow().get(&path) {
+            return Ok(Arc::clone(canvas));
+        }
+        let pfm = path.extension().is_some_and(|e| e == "pfm");
+        let canvas = File::open(&path)
+            .and_then(|f| {
+                if pfm {
+                    Canvas::from_pfm(f)
+                } else {
+                    Canvas::from_ppm(f)
+                }
+            })
+            .map(Arc::new)
+            .map_err(|e| node.error(format!("cannot load {}: {}", path.display(), e)))?;
+        self.images.borrow_mut().insert(path, Arc::clone(&canvas));
+        Ok(canvas)
+    }
+
+    /// Returns matrix encoding sequence of transformations `node`.
+    fn transform(&self, node: &Node) -> Result<Transform, ParseError> {
+        let mut t = Transform::identity();
+        for step in node.as_sequence()? {
+            t = match step.value {
+                Value::Scalar(_) => t.then_transform(&self.named_transform(step)?),
+                _ => transform_step(t, step)?,
+            };
+        }
+        Ok(t)
+    }
+
+    /// Returns transformation defined under the name in scalar `node`.
+    fn named_transform(&self, node: &Node) -> Result<Transform, ParseError> {
+        let name = node.as_scalar()?;
+        if self.expanding.borrow().iter().any(|n| n == name) {
+            return Err(node.error(format!("definition '{}' refers to itself", name)));
+        }
+        let definition = self.lookup(node)?;
+        self.expanding.borrow_mut().push(name.to_string());
+        let t = self.transform(definition);
+        self.expanding.borrow_mut().pop();
+        t
+    }
+}
+
+/// Returns `t` followed by a single transformation such as `[translate, 1, 2, 3]`
+/// or `[rotate, x, y, z, angle]` around axis `(x, y, z)`.
+fn transform_step(t: Transform, step: &Node) -> Result<Transform, ParseError> {
+    let items = step.as_sequence()?;
+    let (op, args) = items
+        .split_first()
+        .ok_or_else(|| step.error("empty transformation"))?;
+    let args = args
+        .iter()
+        .map(Node::as_f64)
+        .collect::<Result<Vec<f64>, _>>()?;
+    let op = op.as_scalar()?;
+    let expected = match op {
+        "translate" | "scale" => 3,
+        "rotate-x" | "rotate-y" | "rotate-z" => 1,
+        "rotate" => 4,
+        "shear" => 6,
+        _ => return Err(step.error(format!("unknown transformation '{}'", op))),
+    };
+    if args.len() != expected {
+        return Err(step.error(format!(
+            "'{}' expects {} arguments, found {}",
+            op,
+            expected,
+            args.len()
+        )));
+    }
+    if args.iter().any(|a| !a.is_finite()) {
+        return Err(step.error(format!("'{}' expects finite arguments", op)));
+    }
+    if op == "rotate" && args[..3].iter().all(|&a| a == 0.0) {
+        return Err(step.error("rotation axis must not be zero"));
+    }
+    Ok(match op {
+        "translate" => t.translate(args[0], args[1], args[2]),
+        "scale" => t.scale(args[0], args[1], args[2]),
+        "rotate-x" => t.rotate_x(args[0]),
+        "rotate-y" => t.rotate_y(args[0]),
+        "rotate-z" => t.rotate_z(args[0]),
+        "rotate" => t.rotate(&Tuple::new_vector(args[0], args[1], args[2]), args[3]),
+        _ => t.shear(args[0], args[1], args[2], args[3], args[4], args[5]),
+    })
+}
+
+fn uv_mapping(node: &Node) -> Result<UvMapping, ParseError> {
+    match node.as_scalar()? {
+        "spherical" => Ok(UvMapping::Spherical),
+        "planar" => Ok(UvMapping::Planar),
+        "cylindrical" => Ok(UvMapping::Cylindrical),
+        "cube" => Ok(UvMapping::Cube),
+        other => Err(node.error(format!("unknown mapping '{}'", other))),
+    }
+}
+
+fn torus(item: &Node) -> Result<Object, ParseError> {
+    let radius = |key, default| match item.get(key) {
+        Some(r) => r.as_f64(),
+        None => Ok(default),
+    };
+    let major_radius = radius("major-radius", 1.0)?;
+    let minor_radius = radius("minor-radius", 0.25)?;
+    if !(minor_radius > 0.0 && minor_radius <= major_radius) {
+        return Err(item.error("torus radii must satisfy 0 < minor-radius <= major-radius"));
+    }
+    Ok(Object::Torus {
+        major_radius,
+        minor_radius,
+    })
+}
+
+fn disk(item: &Node) -> Result<Object, ParseError> {
+    let inner_radius = match item.get("inner-radius") {
+        Some(r) => r.as_f64()?,
+        None => 0.0,
+    };
+    if !(0.0..1.0).contains(&inner_radius) {
+        return Err(item.error("disk inner-radius must be in [0, 1)"));
+    }
+    Ok(Object::Disk { inner_radius })
+}
+
+fn medium(node: &Node) -> Result<Medium, ParseError> {
+    check_keys(node, &["absorption", "scattering"])?;
+    let coefficient = |key| match node.get(key) {
+        Some(c) => c.as_f64(),
+        None => Ok(0.0),
+    };
+    Ok(Medium {
+        absorption: coefficient("absorption")?,
+        scattering: coefficient("scattering")?,
+    })
+}
+
+/// Returns physical parameters of `m`, making it physical if needed.
+fn physical(m: &mut Material) -> &mut Physical {
+    m.physical.get_or_insert_with(Physical::default)
+}
+
+/// Returns number `node` of attribute `key`, which must be in `[0, 1]`.
+fn fraction(node: &Node, key: &str) -> Result<f64, ParseError> {
+    let value = node.as_f64()?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(node.error(format!("{} must be in [0, 1]", key)));
+    }
+    Ok(value)
+}
+
+fn diffuse_model(node: &Node) -> Result<DiffuseModel, ParseError> {
+    match node.as_scalar()? {
+        "lambertian" => Ok(DiffuseModel::Lambertian),
+        "oren-nayar" => Ok(DiffuseModel::OrenNayar),
+        other => Err(node.error(format!("unknown diffuse model '{}'", other))),
+    }
+}
+
+/// Fails if mapping `node` contains a key not in `allowed`.
+fn check_keys(node: &Node, allowed: &[&str]) -> Result<(), ParseError> {
+    for (key, value) in node.as_mapping()? {
+        if !allowed.contains(&key.as_str()) {
+            return Err(value.error(format!("unexpected attribute '{}'", key)));
+        }
+    }
+    Ok(())
+}
+
+fn required<'a>(node: &'a Node, key: &str) -> Result<&'a Node, ParseError> {
+    node.get(key)
+        .ok_or_else(|| node.error(format!("missing attribute '{}'", key)))
+}
+
+fn triplet(node: &Node) -> Result<(f64, f64, f64), ParseError> {
+    match node.as_sequence()? {
+        [x, y, z] => Ok((x.as_f64()?, y.as_f64()?, z.as_f64()?)),
+        _ => Err(node.error("expected 3 numbers")),
+    }
+}
+
+fn point(node: &Node) -> Result<Tuple, ParseError> {
+    let (x, y, z) = triplet(node)?;
+    Ok(Tuple::new_point(x, y, z))
+}
+
+fn vector(node: &Node) -> Result<Tuple, ParseError> {
+    let (x, y, z) = triplet(node)?;
+    Ok(Tuple::new_vector(x, y, z))
+}
+
+fn color(node: &Node) -> Result<Color, ParseError> {
+    let (r, g, b) = triplet(node)?;
+    Ok(Color::new(r, g, b))
+}
+
+/// Returns noise with optional `seed` and number of `octaves` of `node`.
+fn noise(node: &Node) -> Result<(Perlin, usize), ParseError> {
+    let seed = match node.get("seed") {
+        Some(s) => s.as_usize()? as u64,
+        None => 0,
+    };
+    let octaves = match node.get("octaves") {
+        Some(o) => o.as_usize()?,
+        None => 4,
+    };
+    Ok((Perlin::new(seed), octaves))
+}
+
+fn color_pair(node: &Node) -> Result<(Color, Color), ParseError> {
+    match node.as_sequence()? {
+        [a, b] => Ok((color(a)?, color(b)?)),
+        _ => Err(node.error("expected 2 colors")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use crate::pattern::PatternKind;
+    use crate::tuple::ORIGIN;
+    use std::f64::consts::PI;
+
+    const CAMERA_AND_LIGHT: &str = "\
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [0, 0, -5]
+  to: [0, 0, 0]
+  up: [0, 1, 0]
+- add: light
+  at: [-10, 10, -10]
+  intensity: [1, 1, 1]
+";
+
+    #[test]
+    fn parsing_camera_and_light() {
+        let scene = parse(CAMERA_AND_LIGHT).unwrap();
+        assert_eq!(scene.camera.hsize(), 100);
+        assert_eq!(scene.camera.vsize(), 50);
+        assert_eq!(scene.camera.field_of_view(), 0.785);
+        assert_eq!(
+            *scene.camera.transform(),
+            transform::view(
+                &Tuple::new_point(0.0, 0.0, -5.0),
+                &ORIGIN,
+                &Tuple::new_vector(0.0, 1.0, 0.0)
+            )
+        );
+        assert_eq!(
+            scene.world.light,
+            PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0))
+        );
+        assert!(scene.world.objects.is_empty());
+    }
+
+    #[test]
+    fn parsing_orthographic_camera() {
+        let src = CAMERA_AND_LIGHT.replace(
+            "field-of-view: 0.785",
+            "projection: orthographic\n  view-width: 4",
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(
+            *scene.camera.projection(),
+            Projection::Orthographic {
+                width: 4.0,
+                height: 2.0
+            }
+        );
+    }
+
+    #[test]
+    fn parsing_panoramic_cameras() {
+        let src = CAMERA_AND_LIGHT.replace("field-of-view: 0.785", "projection: equirectangular");
+        assert_eq!(
+            *parse(&src).unwrap().camera.projection(),
+            Projection::Equirectangular
+        );
+        let src = CAMERA_AND_LIGHT.replace(
+            "field-of-view: 0.785",
+            "projection: fisheye\n  field-of-view: 3.0",
+        );
+        assert_eq!(
+            *parse(&src).unwrap().camera.projection(),
+            Projection::Fisheye { field_of_view: 3.0 }
+        );
+    }
+
+    #[test]
+    fn reporting_unknown_projection() {
+        let src = CAMERA_AND_LIGHT.replace("field-of-view: 0.785", "projection: weird");
+        assert_eq!(parse(&src).err().unwrap().line, 4);
+    }
+
+    #[test]
+    fn parsing_shape_with_material_and_transform() {
+        let src = format!(
+            "{}\
+- add: sphere
+  material:
+    color: [1, 0, 0]
+    diffuse: 0.5
+  transform:
+    - [scale, 2, 2, 2]
+    - [rotate-y, {}]
+    - [translate, 1, 0, 0]
+    - [rotate, 0, 0, 2, 0.5]
+",
+            CAMERA_AND_LIGHT,
+            PI / 2.0
+        );
+        let scene = parse(&src).unwrap();
+        let s = &scene.world.objects[0];
+        assert_eq!(s.object, Object::Sphere);
+        assert_eq!(
+            s.material,
+            Material {
+                color: color::RED,
+                diffuse: 0.5,
+                ..Material::default()
+            }
+        );
+        assert_eq!(
+            *s.transform(),
+            &(&transform::rotation_z(0.5) * &transform::translation(1.0, 0.0, 0.0))
+                * &(&transform::rotation_y(PI / 2.0) * &transform::scaling(2.0, 2.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn parsing_motion_blur() {
+        let src = format!(
+            "{}- add: sphere
+  motion:
+    - time: 0
+      transform: []
+    - time: 1
+      transform:
+        - [translate, 2, 0, 0]
+",
+            CAMERA_AND_LIGHT.replace("up: [0, 1, 0]", "up: [0, 1, 0]\n  shutter: [0, 0.5]")
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(scene.camera.shutter_close, 0.5);
+        let s = &scene.world.objects[0];
+        assert_eq!(
+            s.transform_at(0.5).into_owned(),
+            transform::translation(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn reporting_bad_motion_keyframes() {
+        let src = format!(
+            "{}- add: sphere\n  motion:\n    - time: nan\n      transform: []\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 13: keyframe time is not finite"
+        );
+        let src = format!("{}- add: sphere\n  motion: []\n", CAMERA_AND_LIGHT);
+        assert_eq!(
+            parse(&src).err().unwrap().message,
+            "motion has no keyframes"
+        );
+        let src = format!(
+            "{}- add: sphere\n  motion:\n    - time: 0\n      transform: []\n    \
+             - time: 1\n      transform: [[scale, -1, 1, 1]]\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().message,
+            "shape 0 (sphere): motion mirrors the shape between keyframes"
+        );
+    }
+
+    #[test]
+    fn parsing_texture_pattern() {
+        let src = format!(
+            "{}- add: sphere
+  material:
+    pattern:
+      type: map
+      mapping: cylindrical
+      uv_pattern:
+        type: checkers
+        width: 4
+        height: 2
+        colors: [[1, 0, 0], [0, 0, 1]]
+      transform:
+        - [scale, 2, 2, 2]
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        let mut expected = Pattern::mapped_texture(
+            UvMapping::Cylindrical,
+            Texture::Checkers {
+                width: 4,
+                height: 2,
+                a: color::RED,
+                b: color::BLUE,
+            },
+        );
+        expected.set_transform(transform::scaling(2.0, 2.0, 2.0));
+        assert_eq!(scene.world.objects[0].material.pattern, Some(expected));
+    }
+
+    #[test]
+    fn reporting_unknown_mapping() {
+        let src = format!(
+            "{}- add: plane
+  material:
+    pattern:
+      type: map
+      mapping: conical
+      uv_pattern: {{ type: checkers, width: 2, height: 2, colors: [[0, 0, 0], [1, 1, 1]] }}
+",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse(&src).err().unwrap();
+        assert_eq!(err.to_string(), "line 15: unknown mapping 'conical'");
+    }
+
+    #[test]
+    fn parsing_image_texture() {
+        let dir = std::env::temp_dir().join("rustytracer_parsing_image_texture");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("red.ppm"), "P3 1 1 255 255 0 0\n").unwrap();
+        let src = format!(
+            "{}- define: red-texture
+  value:
+    type: image
+    file: red.ppm
+    filter: nearest
+    addressing: mirror
+- add: sphere
+  material:
+    pattern: {{ type: map, uv_pattern: red-texture }}
+- add: plane
+  material:
+    pattern: {{ type: map, uv_pattern: red-texture }}
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse_in_dir(&src, &dir).unwrap();
+        let texture = |i: usize| match &scene.world.objects[i].material.pattern {
+            Some(Pattern {
+                kind:
+                    PatternKind::Texture {
+                        texture: Texture::Image(image),
+                        ..
+                    },
+                ..
+            }) => image.clone(),
+            p => panic!("unexpected pattern {:?}", p),
+        };
+        let image = texture(0);
+        assert_eq!(image.filter, Filter::Nearest);
+        assert_eq!(image.addressing, Addressing::Mirror);
+        assert_eq!(image.color_at(0.5, 0.5), color::RED);
+        assert!(Arc::ptr_eq(&image.canvas, &texture(1).canvas));
+    }
+
+    #[test]
+    fn reporting_missing_image() {
+        let src = format!(
+            "{}- add: sphere
+  material:
+    pattern:
+      type: map
+      uv_pattern: {{ type: image, file: missing.ppm }}
+",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse_in_dir(&src, Path::new("/nonexistent")).err().unwrap();
+        assert_eq!(err.line, 15);
+        assert!(err
+            .message
+            .starts_with("cannot load /nonexistent/missing.ppm: "));
+    }
+
+    #[test]
+    fn parsing_background() {
+        let src = format!(
+            "{}- add: background
+  type: gradient
+  top: [0, 0, 1]
+  bottom: [1, 1, 1]
+",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).unwrap().world.background,
+            Background::Gradient {
+                top: color::BLUE,
+                bottom: color::WHITE
+            }
+        );
+        let scene = parse(CAMERA_AND_LIGHT).unwrap();
+        assert_eq!(scene.world.background, Background::Color(color::BLACK));
+    }
+
+    #[test]
+    fn parsing_ambient_occlusion() {
+        let src = format!(
+            "{}- add: ambient-occlusion\n  radius: 0.5\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).unwrap().world.ambient_occlusion,
+            Some(AmbientOcclusion {
+                samples: 16,
+                radius: 0.5
+            })
+        );
+        assert_eq!(
+            parse(CAMERA_AND_LIGHT).unwrap().world.ambient_occlusion,
+            None
+        );
+    }
+
+    #[test]
+    fn parsing_fog_and_media() {
+        let src = format!(
+            "{}- add: fog
+  color: [1, 1, 1]
+  density: 0.1
+- add: sphere
+  material:
+    medium: {{ scattering: 0.5 }}
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(
+            scene.world.fog,
+            Some(Fog {
+                color: color::WHITE,
+                density: 0.1,
+                height_falloff: 0.0
+            })
+        );
+        assert_eq!(
+            scene.world.objects[0].material.medium,
+            Some(Medium {
+                absorption: 0.0,
+                scattering: 0.5
+            })
+        );
+        let src = format!(
+            "{}- add: disk\n  material: {{ medium: {{ absorption: 1 }} }}\n",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse(&src).err().unwrap();
+        assert_eq!(
+            err.message,
+            "shape 0 (disk): only closed shapes can be filled with a medium"
+        );
+        let src = format!(
+            "{}- add: fog\n  color: [1, 1, 1]\n  density: -0.1\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 13: fog density must not be negative"
+        );
+    }
+
+    #[test]
+    fn parsing_hdr_environment_map() {
+        let dir = std::env::temp_dir().join("rustytracer_parsing_hdr_environment_map");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut pfm = b"PF 1 1 -1\n".to_vec();
+        for v in &[2.0_f32, 4.0, 8.0] {
+            pfm.extend_from_slice(&v.to_le_bytes());
+        }
+        std::fs::write(dir.join("sky.pfm"), pfm).unwrap();
+        let src = format!(
+            "{}- add: background\n  type: equirectangular\n  file: sky.pfm\n",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse_in_dir(&src, &dir).unwrap();
+        assert_eq!(
+            scene
+                .world
+                .background
+                .color_at(&Tuple::new_vector(0.0, 0.0, 1.0)),
+            Color::new(2.0, 4.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn parsing_noise_patterns() {
+        let src = format!(
+            "{}- add: sphere
+  material:
+    pattern:
+      type: perturbed
+      scale: 0.5
+      seed: 3
+      pattern:
+        type: marble
+        colors: [[1, 1, 1], [0, 0, 0]]
+        turbulence: 2
+        octaves: 6
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        let marble = Pattern::new(PatternKind::Marble {
+            a: color::WHITE,
+            b: color::BLACK,
+            noise: Perlin::new(0),
+            turbulence: 2.0,
+            octaves: 6,
+        });
+        assert_eq!(
+            scene.world.objects[0].material.pattern,
+            Some(Pattern::new(PatternKind::Perturbed {
+                pattern: Box::new(marble),
+                noise: Perlin::new(3),
+                scale: 0.5,
+                octaves: 4,
+            }))
+        );
+    }
+
+    #[test]
+    fn parsing_physical_and_emissive_materials() {
+        let src = format!(
+            "{}- add: sphere
+  material:
+    color: [1, 0.8, 0.3]
+    emission: [0.1, 0, 0]
+    metallic: 1
+    roughness: 0.2
+- add: plane
+  material: {{ diffuse-model: oren-nayar }}
+- add: plane
+  material: {{ diffuse-model: phong }}
+",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse(&src).err().unwrap();
+        assert_eq!(err.to_string(), "line 20: unknown diffuse model 'phong'");
+        let src = src.replace("- add: plane\n  material: { diffuse-model: phong }\n", "");
+        let scene = parse(&src).unwrap();
+        assert_eq!(
+            scene.world.objects[0].material.emission,
+            Color::new(0.1, 0.0, 0.0)
+        );
+        assert_eq!(
+            scene.world.objects[0].material.physical,
+            Some(Physical {
+                metallic: 1.0,
+                roughness: 0.2,
+                diffuse: DiffuseModel::Lambertian,
+            })
+        );
+        assert_eq!(
+            scene.world.objects[1].material.physical,
+            Some(Physical {
+                diffuse: DiffuseModel::OrenNayar,
+                ..Physical::default()
+            })
+        );
+    }
+
+    #[test]
+    fn reporting_out_of_range_physical_material() {
+        let src = format!(
+            "{}- add: sphere\n  material:\n    metallic: 1\n    roughness: 5\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 14: roughness must be in [0, 1]"
+        );
+        let src = format!(
+            "{}- add: sphere\n  material: {{ metallic: -3 }}\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 12: metallic must be in [0, 1]"
+        );
+    }
+
+    #[test]
+    fn parsing_bumps() {
+        let src = format!(
+            "{}- add: sphere
+  material:
+    bump: {{ type: noise, strength: 0.1, seed: 2 }}
+- add: plane
+  material:
+    bump:
+      type: normal-map
+      mapping: planar
+      uv_pattern: {{ type: checkers, width: 1, height: 1, colors: [[0.5, 0.5, 1], [0, 0, 0]] }}
+      transform:
+        - [scale, 2, 2, 2]
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(
+            scene.world.objects[0].material.bump,
+            Some(Bump::new(BumpKind::Noise {
+                noise: Perlin::new(2),
+                octaves: 4,
+                strength: 0.1
+            }))
+        );
+        let bump = scene.world.objects[1].material.bump.as_ref().unwrap();
+        assert_eq!(*bump.transform(), transform::scaling(2.0, 2.0, 2.0));
+        assert!(matches!(
+            bump.kind,
+            BumpKind::NormalMap {
+                mapping: Some(UvMapping::Planar),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn extending_definitions() {
+        let src = format!(
+            "{}\
+- define: base
+  value:
+    color: [1, 0, 0]
+    ambient: 0.5
+- define: derived
+  extend: base
+  value:
+    ambient: 0.2
+- define: small
+  value:
+    - [scale, 0.5, 0.5, 0.5]
+- define: small-and-up
+  extend: small
+  value:
+    - [translate, 0, 1, 0]
+- add: plane
+  material: derived
+  transform:
+    - small-and-up
+    - [translate, 1, 0, 0]
+",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        let s = &scene.world.objects[0];
+        assert_eq!(s.object, Object::Plane);
+        assert_eq!(s.material.color, color::RED);
+        assert_eq!(s.material.ambient, 0.2);
+        assert_eq!(
+            *s.transform(),
+            &transform::translation(1.0, 1.0, 0.0) * &transform::scaling(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn parsing_torus() {
+        let src = format!(
+            "{}- add: torus\n  major-radius: 2\n  minor-radius: 0.5\n- add: torus\n",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(
+            scene.world.objects[0].object,
+            Object::Torus {
+                major_radius: 2.0,
+                minor_radius: 0.5
+            }
+        );
+        assert_eq!(
+            scene.world.objects[1].object,
+            Object::Torus {
+                major_radius: 1.0,
+                minor_radius: 0.25
+            }
+        );
+
+        let src = format!("{}- add: torus\n  minor-radius: 2\n", CAMERA_AND_LIGHT);
+        assert_eq!(parse(&src).err().unwrap().line, 11);
+        let src = format!("{}- add: sphere\n  minor-radius: 2\n", CAMERA_AND_LIGHT);
+        assert_eq!(
+            parse(&src).err().unwrap().message,
+            "unexpected attribute 'minor-radius'"
+        );
+    }
+
+    #[test]
+    fn parsing_disk_and_rectangle() {
+        let src = format!(
+            "{}- add: disk\n  inner-radius: 0.5\n- add: disk\n- add: rectangle\n",
+            CAMERA_AND_LIGHT
+        );
+        let scene = parse(&src).unwrap();
+        let objects: Vec<&Object> = scene.world.objects.iter().map(|s| &s.object).collect();
+        assert_eq!(
+            objects,
+            [
+                &Object::Disk { inner_radius: 0.5 },
+                &Object::Disk { inner_radius: 0.0 },
+                &Object::Rectangle
+            ]
+        );
+
+        let src = format!("{}- add: disk\n  inner-radius: 1\n", CAMERA_AND_LIGHT);
+        assert_eq!(
+            parse(&src).err().unwrap().message,
+            "disk inner-radius must be in [0, 1)"
+        );
+    }
+
+    #[test]
+    fn reporting_unknown_shape() {
+        let src = format!("{}- add: teapot\n", CAMERA_AND_LIGHT);
+        let err = parse(&src).err().unwrap();
+        assert_eq!(err.line, 11);
+        assert_eq!(err.to_string(), "line 11: unknown shape 'teapot'");
+    }
+
+    #[test]
+    fn reporting_self_referring_transform() {
+        let src = format!(
+            "- define: t\n  value: [t]\n{}- add: sphere\n  transform: [t]\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 2: definition 't' refers to itself"
+        );
+
+        let src = format!(
+            "- define: a\n  value: [b]\n- define: b\n  value: [[scale, 2, 2, 2], a]\n\
+             {}- add: sphere\n  transform: [a]\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 4: definition 'a' refers to itself"
+        );
+    }
+
+    #[test]
+    fn reporting_undefined_material() {
+        let src = format!(
+            "{}- add: sphere\n  transform: []\n  material: nope\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(parse(&src).err().unwrap().line, 13);
+    }
+
+    #[test]
+    fn reporting_bad_transform_arguments() {
+        let src = format!(
+            "{}- add: sphere\n  transform:\n    - [scale, 1, 2]\n",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse(&src).err().unwrap();
+        assert_eq!(err.line, 13);
+        assert_eq!(err.message, "'scale' expects 3 arguments, found 2");
+
+        let src = src.replace("[scale, 1, 2]", "[rotate, 0, 0, 0, 1]");
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 13: rotation axis must not be zero"
+        );
+        let src = src.replace("[rotate, 0, 0, 0, 1]", "[translate, 1, inf, 0]");
+        assert_eq!(
+            parse(&src).err().unwrap().to_string(),
+            "line 13: 'translate' expects finite arguments"
+        );
+    }
+
+    #[test]
+    fn reporting_singular_transform() {
+        let src = format!(
+            "{}- add: sphere\n- add: sphere\n  transform:\n    - [scale, 1, 0, 1]\n",
+            CAMERA_AND_LIGHT
+        );
+        let err = parse(&src).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "line 12: shape 1 (sphere): transformation is not invertible"
+        );
+
+        let src = format!(
+            "{}- add: sphere\n  material:\n    pattern:\n      type: marble\n      \
+             colors: [[1, 1, 1], [0, 0, 0]]\n      transform:\n        - [scale, 0, 0, 0]\n",
+            CAMERA_AND_LIGHT
+        );
+        assert_eq!(parse(&src).err().unwrap().line, 11);
+    }
+
+    #[test]
+    fn reporting_degenerate_camera_view() {
+        let src = CAMERA_AND_LIGHT.replace("up: [0, 1, 0]", "up: [0, 0, 1]");
+        let err = parse(&src).err().unwrap();
+        assert_eq!(err.line, 1);
+        assert!(err.message.starts_with("degenerate camera view"));
+    }
+
+    #[test]
+    fn reporting_bad_number() {
+        let src = CAMERA_AND_LIGHT.replace("height: 50", "height: fifty");
+        assert_eq!(parse(&src).err().unwrap().line, 3);
+    }
+
+    #[test]
+    fn reporting_missing_camera() {
+        let src = "- add: light\n  at: [0, 0, 0]\n  intensity: [1, 1, 1]\n";
+        assert_eq!(parse(src).err().unwrap().message, "scene has no camera");
+    }
+}
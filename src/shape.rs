@@ -1,12 +1,20 @@
 //! Shape type.
 //!
-//! TRTC chapters 5 (sphere) and 9 (abstract shape).
+//! TRTC chapters 5 (sphere) and 9 (abstract shape).  Tori are intersected by
+//! solving a quartic (see `util::solve_quartic()`).
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::f64::consts::PI;
 
+use crate::color::Color;
+use crate::error::Error;
 use crate::light::Material;
 use crate::matrix::Matrix;
+use crate::pattern::UvMapping;
 use crate::ray::Ray;
+use crate::rng::Rng;
+use crate::transform::{self, Decomposition, Transform};
 use crate::tuple::{Tuple, ORIGIN};
 use crate::util;
 
@@ -16,7 +24,12 @@ use crate::util;
 /// are in `Object`.
 #[derive(PartialEq, Debug)]
 pub struct Shape {
-    pub transform: Matrix,
+    /// Also holds the inverse transformation, computed once.
+    transform: Transform,
+
+    /// Time-dependent transformation of a moving shape.  Overrides `transform` when set.
+    pub motion: Option<Motion>,
+
     pub material: Material,
     pub object: Object,
 }
@@ -24,16 +37,18 @@ pub struct Shape {
 impl Shape {
     pub fn new(object: Object) -> Shape {
         Shape {
-            transform: Matrix::new_4x4_identity(),
+            transform: Transform::identity(),
+            motion: None,
             material: Material::default(),
             object,
         }
     }
 
     /// Creates a new shape with the specified transformation.
-    pub fn with_transform(object: Object, t: Matrix) -> Shape {
+    pub fn with_transform(object: Object, t: impl Into<Transform>) -> Shape {
         Shape {
-            transform: t,
+            transform: t.into(),
+            motion: None,
             material: Material::default(),
             object,
         }
@@ -42,38 +57,252 @@ impl Shape {
     /// Creates a new shape with the specified transformation and material.
     pub fn with_transform_and_material(
         object: Object,
-        transform: Matrix,
+        transform: impl Into<Transform>,
         material: Material,
     ) -> Shape {
         Shape {
-            transform,
+            transform: transform.into(),
+            motion: None,
             material,
             object,
         }
     }
 
+    pub fn transform(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+
+    /// Sets transformation of this shape: a `Matrix`, inverted once here, or a
+    /// `Transform` whose inverse is already known.
+    pub fn set_transform(&mut self, t: impl Into<Transform>) {
+        self.transform = t.into();
+    }
+
+    /// Returns an error if this shape cannot be rendered: its transformations,
+    /// including those of its motion at any time and of its material, must be
+    /// invertible, and only closed shapes can be filled with a medium.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.transform.try_inverse()?;
+        if let Some(motion) = &self.motion {
+            motion.validate()?;
+        }
+        if let Some(pattern) = &self.material.pattern {
+            pattern.validate()?;
+        }
+        if let Some(bump) = &self.material.bump {
+            bump.validate()?;
+        }
+        if self.material.medium.is_some() && !self.object.is_closed() {
+            return Err(Error::OpenMedium);
+        }
+        Ok(())
+    }
+
+    /// Returns transformation of this shape at `time`.
+    pub fn transform_at(&self, time: f64) -> Cow<'_, Matrix> {
+        match &self.motion {
+            Some(m) => Cow::Owned(m.transform_at(time)),
+            None => Cow::Borrowed(self.transform.matrix()),
+        }
+    }
+
+    /// Returns inverse of the transformation of this shape at `time`, only
+    /// computed for moving shapes.
+    fn inverse_at(&self, time: f64) -> Cow<'_, Matrix> {
+        match &self.motion {
+            Some(m) => Cow::Owned(m.transform_at(time).inverted()),
+            None => Cow::Borrowed(self.transform.inverted()),
+        }
+    }
+
     /// Computes normal vector on this shape at point `p` in world space.
     pub fn normal_at(&self, world_pt: &Tuple) -> Tuple {
+        self.normal_at_time(world_pt, 0.0)
+    }
+
+    /// Computes normal vector at point `p` in world space on this shape as it is at `time`.
+    pub fn normal_at_time(&self, world_pt: &Tuple, time: f64) -> Tuple {
+        self.world_normal(world_pt, time, false)
+    }
+
+    /// Same as `normal_at_time()`, perturbed by the bump of the material of
+    /// this shape if any.
+    pub fn shading_normal_at(&self, world_pt: &Tuple, time: f64) -> Tuple {
+        self.world_normal(world_pt, time, true)
+    }
+
+    fn world_normal(&self, world_pt: &Tuple, time: f64, bumped: bool) -> Tuple {
         debug_assert!(world_pt.is_point());
 
-        let it = self.transform.inverted();
-        let obj_pt = &it * world_pt;
-        let obj_normal = self.object.normal_at(&obj_pt);
+        let it = self.inverse_at(time);
+        let obj_pt = &*it * world_pt;
+        let mut obj_normal = self.object.normal_at(&obj_pt);
+        if let (true, Some(b)) = (bumped, &self.material.bump) {
+            obj_normal = b.perturbed_normal(&self.object, &obj_pt, &obj_normal);
+        }
         let mut world_normal = &it.transposed() * &obj_normal;
         world_normal.set(3, 0.0);
         world_normal.normalized()
     }
 
+    /// Returns color of the material of this shape at point `world_pt` in world
+    /// space, on this shape as it is at `time`.
+    pub fn color_at(&self, world_pt: &Tuple, time: f64) -> Color {
+        debug_assert!(world_pt.is_point());
+        match &self.material.pattern {
+            Some(p) => {
+                let obj_pt = &*self.inverse_at(time) * world_pt;
+                p.color_at(&self.object, &obj_pt)
+            }
+            None => self.material.color.clone(),
+        }
+    }
+
+    /// Returns texture coordinates of point `world_pt` in world space on this
+    /// shape as it is at `time`, following the natural mapping of its object
+    /// (see `Object::uv_mapping()`).
+    pub fn uv_at(&self, world_pt: &Tuple, time: f64) -> (f64, f64) {
+        debug_assert!(world_pt.is_point());
+        let obj_pt = &*self.inverse_at(time) * world_pt;
+        self.object.uv_mapping().map(&obj_pt)
+    }
+
+    /// Picks a random point on the surface of this shape as it is at `time`,
+    /// or returns `None` if the shape is unbounded.
+    pub fn sample_surface(&self, time: f64, rng: &mut Rng) -> Option<SurfaceSample> {
+        let (obj_pt, obj_pdf) = self.object.sample_surface(rng)?;
+        let t = self.transform_at(time);
+        let obj_normal = self.object.normal_at(&obj_pt);
+        Some(SurfaceSample {
+            point: &*t * &obj_pt,
+            normal: self.normal_at_time(&(&*t * &obj_pt), time),
+            pdf: obj_pdf / area_scale(&t, &self.inverse_at(time), &obj_normal),
+        })
+    }
+
+    /// Returns probability density per unit area of `sample_surface()` picking
+    /// `world_pt` on this shape as it is at `time`.
+    pub fn surface_pdf(&self, world_pt: &Tuple, time: f64) -> f64 {
+        debug_assert!(world_pt.is_point());
+        let it = self.inverse_at(time);
+        let obj_pt = &*it * world_pt;
+        let obj_normal = self.object.normal_at(&obj_pt);
+        self.object.surface_pdf() / area_scale(&self.transform_at(time), &it, &obj_normal)
+    }
+
     /// Computes intersection between this shape and `ray`.
     ///
-    /// Returns sequence of intersections.  If there is no intersection, the sequence is empty.  If the
-    /// ray is tangent to the shape, the sequence contains two identical intersections.
-    pub fn intersections<'a, 'b>(&'a self, ray: &'b Ray) -> Vec<Intersection<'a>> {
-        let trans_ray = ray.transformed(&self.transform.inverted());
+    /// Returns sequence of intersections in the interval of `ray`.  If there is no intersection, the
+    /// sequence is empty.  If the ray is tangent to the shape, the sequence contains two identical
+    /// intersections.
+    pub fn intersections<'a>(&'a self, ray: &Ray) -> Vec<Intersection<'a>> {
+        let trans_ray = ray.transformed(&self.inverse_at(ray.time()));
         self.object.intersections(self, trans_ray)
     }
 }
 
+/// Returns factor by which transformation `t`, whose inverse is `it`, scales
+/// small areas of a surface with normal `obj_normal`.
+fn area_scale(t: &Matrix, it: &Matrix, obj_normal: &Tuple) -> f64 {
+    let mut n = &it.transposed() * obj_normal;
+    n.set(3, 0.0);
+    t.determinant().abs() * n.magnitude() / obj_normal.magnitude()
+}
+
+/// Point picked at random on the surface of a shape.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SurfaceSample {
+    pub point: Tuple,
+
+    /// Unit normal at `point`.
+    pub normal: Tuple,
+
+    /// Probability density per unit area of picking `point`.
+    pub pdf: f64,
+}
+
+/// Keyframed transformation of a moving shape.
+///
+/// The transformation is interpolated between the keyframes surrounding the
+/// requested time and is constant before the first and after the last keyframe.
+/// Rotations turn smoothly (see `transform::Decomposition::interpolated()`).
+#[derive(PartialEq, Debug, Clone)]
+pub struct Motion {
+    /// (time, transformation) pairs sorted by time.
+    keyframes: Vec<(f64, Matrix)>,
+
+    /// Decomposition of each keyframe transformation, if affine and invertible.
+    decompositions: Vec<Option<Decomposition>>,
+}
+
+impl Motion {
+    /// Creates a motion from (time, transformation) pairs.  Fails if there is
+    /// no pair or if a time is not finite.
+    pub fn new(mut keyframes: Vec<(f64, Matrix)>) -> Result<Motion, Error> {
+        if keyframes.is_empty() {
+            return Err(Error::NoKeyframes);
+        }
+        if keyframes.iter().any(|(t, _)| !t.is_finite()) {
+            return Err(Error::NonFiniteTime);
+        }
+        keyframes.sort_by(|l, r| l.0.total_cmp(&r.0));
+        Ok(Motion::from_sorted(keyframes))
+    }
+
+    /// Creates a motion from `start` at time 0 to `end` at time 1.
+    pub fn linear(start: Matrix, end: Matrix) -> Motion {
+        Motion::from_sorted(vec![(0.0, start), (1.0, end)])
+    }
+
+    fn from_sorted(keyframes: Vec<(f64, Matrix)>) -> Motion {
+        let decompositions = keyframes
+            .iter()
+            .map(|(_, m)| transform::decompose(m).ok())
+            .collect();
+        Motion {
+            keyframes,
+            decompositions,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[(f64, Matrix)] {
+        &self.keyframes
+    }
+
+    /// Returns an error if the transformation of this motion is not invertible
+    /// at some time.  Keyframes must be affine and invertible, and consecutive
+    /// keyframes must not mirror one another: interpolating between them would
+    /// flatten the shape on the way.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut handedness = Vec::new();
+        for (_, m) in &self.keyframes {
+            handedness.push(transform::decompose(m)?.scale.z() > 0.0);
+        }
+        if handedness.windows(2).any(|w| w[0] != w[1]) {
+            return Err(Error::MirroringMotion);
+        }
+        Ok(())
+    }
+
+    /// Returns interpolated transformation at `time`.
+    pub fn transform_at(&self, time: f64) -> Matrix {
+        let next = self.keyframes.iter().position(|(t, _)| *t > time);
+        match next {
+            Some(0) => self.keyframes[0].1.clone(),
+            Some(i) => {
+                let (t0, m0) = &self.keyframes[i - 1];
+                let (t1, m1) = &self.keyframes[i];
+                let t = (time - t0) / (t1 - t0);
+                match (&self.decompositions[i - 1], &self.decompositions[i]) {
+                    (Some(d0), Some(d1)) => d0.interpolated(d1, t).matrix(),
+                    _ => m0.interpolated(m1, t),
+                }
+            }
+            None => self.keyframes[self.keyframes.len() - 1].1.clone(),
+        }
+    }
+}
+
 /// `Shape` helper storing object-specific bits.
 ///
 /// An alternative design would be to store in `Shape` a box to a trait object
@@ -83,12 +312,26 @@ impl Shape {
 pub enum Object {
     Sphere,
     Plane,
+    /// Ring around the y axis in the xz plane: points at distance
+    /// `minor_radius` from the circle of radius `major_radius`.
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    /// Disk of radius 1 around the origin in the xz plane, with a hole of
+    /// `inner_radius` (0 for none) making it an annulus.
+    Disk {
+        inner_radius: f64,
+    },
+    /// Square from -1 to 1 in x and z in the xz plane, scaled into any
+    /// rectangle by the transformation of the shape.
+    Rectangle,
 }
 
 impl Object {
     /// See Shape::intersections()
     fn intersections<'a>(&self, shape: &'a Shape, trans_ray: Ray) -> Vec<Intersection<'a>> {
-        match self {
+        let mut xs = match self {
             Self::Sphere => {
                 let sphere_to_ray = trans_ray.origin() - &ORIGIN;
                 let a = Tuple::dot(trans_ray.direction(), trans_ray.direction());
@@ -112,17 +355,28 @@ impl Object {
                     ]
                 }
             }
-            Self::Plane => {
+            Self::Plane | Self::Disk { .. } | Self::Rectangle => {
                 if util::nearly_equal(trans_ray.direction().y(), 0.0) {
                     Vec::new()
                 } else {
-                    vec![Intersection {
-                        distance: -trans_ray.origin().y() / trans_ray.direction().y(),
-                        shape,
-                    }]
+                    let distance = -trans_ray.origin().y() / trans_ray.direction().y();
+                    if self.contains_planar(&trans_ray.position(distance)) {
+                        vec![Intersection { distance, shape }]
+                    } else {
+                        Vec::new()
+                    }
                 }
             }
-        }
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => torus_distances(&trans_ray, *major_radius, *minor_radius)
+                .into_iter()
+                .map(|distance| Intersection { distance, shape })
+                .collect(),
+        };
+        xs.retain(|x| trans_ray.contains(x.distance));
+        xs
     }
 
     /// See Shape::normal_at()
@@ -130,9 +384,170 @@ impl Object {
         debug_assert!(pt.is_point());
         match self {
             Object::Sphere => pt - &ORIGIN,
-            Object::Plane => Tuple::new_vector(0.0, 1.0, 0.0),
+            Object::Plane | Object::Disk { .. } | Object::Rectangle => {
+                Tuple::new_vector(0.0, 1.0, 0.0)
+            }
+            Object::Torus { major_radius, .. } => {
+                // Away from the closest point on the center circle.
+                let radial = Tuple::new_vector(pt.x(), 0.0, pt.z());
+                let rho = radial.magnitude();
+                if rho < util::EPSILON {
+                    pt - &ORIGIN
+                } else {
+                    &(pt - &ORIGIN) - &(&radial * (major_radius / rho))
+                }
+            }
+        }
+    }
+
+    /// Returns name of this kind of object, as written in scene descriptions.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Object::Sphere => "sphere",
+            Object::Plane => "plane",
+            Object::Torus { .. } => "torus",
+            Object::Disk { .. } => "disk",
+            Object::Rectangle => "rectangle",
+        }
+    }
+
+    /// Returns whether this object fits in a box.
+    pub fn is_bounded(&self) -> bool {
+        match self {
+            Object::Sphere | Object::Torus { .. } | Object::Disk { .. } | Object::Rectangle => true,
+            Object::Plane => false,
+        }
+    }
+
+    /// Returns whether this object encloses a volume, i.e. rays entering it
+    /// always leave it through its surface again.
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Object::Sphere | Object::Torus { .. } => true,
+            Object::Plane | Object::Disk { .. } | Object::Rectangle => false,
+        }
+    }
+
+    /// Returns whether point `pt` of the xz plane is on this planar object,
+    /// which is always the case for `Plane`.
+    fn contains_planar(&self, pt: &Tuple) -> bool {
+        match self {
+            Object::Disk { inner_radius } => {
+                let r2 = pt.x() * pt.x() + pt.z() * pt.z();
+                r2 <= 1.0 && r2 >= inner_radius * inner_radius
+            }
+            Object::Rectangle => pt.x().abs() <= 1.0 && pt.z().abs() <= 1.0,
+            _ => true,
+        }
+    }
+
+    /// Returns uniformly distributed point on the surface of this object and
+    /// its probability density per unit area, or `None` if unbounded.
+    fn sample_surface(&self, rng: &mut Rng) -> Option<(Tuple, f64)> {
+        match self {
+            Object::Sphere => {
+                let z = 1.0 - 2.0 * rng.next_f64();
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * rng.next_f64();
+                Some((
+                    Tuple::new_point(r * phi.cos(), r * phi.sin(), z),
+                    self.surface_pdf(),
+                ))
+            }
+            Object::Plane => None,
+            Object::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                // Parameterized by angles around the y axis (phi) and around
+                // the center circle (theta), the area element is proportional
+                // to the distance to the y axis: sample theta by rejection.
+                let theta = loop {
+                    let theta = 2.0 * PI * rng.next_f64();
+                    let rho = major_radius + minor_radius * theta.cos();
+                    if rng.next_f64() * (major_radius + minor_radius) <= rho {
+                        break theta;
+                    }
+                };
+                let phi = 2.0 * PI * rng.next_f64();
+                let rho = major_radius + minor_radius * theta.cos();
+                Some((
+                    Tuple::new_point(rho * phi.cos(), minor_radius * theta.sin(), rho * phi.sin()),
+                    self.surface_pdf(),
+                ))
+            }
+            Object::Disk { inner_radius } => {
+                // The area inside radius r grows with r^2.
+                let r2 = inner_radius * inner_radius;
+                let r = (r2 + (1.0 - r2) * rng.next_f64()).sqrt();
+                let phi = 2.0 * PI * rng.next_f64();
+                Some((
+                    Tuple::new_point(r * phi.cos(), 0.0, r * phi.sin()),
+                    self.surface_pdf(),
+                ))
+            }
+            Object::Rectangle => Some((
+                Tuple::new_point(2.0 * rng.next_f64() - 1.0, 0.0, 2.0 * rng.next_f64() - 1.0),
+                self.surface_pdf(),
+            )),
         }
     }
+
+    /// See `Object::sample_surface()`.
+    fn surface_pdf(&self) -> f64 {
+        match self {
+            Object::Sphere => 1.0 / (4.0 * PI),
+            Object::Plane => 0.0,
+            Object::Torus {
+                major_radius,
+                minor_radius,
+            } => 1.0 / (4.0 * PI * PI * major_radius * minor_radius),
+            Object::Disk { inner_radius } => 1.0 / (PI * (1.0 - inner_radius * inner_radius)),
+            Object::Rectangle => 0.25,
+        }
+    }
+
+    /// Returns the texture mapping following the surface of this object.
+    pub fn uv_mapping(&self) -> UvMapping {
+        match self {
+            Object::Sphere => UvMapping::Spherical,
+            Object::Plane => UvMapping::Planar,
+            Object::Torus { major_radius, .. } => UvMapping::Toroidal {
+                major_radius: *major_radius,
+            },
+            Object::Disk { inner_radius } => UvMapping::Polar {
+                inner_radius: *inner_radius,
+            },
+            Object::Rectangle => UvMapping::Rectangular,
+        }
+    }
+}
+
+/// Returns distances along `ray` to a torus (see `Object::Torus`), in
+/// increasing order.
+fn torus_distances(ray: &Ray, major_radius: f64, minor_radius: f64) -> Vec<f64> {
+    // Coefficients of the quartic are much better conditioned with a unit
+    // direction and an origin close to the torus.
+    let scale = ray.direction().magnitude();
+    let dir = ray.direction().normalized();
+    let shift = -Tuple::dot(&(ray.origin() - &ORIGIN), &dir);
+    let o = &(ray.origin() - &ORIGIN) + &(&dir * shift);
+    let r2 = major_radius * major_radius;
+    let od = Tuple::dot(&o, &dir);
+    let k = Tuple::dot(&o, &o) + r2 - minor_radius * minor_radius;
+    let dxz = dir.x() * dir.x() + dir.z() * dir.z();
+    let oxz = o.x() * o.x() + o.z() * o.z();
+    let odxz = o.x() * dir.x() + o.z() * dir.z();
+    util::solve_quartic(
+        1.0,
+        4.0 * od,
+        2.0 * k + 4.0 * od * od - 4.0 * r2 * dxz,
+        4.0 * od * k - 8.0 * r2 * odxz,
+        k * k - 4.0 * r2 * oxz,
+    )
+    .into_iter()
+    .map(|t| (t + shift) / scale)
+    .collect()
 }
 
 /// Intersection between a `Shape` and a `Ray`.
@@ -149,19 +564,31 @@ impl Intersection<'_> {
     /// Precomputes data used to compute lighting and shading.
     ///
     /// `ray` is cast from the eye to this intersection point.
-    pub fn prepare_computations(&self, ray: &Ray) -> Computations {
+    pub fn prepare_computations(&self, ray: &Ray) -> Computations<'_> {
         debug_assert!(ray.direction().is_vector());
         let point = ray.position(self.distance);
-        let mut normal_vec = self.shape.normal_at(&point);
+        let mut geometric_normal = self.shape.normal_at_time(&point, ray.time());
         let eye_vec = -ray.direction();
         let mut inside = false;
-        if Tuple::dot(&normal_vec, &eye_vec) < 0.0 {
+        if Tuple::dot(&geometric_normal, &eye_vec) < 0.0 {
             inside = true;
-            normal_vec = -&normal_vec;
+            geometric_normal = -&geometric_normal;
         }
-        let over_point = &point + &(&normal_vec * util::EPSILON);
+        // Bumps only affect shading: shadow rays leave from the true surface.
+        let over_point = &point + &(&geometric_normal * util::EPSILON);
+        let normal_vec = if self.shape.material.bump.is_some() {
+            let n = self.shape.shading_normal_at(&point, ray.time());
+            if inside {
+                -&n
+            } else {
+                n
+            }
+        } else {
+            geometric_normal
+        };
         Computations {
             distance: self.distance,
+            time: ray.time(),
             object: self.shape,
             normal_vec,
             point,
@@ -178,6 +605,9 @@ pub struct Computations<'a> {
     /// `Intersection::distance` copy.
     pub distance: f64,
 
+    /// Time the intersecting ray was cast at.
+    pub time: f64,
+
     /// `Intersection::shape` copy.
     pub object: &'a Shape,
 
@@ -190,7 +620,8 @@ pub struct Computations<'a> {
     /// Vector from intersection point to eye.
     pub eye_vec: Tuple,
 
-    /// Surface normal vector at intersection point.
+    /// Surface normal vector at intersection point, including the bump of the
+    /// material of `object` if any.
     pub normal_vec: Tuple,
 
     /// The intersection is inside `object`.
@@ -200,11 +631,11 @@ pub struct Computations<'a> {
 /// Behavior associated with a sequence of `Intersection`.
 pub trait IntersectionList {
     /// Returns intersection with the smallest non-negative distance.
-    fn hit(&self) -> Option<&Intersection>;
+    fn hit(&self) -> Option<&Intersection<'_>>;
 }
 
 impl IntersectionList for Vec<Intersection<'_>> {
-    fn hit(&self) -> Option<&Intersection> {
+    fn hit(&self) -> Option<&Intersection<'_>> {
         self.iter().filter(|i| i.distance >= 0.0).min_by(|l, r| {
             if l.distance < r.distance {
                 Ordering::Less
@@ -218,13 +649,16 @@ impl IntersectionList for Vec<Intersection<'_>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bump::{Bump, BumpKind};
+    use crate::medium::Medium;
+    use crate::pattern::{Pattern, Texture};
     use crate::transform;
     use std::f64::consts::PI;
 
     #[test]
     fn sphere_default_transformation_is_identity() {
         assert_eq!(
-            Shape::new(Object::Sphere).transform,
+            *Shape::new(Object::Sphere).transform(),
             Matrix::new_4x4_identity()
         );
     }
@@ -233,15 +667,16 @@ mod tests {
     fn creating_sphere_with_non_default_transformation() {
         let t = transform::scaling(1.0, 2.0, 3.0);
         let s = Shape::with_transform(Object::Sphere, t.clone());
-        assert_eq!(s.transform, t);
+        assert_eq!(*s.transform(), t);
+        assert_eq!(*s.transform.inverted(), t.inverted());
     }
 
     #[test]
     fn changing_sphere_transformation() {
         let mut s = Shape::new(Object::Sphere);
         let t = transform::translation(2.0, 3.0, 4.0);
-        s.transform = t.clone();
-        assert_eq!(s.transform, t);
+        s.set_transform(Transform::identity().translate(2.0, 3.0, 4.0));
+        assert_eq!(*s.transform(), t);
     }
 
     #[test]
@@ -285,6 +720,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn normal_on_translated_sphere() {
         let s = Shape::with_transform(Object::Sphere, transform::translation(0.0, 1.0, 0.0));
         assert_eq!(
@@ -513,6 +949,7 @@ mod tests {
             i.prepare_computations(&r),
             Computations {
                 distance: 4.0,
+                time: 0.0,
                 object: &s,
                 point: Tuple::new_point(0.0, 0.0, -1.0),
                 over_point: Tuple::new_point(0.0, 0.0, -1.00001),
@@ -535,6 +972,7 @@ mod tests {
             i.prepare_computations(&r),
             Computations {
                 distance: 1.0,
+                time: 0.0,
                 object: &s,
                 point: Tuple::new_point(0.0, 0.0, 1.0),
                 over_point: Tuple::new_point(0.0, 0.0, 0.99999),
@@ -549,7 +987,7 @@ mod tests {
     fn hit_should_offset_point() {
         let r = Ray::from_triplets((0.0, 0.0, -5.0), (0.0, 0.0, 1.0));
         let s = Shape {
-            transform: transform::translation(0.0, 0.0, 1.0),
+            transform: Transform::identity().translate(0.0, 0.0, 1.0),
             ..Shape::new(Object::Sphere)
         };
         let i = Intersection {
@@ -561,6 +999,143 @@ mod tests {
         assert!(comps.point.z() > comps.over_point.z());
     }
 
+    #[test]
+    fn bump_affects_shading_normal_only() {
+        let r = Ray::from_triplets((0.5, 1.0, 0.5), (0.0, -1.0, 0.0));
+        let mut s = Shape::new(Object::Plane);
+        s.material.bump = Some(Bump::new(BumpKind::NormalMap {
+            mapping: None,
+            texture: Texture::Function(|_, _| Color::new(1.0, 0.5, 1.0)),
+        }));
+        let i = Intersection {
+            distance: 1.0,
+            shape: &s,
+        };
+        let comps = i.prepare_computations(&r);
+        assert_eq!(
+            comps.normal_vec,
+            Tuple::new_vector(1.0, 1.0, 0.0).normalized()
+        );
+        assert_eq!(comps.over_point, Tuple::new_point(0.5, util::EPSILON, 0.5));
+        assert_eq!(s.normal_at(&comps.point), Tuple::new_vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn interpolating_motion_keyframes() {
+        let m = Motion::new(vec![
+            (1.0, transform::translation(2.0, 0.0, 0.0)),
+            (0.0, transform::translation(0.0, 0.0, 0.0)),
+            (2.0, transform::translation(2.0, 4.0, 0.0)),
+        ])
+        .unwrap();
+        assert_eq!(m.keyframes()[0].0, 0.0);
+        assert_eq!(m.transform_at(-1.0), transform::translation(0.0, 0.0, 0.0));
+        assert_eq!(m.transform_at(0.5), transform::translation(1.0, 0.0, 0.0));
+        assert_eq!(m.transform_at(1.0), transform::translation(2.0, 0.0, 0.0));
+        assert_eq!(m.transform_at(1.25), transform::translation(2.0, 1.0, 0.0));
+        assert_eq!(m.transform_at(3.0), transform::translation(2.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn motion_needs_keyframes_at_finite_times() {
+        assert_eq!(Motion::new(Vec::new()), Err(Error::NoKeyframes));
+        let identity = Matrix::new_4x4_identity;
+        assert_eq!(
+            Motion::new(vec![(0.0, identity()), (f64::NAN, identity())]),
+            Err(Error::NonFiniteTime)
+        );
+        assert_eq!(
+            Motion::new(vec![(f64::INFINITY, identity())]),
+            Err(Error::NonFiniteTime)
+        );
+    }
+
+    #[test]
+    fn validating_motions() {
+        let m = Motion::linear(
+            transform::rotation_y(0.0),
+            &transform::translation(0.0, 2.0, 0.0) * &transform::scaling(2.0, 0.5, 1.0),
+        );
+        assert_eq!(m.validate(), Ok(()));
+        let m = Motion::linear(
+            transform::scaling(-1.0, 1.0, 1.0),
+            transform::scaling(2.0, 1.0, -3.0),
+        );
+        assert_eq!(m.validate(), Ok(()));
+
+        // Interpolating between mirrored keyframes goes through a flat shape.
+        let m = Motion::linear(
+            transform::scaling(1.0, 1.0, 1.0),
+            transform::scaling(-1.0, 1.0, 1.0),
+        );
+        assert!(!m.transform_at(0.5).invertible());
+        assert_eq!(m.validate(), Err(Error::MirroringMotion));
+
+        let mut projective = Matrix::new_4x4_identity();
+        projective.set(3, 2, 1.0);
+        let m = Motion::linear(Matrix::new_4x4_identity(), projective);
+        assert_eq!(m.validate(), Err(Error::NotAffine));
+    }
+
+    #[test]
+    fn rotating_motion_turns_smoothly() {
+        let m = Motion::linear(
+            transform::rotation_y(0.0),
+            &transform::translation(0.0, 2.0, 0.0) * &transform::rotation_y(PI),
+        );
+        assert_eq!(
+            m.transform_at(0.5),
+            &transform::translation(0.0, 1.0, 0.0) * &transform::rotation_y(PI / 2.0)
+        );
+        // Interpolating non-invertible transformations still works.
+        let m = Motion::linear(
+            transform::scaling(0.0, 1.0, 1.0),
+            transform::scaling(2.0, 1.0, 1.0),
+        );
+        assert_eq!(m.transform_at(0.5), transform::scaling(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn moving_sphere_is_intersected_where_it_is_at_ray_time() {
+        let s = Shape {
+            motion: Some(Motion::linear(
+                Matrix::new_4x4_identity(),
+                transform::translation(0.0, 4.0, 0.0),
+            )),
+            ..Shape::new(Object::Sphere)
+        };
+        let r = Ray::from_triplets((0.0, 2.0, -5.0), (0.0, 0.0, 1.0));
+        assert!(s.intersections(&r).is_empty());
+        assert_eq!(
+            s.intersections(&r.with_time(0.5)),
+            [
+                Intersection {
+                    shape: &s,
+                    distance: 4.0
+                },
+                Intersection {
+                    shape: &s,
+                    distance: 6.0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn normal_on_moving_sphere() {
+        let s = Shape {
+            motion: Some(Motion::linear(
+                Matrix::new_4x4_identity(),
+                transform::translation(0.0, 4.0, 0.0),
+            )),
+            ..Shape::new(Object::Sphere)
+        };
+        assert_eq!(
+            s.normal_at_time(&Tuple::new_point(0.0, 2.0, -1.0), 0.5),
+            Tuple::new_vector(0.0, 0.0, -1.0)
+        );
+    }
+
     #[test]
     fn normal_of_plane_is_constant_everywhere() {
         let p = Object::Plane;
@@ -609,4 +1184,284 @@ mod tests {
             }]
         );
     }
+
+    #[test]
+    fn color_of_shape_without_pattern() {
+        let s = Shape::new(Object::Sphere);
+        assert_eq!(
+            s.color_at(&Tuple::new_point(1.0, 0.0, 0.0), 0.0),
+            crate::color::WHITE
+        );
+    }
+
+    #[test]
+    fn color_of_pattern_in_object_space() {
+        let mut s = Shape::with_transform(Object::Sphere, transform::translation(0.0, 5.0, 0.0));
+        s.material.pattern = Some(Pattern::texture(Texture::Function(|u, v| {
+            Color::new(u, v, 0.0)
+        })));
+        assert_eq!(
+            s.color_at(&Tuple::new_point(1.0, 5.0, 0.0), 0.0),
+            Color::new(0.25, 0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn texture_coordinates_in_object_space() {
+        let s = Shape::with_transform(Object::Sphere, transform::translation(0.0, 5.0, 0.0));
+        assert_eq!(s.uv_at(&Tuple::new_point(1.0, 5.0, 0.0), 0.0), (0.25, 0.5));
+        let p = Shape::new(Object::Plane);
+        assert_eq!(
+            p.uv_at(&Tuple::new_point(2.25, 0.0, -0.5), 0.0),
+            (0.25, 0.5)
+        );
+    }
+
+    #[test]
+    fn validating_shapes() {
+        let mut s = Shape::new(Object::Sphere);
+        assert_eq!(s.validate(), Ok(()));
+        s.motion = Some(Motion::linear(
+            Matrix::new_4x4_identity(),
+            transform::scaling(0.0, 1.0, 1.0),
+        ));
+        assert_eq!(s.validate(), Err(Error::SingularMatrix));
+
+        let mut s = Shape::with_transform(Object::Plane, transform::scaling(1.0, 0.0, 1.0));
+        assert_eq!(s.validate(), Err(Error::SingularMatrix));
+        s.set_transform(Transform::identity());
+        s.material.medium = Some(Medium {
+            absorption: 1.0,
+            scattering: 0.0,
+        });
+        assert_eq!(s.validate(), Err(Error::OpenMedium));
+        s.object = Object::Disk { inner_radius: 0.0 };
+        assert_eq!(s.validate(), Err(Error::OpenMedium));
+        s.object = Object::Rectangle;
+        assert_eq!(s.validate(), Err(Error::OpenMedium));
+        s.object = Object::Torus {
+            major_radius: 1.0,
+            minor_radius: 0.25,
+        };
+        assert_eq!(s.validate(), Ok(()));
+    }
+
+    #[test]
+    fn sampling_sphere_surface() {
+        let s = Shape::with_transform(
+            Object::Sphere,
+            &transform::translation(1.0, 0.0, 0.0) * &transform::scaling(2.0, 2.0, 2.0),
+        );
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let sample = s.sample_surface(0.0, &mut rng).unwrap();
+            let center = Tuple::new_point(1.0, 0.0, 0.0);
+            assert!(util::nearly_equal(
+                (&sample.point - &center).magnitude(),
+                2.0
+            ));
+            assert_eq!(sample.normal, (&sample.point - &center).normalized());
+            assert!(util::nearly_equal(sample.pdf, 1.0 / (16.0 * PI)));
+            assert!(util::nearly_equal(
+                s.surface_pdf(&sample.point, 0.0),
+                sample.pdf
+            ));
+        }
+    }
+
+    #[test]
+    fn sampling_ellipsoid_surface_covers_its_area() {
+        // Prolate spheroid with semi-axes 2, 1 and 1.
+        let s = Shape::with_transform(Object::Sphere, transform::scaling(2.0, 1.0, 1.0));
+        let e = 0.75_f64.sqrt();
+        let area = 2.0 * PI * (1.0 + 2.0 / e * e.asin());
+        let mut rng = Rng::new(0);
+        let n = 20000;
+        let mut estimate = 0.0;
+        for _ in 0..n {
+            let sample = s.sample_surface(0.0, &mut rng).unwrap();
+            assert!(util::nearly_equal(
+                s.surface_pdf(&sample.point, 0.0),
+                sample.pdf
+            ));
+            estimate += 1.0 / sample.pdf / n as f64;
+        }
+        assert!((estimate - area).abs() < 0.01 * area);
+    }
+
+    fn torus() -> Shape {
+        Shape::new(Object::Torus {
+            major_radius: 1.0,
+            minor_radius: 0.25,
+        })
+    }
+
+    fn distances(xs: &[Intersection]) -> Vec<f64> {
+        xs.iter().map(|x| x.distance).collect()
+    }
+
+    #[test]
+    fn ray_intersects_torus() {
+        let t = torus();
+        let cases = [
+            // Through the hole.
+            (
+                (-5.0, 0.0, 0.0),
+                (1.0, 0.0, 0.0),
+                vec![3.75, 4.25, 5.75, 6.25],
+            ),
+            // Through the tube only.
+            ((1.0, 5.0, 0.0), (0.0, -1.0, 0.0), vec![4.75, 5.25]),
+            // Tangent to the top of the tube.
+            ((-5.0, 0.25, 0.0), (2.0, 0.0, 0.0), vec![2.0, 2.0, 3.0, 3.0]),
+            // Along the axis, through the hole.
+            ((0.0, 5.0, 0.0), (0.0, -1.0, 0.0), vec![]),
+            // Far from the torus, with a scaled direction.
+            ((0.0, 1000.0, -1000.0), (0.0, 0.0, 0.001), vec![]),
+        ];
+        for (origin, direction, expected) in &cases {
+            let r = Ray::from_triplets(*origin, *direction);
+            let actual = distances(&t.object.intersections(&t, r));
+            assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-6, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn intersecting_distant_torus_is_accurate() {
+        let t = Shape::with_transform(
+            Object::Torus {
+                major_radius: 1.0,
+                minor_radius: 0.01,
+            },
+            transform::translation(0.0, 1000.0, 0.0),
+        );
+        let r = Ray::from_triplets((1.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+        let xs = distances(&t.intersections(&r));
+        assert_eq!(xs.len(), 2);
+        assert!((xs[0] - 999.99).abs() < 1e-6, "{:?}", xs);
+        assert!((xs[1] - 1000.01).abs() < 1e-6, "{:?}", xs);
+    }
+
+    #[test]
+    fn normal_on_torus() {
+        let t = torus();
+        let cases = [
+            ((1.25, 0.0, 0.0), (1.0, 0.0, 0.0)),
+            ((0.75, 0.0, 0.0), (-1.0, 0.0, 0.0)),
+            ((0.0, 0.25, -1.0), (0.0, 1.0, 0.0)),
+            ((0.0, 0.0, -1.25), (0.0, 0.0, -1.0)),
+        ];
+        for ((x, y, z), (nx, ny, nz)) in &cases {
+            assert_eq!(
+                t.normal_at(&Tuple::new_point(*x, *y, *z)),
+                Tuple::new_vector(*nx, *ny, *nz)
+            );
+        }
+        assert!(t.object.is_bounded());
+    }
+
+    #[test]
+    fn sampling_torus_surface_covers_its_area() {
+        let t = Shape::with_transform(
+            Object::Torus {
+                major_radius: 1.0,
+                minor_radius: 0.25,
+            },
+            transform::scaling(2.0, 2.0, 2.0),
+        );
+        let area = 4.0 * PI * PI * 2.0 * 0.5;
+        let mut rng = Rng::new(0);
+        let n = 1000;
+        let mut above = 0;
+        for _ in 0..n {
+            let sample = t.sample_surface(0.0, &mut rng).unwrap();
+            let p = &sample.point;
+            let tube = (p.x().hypot(p.z()) - 2.0).hypot(p.y());
+            assert!(util::nearly_equal(tube, 0.5));
+            assert!(util::nearly_equal(sample.pdf, 1.0 / area));
+            assert!(util::nearly_equal(t.surface_pdf(p, 0.0), sample.pdf));
+            if p.x().hypot(p.z()) > 2.0 {
+                above += 1;
+            }
+        }
+        // The outer half of the tube has more area than the inner half.
+        let outer = 0.5 + 0.25 / PI;
+        assert!((above as f64 / n as f64 - outer).abs() < 0.05, "{}", above);
+    }
+
+    #[test]
+    fn ray_intersects_disk_and_rectangle_inside_their_bounds() {
+        let disk = Shape::new(Object::Disk { inner_radius: 0.0 });
+        let annulus = Shape::new(Object::Disk { inner_radius: 0.5 });
+        let rect = Shape::new(Object::Rectangle);
+        let cases = [
+            ((0.0, 1.0, 0.0), vec![1.0], vec![], vec![1.0]),
+            ((0.7, 1.0, 0.0), vec![1.0], vec![1.0], vec![1.0]),
+            ((0.8, 1.0, 0.8), vec![], vec![], vec![1.0]),
+            ((0.0, 1.0, 1.2), vec![], vec![], vec![]),
+        ];
+        for (origin, d, a, r) in &cases {
+            let ray = Ray::from_triplets(*origin, (0.0, -1.0, 0.0));
+            assert_eq!(&distances(&disk.intersections(&ray)), d, "{:?}", origin);
+            assert_eq!(&distances(&annulus.intersections(&ray)), a, "{:?}", origin);
+            assert_eq!(&distances(&rect.intersections(&ray)), r, "{:?}", origin);
+        }
+        let parallel = Ray::from_triplets((-2.0, 0.0, 0.0), (1.0, 0.0, 0.0));
+        assert!(disk.intersections(&parallel).is_empty());
+        assert_eq!(rect.normal_at(&ORIGIN), Tuple::new_vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn sampling_disk_and_rectangle_surfaces() {
+        let annulus = Shape::with_transform(
+            Object::Disk { inner_radius: 0.5 },
+            transform::scaling(2.0, 1.0, 2.0),
+        );
+        let rect = Shape::with_transform(
+            Object::Rectangle,
+            &transform::rotation_x(PI / 2.0) * &transform::scaling(3.0, 1.0, 0.5),
+        );
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            let sample = annulus.sample_surface(0.0, &mut rng).unwrap();
+            let r = sample.point.x().hypot(sample.point.z());
+            assert!((1.0..=2.0).contains(&r) && sample.point.y() == 0.0);
+            assert!(util::nearly_equal(sample.pdf, 1.0 / (3.0 * PI)));
+            assert!(util::nearly_equal(
+                annulus.surface_pdf(&sample.point, 0.0),
+                sample.pdf
+            ));
+
+            let sample = rect.sample_surface(0.0, &mut rng).unwrap();
+            let p = &sample.point;
+            assert!(p.x().abs() <= 3.0 && p.y().abs() <= 0.5 && util::nearly_equal(p.z(), 0.0));
+            assert_eq!(sample.normal, Tuple::new_vector(0.0, 0.0, 1.0));
+            assert!(util::nearly_equal(sample.pdf, 1.0 / 6.0));
+        }
+    }
+
+    #[test]
+    fn texture_coordinates_on_disk_and_rectangle() {
+        let annulus = Shape::new(Object::Disk { inner_radius: 0.5 });
+        assert_eq!(
+            annulus.uv_at(&Tuple::new_point(0.75, 0.0, 0.0), 0.0),
+            (0.25, 0.5)
+        );
+        let rect = Shape::with_transform(Object::Rectangle, transform::scaling(2.0, 1.0, 1.0));
+        assert_eq!(
+            rect.uv_at(&Tuple::new_point(-1.0, 0.0, 0.5), 0.0),
+            (0.25, 0.75)
+        );
+    }
+
+    #[test]
+    fn unbounded_shapes_cannot_be_sampled() {
+        let p = Shape::new(Object::Plane);
+        assert!(!p.object.is_bounded());
+        assert_eq!(p.sample_surface(0.0, &mut Rng::new(0)), None);
+        assert_eq!(p.surface_pdf(&ORIGIN, 0.0), 0.0);
+    }
 }
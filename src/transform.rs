@@ -2,11 +2,30 @@
 //!
 //! See TRTC chapter 4.
 //!
+//! `Transform` chains transformations in reading order:
+//!
+//! ```
+//! use rustytracer::transform::{self, Transform};
+//!
+//! let t = Transform::identity()
+//!     .scale(2.0, 2.0, 2.0)
+//!     .rotate_y(0.5)
+//!     .translate(1.0, 0.0, 0.0);
+//! assert_eq!(
+//!     *t.matrix(),
+//!     &(&transform::translation(1.0, 0.0, 0.0) * &transform::rotation_y(0.5))
+//!         * &transform::scaling(2.0, 2.0, 2.0)
+//! );
+//! ```
+//!
 //! TODO: Multiplying more than 2 matrices is ugly becuse of explicit references.
 //! TODO: Is it possible to implement expression templates?
 
+use crate::error::Error;
 use crate::matrix::Matrix;
+use crate::quaternion::Quaternion;
 use crate::tuple::Tuple;
+use crate::util;
 
 /// Returns a matrix that encodes the given translation.
 pub fn translation(dx: f64, dy: f64, dz: f64) -> Matrix {
@@ -56,6 +75,12 @@ pub fn rotation_z(angle: f64) -> Matrix {
     res
 }
 
+/// Returns matrix encoding rotation of `angle` radians around vector `axis`
+/// through the origin.
+pub fn rotation(axis: &Tuple, angle: f64) -> Matrix {
+    Quaternion::from_axis_angle(axis, angle).to_matrix()
+}
+
 /// Returns matrix encoding shearing effect.
 pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix {
     let mut res = Matrix::new_4x4_identity();
@@ -87,6 +112,244 @@ pub fn view(from: &Tuple, to: &Tuple, up: &Tuple) -> Matrix {
     &orientation * &translation(-from.x(), -from.y(), -from.z())
 }
 
+/// Sequence of transformations applied in the order they are added, with its
+/// inverse computed step by step rather than by inverting the whole matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    /// `None` once a step is not invertible.
+    inverse: Option<Matrix>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    /// Creates a transformation leaving everything in place.
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix::new_4x4_identity(),
+            inverse: Some(Matrix::new_4x4_identity()),
+        }
+    }
+
+    /// Returns this transformation followed by `step`, whose inverse is
+    /// `inverse` if any.
+    fn then_with_inverse(self, step: &Matrix, inverse: Option<Matrix>) -> Transform {
+        Transform {
+            matrix: step * &self.matrix,
+            inverse: match (self.inverse, inverse) {
+                (Some(i), Some(step_inverse)) => Some(&i * &step_inverse),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn translate(self, dx: f64, dy: f64, dz: f64) -> Transform {
+        self.then_with_inverse(&translation(dx, dy, dz), Some(translation(-dx, -dy, -dz)))
+    }
+
+    pub fn scale(self, x: f64, y: f64, z: f64) -> Transform {
+        let inverse = if x != 0.0 && y != 0.0 && z != 0.0 {
+            Some(scaling(1.0 / x, 1.0 / y, 1.0 / z))
+        } else {
+            None
+        };
+        self.then_with_inverse(&scaling(x, y, z), inverse)
+    }
+
+    pub fn rotate_x(self, angle: f64) -> Transform {
+        self.then_with_inverse(&rotation_x(angle), Some(rotation_x(-angle)))
+    }
+
+    pub fn rotate_y(self, angle: f64) -> Transform {
+        self.then_with_inverse(&rotation_y(angle), Some(rotation_y(-angle)))
+    }
+
+    pub fn rotate_z(self, angle: f64) -> Transform {
+        self.then_with_inverse(&rotation_z(angle), Some(rotation_z(-angle)))
+    }
+
+    /// See `rotation()`.  A zero or infinite `axis`, or an infinite `angle`,
+    /// gives no rotation at all: the result has no inverse.
+    pub fn rotate(self, axis: &Tuple, angle: f64) -> Transform {
+        let step = Quaternion::from_axis_angle(axis, angle).to_matrix();
+        let length = axis.magnitude();
+        // The inverse of a rotation matrix is its transpose.
+        let inverse = if length > 0.0 && length.is_finite() && angle.is_finite() {
+            Some(step.transposed())
+        } else {
+            None
+        };
+        self.then_with_inverse(&step, inverse)
+    }
+
+    /// See `shearing()`.  Unlike other steps, the inverse of shearing is
+    /// computed numerically.
+    pub fn shear(self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Transform {
+        self.then(&shearing(xy, xz, yx, yz, zx, zy))
+    }
+
+    /// Returns this transformation followed by arbitrary transformation `m`,
+    /// whose inverse is computed numerically.
+    pub fn then(self, m: &Matrix) -> Transform {
+        let inverse = m.try_inverted().ok();
+        self.then_with_inverse(m, inverse)
+    }
+
+    /// Returns this transformation followed by `t`.
+    pub fn then_transform(self, t: &Transform) -> Transform {
+        self.then_with_inverse(&t.matrix, t.inverse.clone())
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    /// Returns matrix undoing this transformation, or `None` if a step of it
+    /// is not invertible.
+    pub fn inverse(&self) -> Option<&Matrix> {
+        self.inverse.as_ref()
+    }
+
+    /// Returns matrix undoing this transformation, or an error if a step of
+    /// it is not invertible.
+    pub fn try_inverse(&self) -> Result<&Matrix, Error> {
+        self.inverse.as_ref().ok_or(Error::SingularMatrix)
+    }
+
+    /// Returns matrix undoing this transformation.
+    ///
+    /// Panics if this transformation is not invertible.  See `inverse()`.
+    pub fn inverted(&self) -> &Matrix {
+        self.inverse
+            .as_ref()
+            .expect("transformation is not invertible")
+    }
+}
+
+impl From<Matrix> for Transform {
+    /// Wraps arbitrary transformation `m`, whose inverse is computed
+    /// numerically, once.
+    fn from(m: Matrix) -> Transform {
+        Transform {
+            inverse: m.try_inverted().ok(),
+            matrix: m,
+        }
+    }
+}
+
+impl From<Transform> for Matrix {
+    fn from(t: Transform) -> Matrix {
+        t.matrix
+    }
+}
+
+/// Affine transformation split into simple parts, to be interpolated or
+/// exported.  See `decompose()`.
+///
+/// The transformation scales, then shears, then rotates, then translates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decomposition {
+    pub translation: Tuple,
+    pub rotation: Quaternion,
+    /// Scale factors along x, y and z.  Mirroring negates the z one.
+    pub scale: Tuple,
+    /// `xy`, `xz` and `yz` arguments of `shearing()`, 0 for most transformations.
+    pub shear: (f64, f64, f64),
+}
+
+impl Decomposition {
+    /// Returns the transformation matrix this decomposition comes from.
+    pub fn matrix(&self) -> Matrix {
+        let t = &self.translation;
+        let s = &self.scale;
+        let (xy, xz, yz) = self.shear;
+        &(&translation(t.x(), t.y(), t.z()) * &self.rotation.to_matrix())
+            * &(&shearing(xy, xz, 0.0, yz, 0.0, 0.0) * &scaling(s.x(), s.y(), s.z()))
+    }
+
+    /// Returns transformation between this one (`t == 0`) and `o` (`t == 1`),
+    /// rotating smoothly and interpolating other parts linearly.
+    pub fn interpolated(&self, o: &Decomposition, t: f64) -> Decomposition {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Decomposition {
+            translation: &self.translation + &(&(&o.translation - &self.translation) * t),
+            rotation: self.rotation.slerp(&o.rotation, t),
+            scale: &self.scale + &(&(&o.scale - &self.scale) * t),
+            shear: (
+                lerp(self.shear.0, o.shear.0),
+                lerp(self.shear.1, o.shear.1),
+                lerp(self.shear.2, o.shear.2),
+            ),
+        }
+    }
+}
+
+/// Splits affine transformation `m` into translation, rotation, scale and
+/// shear.  Returns an error if `m` is not affine or not invertible.
+pub fn decompose(m: &Matrix) -> Result<Decomposition, Error> {
+    assert!(m.nrows() == 4 && m.ncols() == 4);
+    let last_row = [m.get(3, 0), m.get(3, 1), m.get(3, 2), m.get(3, 3)];
+    if last_row
+        .iter()
+        .zip(&[0.0, 0.0, 0.0, 1.0])
+        .any(|(a, b)| !util::nearly_equal(*a, *b))
+    {
+        return Err(Error::NotAffine);
+    }
+    let column = |c| Tuple::new_vector(m.get(0, c), m.get(1, c), m.get(2, c));
+
+    // Gram-Schmidt orthonormalization of the columns of the linear part gives
+    // the rotation, with the scale and shear left in an upper triangular
+    // matrix.
+    let c0 = column(0);
+    let sx = c0.magnitude();
+    if sx == 0.0 {
+        return Err(Error::SingularMatrix);
+    }
+    let r0 = &c0 * (1.0 / sx);
+
+    let c1 = column(1);
+    let a = Tuple::dot(&r0, &c1);
+    let c1 = &c1 - &(&r0 * a);
+    let sy = c1.magnitude();
+    if sy == 0.0 {
+        return Err(Error::SingularMatrix);
+    }
+    let r1 = &c1 * (1.0 / sy);
+
+    let c2 = column(2);
+    let b = Tuple::dot(&r0, &c2);
+    let c = Tuple::dot(&r1, &c2);
+    let c2 = &(&c2 - &(&r0 * b)) - &(&r1 * c);
+    let mut sz = c2.magnitude();
+    if sz == 0.0 {
+        return Err(Error::SingularMatrix);
+    }
+    let mut r2 = &c2 * (1.0 / sz);
+    if Tuple::dot(&Tuple::cross(&r0, &r1), &r2) < 0.0 {
+        r2 = -&r2;
+        sz = -sz;
+    }
+
+    let rotation = Quaternion::from_matrix(&Matrix::new_4x4(&[
+        [r0.x(), r1.x(), r2.x(), 0.0],
+        [r0.y(), r1.y(), r2.y(), 0.0],
+        [r0.z(), r1.z(), r2.z(), 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]));
+    Ok(Decomposition {
+        translation: Tuple::new_vector(m.get(0, 3), m.get(1, 3), m.get(2, 3)),
+        rotation,
+        scale: Tuple::new_vector(sx, sy, sz),
+        shear: (a / sy, b / sz, c / sz),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,4 +587,117 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn rotating_around_arbitrary_axis() {
+        let axis = Tuple::new_vector(1.0, 1.0, 1.0);
+        // A third of a turn around the diagonal cycles the axes.
+        let r = rotation(&axis, 2.0 * PI / 3.0);
+        assert_eq!(
+            &r * &Tuple::new_point(1.0, 0.0, 0.0),
+            Tuple::new_point(0.0, 1.0, 0.0)
+        );
+        assert_eq!(&r * &axis, axis);
+        assert_eq!(
+            rotation(&Tuple::new_vector(0.0, 1.0, 0.0), 0.4),
+            rotation_y(0.4)
+        );
+    }
+
+    #[test]
+    fn decomposing_affine_transformations() {
+        let m = &(&translation(1.0, -2.0, 3.0) * &rotation_y(0.7))
+            * &(&rotation_x(-0.3) * &scaling(2.0, 0.5, 3.0));
+        let shear_is = |d: &Decomposition, (xy, xz, yz)| {
+            util::nearly_equal(d.shear.0, xy)
+                && util::nearly_equal(d.shear.1, xz)
+                && util::nearly_equal(d.shear.2, yz)
+        };
+        let d = decompose(&m).unwrap();
+        assert_eq!(d.translation, Tuple::new_vector(1.0, -2.0, 3.0));
+        assert_eq!(d.scale, Tuple::new_vector(2.0, 0.5, 3.0));
+        assert!(shear_is(&d, (0.0, 0.0, 0.0)));
+        assert_eq!(d.rotation.to_matrix(), &rotation_y(0.7) * &rotation_x(-0.3));
+        assert_eq!(d.matrix(), m);
+
+        // Shearing and mirroring are kept too.
+        let m = &(&rotation_z(1.0) * &shearing(0.5, 0.0, 0.0, 0.25, 0.0, 0.0))
+            * &scaling(1.0, 2.0, -1.0);
+        let d = decompose(&m).unwrap();
+        assert_eq!(d.scale.z(), -1.0);
+        assert!(shear_is(&d, (0.5, 0.0, 0.25)));
+        assert_eq!(d.matrix(), m);
+    }
+
+    #[test]
+    fn decomposing_invalid_transformations() {
+        assert_eq!(
+            decompose(&scaling(1.0, 0.0, 1.0)),
+            Err(Error::SingularMatrix)
+        );
+        let mut m = Matrix::new_4x4_identity();
+        m.set(3, 0, 1.0);
+        assert_eq!(decompose(&m), Err(Error::NotAffine));
+    }
+
+    #[test]
+    fn interpolating_decompositions_rotates_rigidly() {
+        let a = decompose(&translation(0.0, 0.0, 0.0)).unwrap();
+        let b = decompose(&(&translation(2.0, 0.0, 0.0) * &rotation_z(PI / 2.0))).unwrap();
+        let m = a.interpolated(&b, 0.5).matrix();
+        assert_eq!(m, &translation(1.0, 0.0, 0.0) * &rotation_z(PI / 4.0));
+        // Unlike element-wise interpolation, lengths are preserved.
+        let v = &m * &Tuple::new_vector(1.0, 0.0, 0.0);
+        assert!(util::nearly_equal(v.magnitude(), 1.0));
+    }
+
+    #[test]
+    fn building_transform_in_reading_order() {
+        let t = Transform::identity()
+            .scale(2.0, 3.0, 4.0)
+            .rotate_x(0.1)
+            .rotate_y(0.2)
+            .rotate_z(0.3)
+            .rotate(&Tuple::new_vector(1.0, 1.0, 0.0), 0.4)
+            .shear(0.5, 0.0, 0.0, 0.25, 0.0, 0.0)
+            .translate(1.0, -2.0, 3.0);
+        let m = &(&(&translation(1.0, -2.0, 3.0) * &shearing(0.5, 0.0, 0.0, 0.25, 0.0, 0.0))
+            * &(&rotation(&Tuple::new_vector(1.0, 1.0, 0.0), 0.4) * &rotation_z(0.3)))
+            * &(&(&rotation_y(0.2) * &rotation_x(0.1)) * &scaling(2.0, 3.0, 4.0));
+        assert_eq!(*t.matrix(), m);
+        assert_eq!(*t.inverse().unwrap(), m.inverted());
+        assert_eq!(&m * t.inverse().unwrap(), Matrix::new_4x4_identity());
+        assert_eq!(Matrix::from(t), m);
+        assert_eq!(Transform::default(), Transform::identity());
+    }
+
+    #[test]
+    fn transform_with_singular_step_has_no_inverse() {
+        let t = Transform::identity()
+            .translate(1.0, 0.0, 0.0)
+            .scale(1.0, 0.0, 1.0);
+        assert_eq!(
+            *t.matrix(),
+            &scaling(1.0, 0.0, 1.0) * &translation(1.0, 0.0, 0.0)
+        );
+        assert_eq!(t.inverse(), None);
+        assert_eq!(t.rotate_x(1.0).inverse(), None);
+        let t = Transform::identity().then(&Matrix::new(4, 4));
+        assert_eq!(t.inverse(), None);
+        let t = Transform::identity().rotate(&Tuple::new_vector(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(t.inverse(), None);
+        let t = Transform::identity().rotate(&Tuple::new_vector(0.0, 1.0, 0.0), f64::NAN);
+        assert_eq!(t.inverse(), None);
+        assert_eq!(Transform::from(Matrix::new(4, 4)).inverse(), None);
+    }
+
+    #[test]
+    fn chaining_transforms() {
+        let a = Transform::identity().scale(2.0, 2.0, 2.0);
+        let b = Transform::from(&translation(1.0, 0.0, 0.0) * &rotation_z(0.5));
+        let t = a.then_transform(&b);
+        let m = &(&translation(1.0, 0.0, 0.0) * &rotation_z(0.5)) * &scaling(2.0, 2.0, 2.0);
+        assert_eq!(*t.matrix(), m);
+        assert_eq!(*t.inverted(), m.inverted());
+    }
 }
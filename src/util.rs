@@ -10,3 +10,186 @@ pub const EPSILON: f64 = 0.00001;
 pub fn nearly_equal(a: f64, b: f64) -> bool {
     (a - b).abs() < EPSILON
 }
+
+/// Newton iterations refining each root found by the closed-form solvers,
+/// which lose precision to cancellation.
+const POLISH_ITERATIONS: usize = 3;
+
+/// Returns real roots of `a x^2 + b x + c`, sorted, repeated if double.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { Vec::new() } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    // Avoids cancellation between -b and the square root.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let mut roots = if q == 0.0 {
+        vec![0.0, 0.0]
+    } else {
+        vec![q / a, c / q]
+    };
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Returns real roots of `a x^3 + b x^2 + c x + d`, sorted.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+    let (b, c, d) = (b / a, c / a, d / a);
+    // Depressed cubic t^3 + p t + q with x = t - b / 3.
+    let shift = b / 3.0;
+    let p = c - b * shift;
+    let q = 2.0 * shift * shift * shift - shift * c + d;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    let ts = if p == 0.0 {
+        vec![(-q).cbrt()]
+    } else if discriminant > 0.0 {
+        let sqrt = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt()]
+    } else {
+        // Three real roots, by the trigonometric method.
+        let r = 2.0 * (-p / 3.0).sqrt();
+        let phi = (3.0 * q / (p * r)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| r * (phi - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos())
+            .collect()
+    };
+    let poly = [1.0, b, c, d];
+    let mut roots: Vec<f64> = ts.iter().map(|t| polish(&poly, t - shift)).collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Returns real roots of `a x^4 + b x^3 + c x^2 + d x + e`, sorted, repeated
+/// if double.
+///
+/// Uses Ferrari's method, then refines roots with Newton's method.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // Depressed quartic y^4 + p y^2 + q y + r with x = y - b / 4.
+    let shift = b / 4.0;
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let ys = if q.abs() < 1e-12 {
+        // Biquadratic: quadratic in y^2.
+        solve_quadratic(1.0, p, r)
+            .into_iter()
+            .filter(|z| *z >= 0.0)
+            .flat_map(|z| vec![-z.sqrt(), z.sqrt()])
+            .collect()
+    } else {
+        // With m a positive root of the resolvent cubic, the depressed
+        // quartic factors into two quadratics.
+        let m = solve_cubic(1.0, p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            Vec::new()
+        } else {
+            let s = (2.0 * m).sqrt();
+            let mut ys = solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s));
+            ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+            ys
+        }
+    };
+    let poly = [1.0, b, c, d, e];
+    let mut roots: Vec<f64> = ys.iter().map(|y| polish(&poly, y - shift)).collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Returns root `x` of polynomial `poly` (coefficients from the highest
+/// degree) refined by Newton's method.
+fn polish(poly: &[f64], mut x: f64) -> f64 {
+    let eval = |x: f64| {
+        poly.iter().fold((0.0, 0.0), |(value, derivative), coef| {
+            (value * x + coef, derivative * x + value)
+        })
+    };
+    for _ in 0..POLISH_ITERATIONS {
+        let (value, derivative) = eval(x);
+        if derivative == 0.0 {
+            break;
+        }
+        let next = x - value / derivative;
+        // Stop when Newton's method stops improving, e.g. at double roots.
+        if !next.is_finite() || eval(next).0.abs() >= value.abs() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn solving_quadratics() {
+        assert_roots(solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0]);
+        assert_roots(solve_quadratic(1.0, 2.0, 1.0), &[-1.0, -1.0]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+        assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+        // No cancellation with very different roots.
+        let roots = solve_quadratic(1.0, -1e8, 1.0);
+        assert!((roots[0] - 1e-8).abs() < 1e-20);
+    }
+
+    #[test]
+    fn solving_cubics() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(2.0, -12.0, 22.0, -12.0), &[1.0, 2.0, 3.0]);
+        // (x + 1)(x^2 + 1)
+        assert_roots(solve_cubic(1.0, 1.0, 1.0, 1.0), &[-1.0]);
+        assert_roots(solve_cubic(1.0, 0.0, 0.0, -8.0), &[2.0]);
+        assert_roots(solve_cubic(0.0, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn solving_quartics() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // Biquadratic (x^2 - 1)(x^2 - 4)
+        assert_roots(
+            solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+        );
+        // (x^2 + 1)(x - 1)(x + 3): two real roots.
+        assert_roots(solve_quartic(1.0, 2.0, -2.0, 2.0, -3.0), &[-3.0, 1.0]);
+        assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+        // Widely spread roots: 0.001, 1, 10, 1000.
+        let (r1, r2, r3, r4) = (0.001, 1.0, 10.0, 1000.0);
+        assert_roots(
+            solve_quartic(
+                1.0,
+                -(r1 + r2 + r3 + r4),
+                r1 * r2 + r1 * r3 + r1 * r4 + r2 * r3 + r2 * r4 + r3 * r4,
+                -(r1 * r2 * r3 + r1 * r2 * r4 + r1 * r3 * r4 + r2 * r3 * r4),
+                r1 * r2 * r3 * r4,
+            ),
+            &[r1, r2, r3, r4],
+        );
+    }
+}
@@ -1,96 +1,353 @@
 //! World type
 
+use crate::background::Background;
 use crate::color::{self, Color};
+use crate::error::Error;
+use crate::integrator::Radiance;
 use crate::light::{self, PointLight, PointStatus};
+use crate::medium::{Fog, Medium};
+use crate::occlusion::AmbientOcclusion;
 use crate::ray::Ray;
+use crate::rng::Rng;
 use crate::shape::{Computations, Intersection, IntersectionList, Shape};
 use crate::tuple::Tuple;
+use crate::util;
+
+/// Points per medium segment where `World::color_at()` computes the light
+/// scattered toward the eye.
+const MEDIUM_STEPS: usize = 16;
 
 /// A scene to render.
 pub struct World {
     pub light: PointLight,
     pub objects: Vec<Shape>,
+
+    /// Seen by rays hitting no object.
+    pub background: Background,
+
+    /// Dims ambient lighting of occluded points when set.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+
+    /// Fills the whole world when set.
+    pub fog: Option<Fog>,
+}
+
+/// First surface hit by a ray.  See `World::cast_ray()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayHit {
+    /// Index of the shape hit in `World::objects`.
+    pub index: usize,
+    /// Distance along the ray, in units of its direction vector.
+    pub distance: f64,
+    pub point: Tuple,
+    /// Surface normal at `point`, facing the origin of the ray.
+    pub normal: Tuple,
+    /// Texture coordinates of `point`.  See `Shape::uv_at()`.
+    pub uv: (f64, f64),
+}
+
+/// Part of a ray inside a shape filled with a medium.
+pub(crate) struct MediumSegment<'a> {
+    /// Distances along the ray where the segment starts and ends.
+    pub start: f64,
+    pub end: f64,
+    pub shape: &'a Shape,
+    pub medium: &'a Medium,
 }
 
 impl World {
-    /// Computes color of intersection between an object of this world and `ray`.
+    /// Creates a world with a black background.
+    pub fn new(light: PointLight, objects: Vec<Shape>) -> World {
+        World {
+            light,
+            objects,
+            background: Background::default(),
+            ambient_occlusion: None,
+            fog: None,
+        }
+    }
+
+    /// Returns an error naming the first shape that cannot be rendered, if any.
+    /// See `Shape::validate()`.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (index, shape) in self.objects.iter().enumerate() {
+            shape.validate().map_err(|e| Error::InvalidShape {
+                index,
+                kind: shape.object.name(),
+                error: Box::new(e),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Computes color of the first intersection between an object of this world
+    /// and `ray` in front of its origin and in its interval, or color of the
+    /// background if there is no such intersection.
+    ///
+    /// Phong shading casts no secondary rays.  The bounces of
+    /// `Integrator::PathTracing` see the background too when they escape.
+    ///
+    /// Media scatter light from the light source only (single scattering).
+    ///
+    /// Ambient occlusion and media, if any, use the same random numbers for all
+    /// rays.  See `sampled_color_at()`.
     pub fn color_at(&self, ray: &Ray) -> Color {
+        self.sampled_color_at(ray, &mut Rng::new(0))
+    }
+
+    /// Same as `color_at()`, using `rng` for ambient occlusion and media.
+    pub fn sampled_color_at(&self, ray: &Ray, rng: &mut Rng) -> Color {
+        self.radiance_at(ray, rng).total()
+    }
+
+    /// Same as `sampled_color_at()`, split into direct and indirect lighting.
+    /// Ambient lighting is indirect.
+    pub fn radiance_at(&self, ray: &Ray, rng: &mut Rng) -> Radiance {
         let xs = self.intersects(ray);
-        if xs.is_empty() {
-            color::BLACK
-        } else {
-            self.shade_hit(&xs[0].prepare_computations(ray))
-        }
+        let (radiance, distance) = match xs.hit() {
+            None => (
+                Radiance {
+                    direct: self.background.color_at(ray.direction()),
+                    indirect: color::BLACK,
+                },
+                f64::INFINITY,
+            ),
+            Some(i) => (
+                self.shade_hit(&i.prepare_computations(ray), rng),
+                i.distance,
+            ),
+        };
+        self.through_media(ray, distance, &radiance, rng)
+    }
+
+    /// Returns emissive shapes that can be sampled as area lights, i.e. the
+    /// bounded ones.
+    pub fn emitters(&self) -> Vec<&Shape> {
+        self.objects
+            .iter()
+            .filter(|s| s.material.is_emissive() && s.object.is_bounded())
+            .collect()
+    }
+
+    /// Returns first surface hit by `ray`, if any.
+    ///
+    /// Shapes filled with a medium have no surface and are never hit.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<RayHit> {
+        self.cast_ray_with(ray, f64::INFINITY, |_, _| true)
+    }
+
+    /// Same as `cast_ray()`, ignoring hits farther than `max_distance` along
+    /// `ray` and shapes for which `filter`, given their index in `objects`,
+    /// returns false.
+    pub fn cast_ray_with<F>(&self, ray: &Ray, max_distance: f64, filter: F) -> Option<RayHit>
+    where
+        F: Fn(usize, &Shape) -> bool,
+    {
+        let (index, hit) = self
+            .objects
+            .iter()
+            .enumerate()
+            .filter(|(i, o)| o.material.medium.is_none() && filter(*i, o))
+            .flat_map(|(i, o)| o.intersections(ray).into_iter().map(move |x| (i, x)))
+            .filter(|(_, x)| x.distance >= 0.0 && x.distance <= max_distance)
+            .min_by(|(_, l), (_, r)| l.distance.total_cmp(&r.distance))?;
+        let comps = hit.prepare_computations(ray);
+        Some(RayHit {
+            index,
+            distance: hit.distance,
+            uv: hit.shape.uv_at(&comps.point, comps.time),
+            point: comps.point,
+            normal: comps.normal_vec,
+        })
     }
 
     /// Computes intersections between this world and `ray`.
-    fn intersects(&self, ray: &Ray) -> Vec<Intersection> {
+    ///
+    /// Shapes filled with a medium have no surface and are left out.  See
+    /// `medium_segments()`.
+    pub(crate) fn intersects(&self, ray: &Ray) -> Vec<Intersection<'_>> {
         let mut xs: Vec<Intersection> = self
             .objects
             .iter()
+            .filter(|o| o.material.medium.is_none())
             .flat_map(|o| o.intersections(ray))
             .collect();
-        xs.sort_unstable_by(|l, r| l.distance.partial_cmp(&r.distance).expect("NaN unexpected"));
+        xs.sort_unstable_by(|l, r| l.distance.total_cmp(&r.distance));
         xs
     }
 
+    /// Returns parts of `ray` from its origin up to `max_distance` inside shapes
+    /// filled with a medium, sorted by start.
+    ///
+    /// Intersections with each shape, along the whole line of `ray` whatever
+    /// its interval, are paired as entry and exit points, so only closed shapes
+    /// are supported.
+    pub(crate) fn medium_segments(&self, ray: &Ray, max_distance: f64) -> Vec<MediumSegment<'_>> {
+        let mut segments = Vec::new();
+        for shape in &self.objects {
+            let medium = match &shape.material.medium {
+                Some(medium) => medium,
+                None => continue,
+            };
+            let line = ray.clone().with_interval(f64::NEG_INFINITY, f64::INFINITY);
+            let mut xs = shape.intersections(&line);
+            xs.sort_unstable_by(|l, r| l.distance.total_cmp(&r.distance));
+            for pair in xs.chunks_exact(2) {
+                let start = pair[0].distance.max(0.0);
+                let end = pair[1].distance.min(max_distance);
+                if start < end {
+                    segments.push(MediumSegment {
+                        start,
+                        end,
+                        shape,
+                        medium,
+                    });
+                }
+            }
+        }
+        segments.sort_unstable_by(|l, r| l.start.total_cmp(&r.start));
+        segments
+    }
+
+    /// Returns `radiance` coming from `distance` along `ray` as seen from the
+    /// origin of `ray`, through media and fog.
+    ///
+    /// Overlapping media are treated as if they followed each other.
+    fn through_media(
+        &self,
+        ray: &Ray,
+        distance: f64,
+        radiance: &Radiance,
+        rng: &mut Rng,
+    ) -> Radiance {
+        let speed = ray.direction().magnitude();
+        let mut transmittance = 1.0;
+        let mut scattered = color::BLACK;
+        for segment in self.medium_segments(ray, distance) {
+            let m = segment.medium;
+            let step = (segment.end - segment.start) * speed / MEDIUM_STEPS as f64;
+            // Transmittance integrated over a step, from its start.
+            let integrated = if m.extinction() > 0.0 {
+                (1.0 - m.transmittance(step)) / m.extinction()
+            } else {
+                step
+            };
+            let tinted = &segment.shape.material.color * &self.light.intensity;
+            for i in 0..MEDIUM_STEPS {
+                let t = segment.start + (i as f64 + rng.next_f64()) * step / speed;
+                let pt = ray.position(t);
+                let to_light = self.transmittance(&pt, &self.light.position, ray.time());
+                // Isotropic scattering of irradiance pi * intensity, as in
+                // `Integrator::PathTracing`.
+                let weight = transmittance * m.transmittance(i as f64 * step) * integrated;
+                scattered = &scattered + &(&tinted * (m.scattering * weight * to_light / 4.0));
+            }
+            transmittance *= m.transmittance(step * MEDIUM_STEPS as f64);
+        }
+        let mut direct = &(&radiance.direct * transmittance) + &scattered;
+        if let Some(fog) = &self.fog {
+            let dir = ray.direction().normalized();
+            let t = fog.transmittance(ray.origin(), &dir, distance * speed);
+            direct = direct.interpolated(&fog.color, 1.0 - t);
+            transmittance *= t;
+        }
+        Radiance {
+            direct,
+            indirect: &radiance.indirect * transmittance,
+        }
+    }
+
     /// Computes color of intersection point described by `comps`.
-    fn shade_hit(&self, comps: &Computations) -> Color {
-        light::lighting(
-            &comps.object.material,
+    fn shade_hit(&self, comps: &Computations, rng: &mut Rng) -> Radiance {
+        let m = &comps.object.material;
+        let color = comps.object.color_at(&comps.over_point, comps.time);
+        let mut ambient = light::ambient_lighting(m, &color, &self.light);
+        if let Some(ao) = &self.ambient_occlusion {
+            let visibility =
+                ao.visibility(self, &comps.over_point, &comps.normal_vec, comps.time, rng);
+            ambient = &ambient * visibility;
+        }
+        let to_light = self.transmittance(&comps.over_point, &self.light.position, comps.time);
+        let status = if to_light > 0.0 {
+            PointStatus::InLight
+        } else {
+            PointStatus::InShadow
+        };
+        let direct = light::direct_lighting(
+            m,
+            &color,
             &self.light,
             &comps.over_point,
             &comps.eye_vec,
             &comps.normal_vec,
-            self.point_status(&comps.over_point),
-        )
+            status,
+        );
+        Radiance {
+            direct: &m.emission + &(&direct * to_light),
+            indirect: ambient,
+        }
     }
 
-    /// Returns whether point `pt` is in shadow.
-    fn point_status(&self, pt: &Tuple) -> PointStatus {
-        debug_assert!(pt.is_point());
-        let vec = &self.light.position - pt;
-        let ray = Ray::new(pt.clone(), vec.normalized());
-        let xs = self.intersects(&ray);
-        if let Some(i) = xs.hit() {
-            if i.distance < vec.magnitude() {
-                PointStatus::InShadow
-            } else {
-                PointStatus::InLight
-            }
-        } else {
-            PointStatus::InLight
+    /// Returns fraction of light going from point `from` to point `to` at
+    /// `time` not blocked by objects or media.  Objects at `to` do not block.
+    pub(crate) fn transmittance(&self, from: &Tuple, to: &Tuple, time: f64) -> f64 {
+        debug_assert!(from.is_point());
+        debug_assert!(to.is_point());
+        let vec = to - from;
+        let distance = vec.magnitude();
+        let ray = Ray::new(from.clone(), &vec * (1.0 / distance))
+            .with_time(time)
+            .with_interval(0.0, distance - util::EPSILON);
+        if !self.intersects(&ray).is_empty() {
+            return 0.0;
         }
+        self.medium_segments(&ray, distance)
+            .iter()
+            .map(|s| s.medium.transmittance(s.end - s.start))
+            .product()
     }
 }
 
 #[cfg(test)]
 pub(crate) mod test_util {
     use super::*;
+    use crate::color;
     use crate::light::Material;
+    use crate::matrix::Matrix;
     use crate::shape::Object;
     use crate::transform;
     use crate::tuple::Tuple;
 
     pub fn default_world() -> World {
-        World {
-            light: PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
-            objects: vec![
-                Shape {
-                    material: Material {
+        World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
+            vec![
+                Shape::with_transform_and_material(
+                    Object::Sphere,
+                    Matrix::new_4x4_identity(),
+                    Material {
                         color: Color::new(0.8, 1.0, 0.6),
                         diffuse: 0.7,
                         specular: 0.2,
                         ..Material::default()
                     },
-                    ..Shape::new(Object::Sphere)
-                },
-                Shape {
-                    transform: transform::scaling(0.5, 0.5, 0.5),
-                    ..Shape::new(Object::Sphere)
-                },
+                ),
+                Shape::with_transform(Object::Sphere, transform::scaling(0.5, 0.5, 0.5)),
             ],
-        }
+        )
+    }
+
+    /// Returns a world with a sphere of radius 1 at the origin filled with
+    /// `medium`, lit from 10 units above by a light of intensity `light`, in
+    /// front of a `background` of uniform color.
+    pub fn world_with_medium(medium: Medium, light: Color, background: Color) -> World {
+        let mut w = World::new(
+            PointLight::new(light, Tuple::new_point(0.0, 10.0, 0.0)),
+            vec![Shape::new(Object::Sphere)],
+        );
+        w.objects[0].material.medium = Some(medium);
+        w.background = Background::Color(background);
+        w
     }
 }
 
@@ -99,9 +356,27 @@ mod tests {
     use super::*;
 
     use super::test_util;
-    use crate::shape::Object;
+    use crate::color;
+    use crate::light::{Material, PointLight};
+    use crate::matrix::Matrix;
+    use crate::shape::{Motion, Object};
     use crate::transform;
-    use crate::tuple::Tuple;
+    use crate::tuple::{Tuple, ORIGIN};
+
+    #[test]
+    fn validating_world_names_invalid_shape() {
+        let mut w = test_util::default_world();
+        assert_eq!(w.validate(), Ok(()));
+        w.objects[1].set_transform(transform::scaling(0.0, 0.0, 0.0));
+        assert_eq!(
+            w.validate(),
+            Err(Error::InvalidShape {
+                index: 1,
+                kind: "sphere",
+                error: Box::new(Error::SingularMatrix)
+            })
+        );
+    }
 
     #[test]
     fn intersect_world_with_ray() {
@@ -133,6 +408,115 @@ mod tests {
         );
     }
 
+    #[test]
+    fn color_when_ray_starts_inside_object() {
+        let w = test_util::default_world();
+        // Inside the inner sphere, intersections behind the origin of the ray,
+        // with both spheres, are ignored.
+        let r = Ray::new(ORIGIN, Tuple::new_vector(0.0, 0.0, 1.0));
+        let xs = w.intersects(&r);
+        assert_eq!(xs[0].distance, -1.0);
+        let comps = xs.hit().unwrap().prepare_computations(&r);
+        assert!(std::ptr::eq(comps.object, &w.objects[1]));
+        assert!(comps.inside);
+        assert_eq!(
+            w.color_at(&r),
+            w.shade_hit(&comps, &mut Rng::new(0)).total()
+        );
+    }
+
+    #[test]
+    fn camera_inside_sphere_does_not_see_shapes_behind_it() {
+        let room = Material {
+            color: Color::new(1.0, 0.0, 0.0),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+        let room = Shape::with_transform_and_material(
+            Object::Sphere,
+            transform::scaling(2.0, 2.0, 2.0),
+            room,
+        );
+        let behind = Material {
+            color: Color::new(0.0, 0.0, 1.0),
+            ..Material::default()
+        };
+        let behind = Shape::with_transform_and_material(
+            Object::Sphere,
+            transform::translation(0.0, 0.0, -3.0),
+            behind,
+        );
+        let w = World::new(PointLight::new(color::WHITE, ORIGIN), vec![behind, room]);
+        let r = Ray::new(ORIGIN, Tuple::new_vector(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(&r), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn intersections_outside_ray_interval_are_ignored() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let distances = |r: &Ray| {
+            w.intersects(r)
+                .iter()
+                .map(|x| x.distance)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(distances(&r.clone().with_interval(4.2, 5.5)), [4.5, 5.5]);
+        // Beyond the interval the ray misses everything.
+        let r = r.with_interval(6.5, f64::INFINITY);
+        assert!(distances(&r).is_empty());
+        assert_eq!(w.color_at(&r), color::BLACK);
+    }
+
+    #[test]
+    fn casting_ray_returns_first_surface_hit() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(
+            w.cast_ray(&r),
+            Some(RayHit {
+                index: 0,
+                distance: 4.0,
+                point: Tuple::new_point(0.0, 0.0, -1.0),
+                normal: Tuple::new_vector(0.0, 0.0, -1.0),
+                uv: (0.0, 0.5),
+            })
+        );
+
+        // Behind the origin of the ray is not hit.
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, 5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(w.cast_ray(&r), None);
+    }
+
+    #[test]
+    fn casting_ray_with_max_distance_and_filter() {
+        let w = test_util::default_world();
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(w.cast_ray_with(&r, 3.9, |_, _| true), None);
+        assert_eq!(
+            w.cast_ray_with(&r, 4.0, |_, _| true).map(|h| h.index),
+            Some(0)
+        );
+        let hit = w.cast_ray_with(&r, f64::INFINITY, |i, _| i != 0).unwrap();
+        assert_eq!(hit.index, 1);
+        assert_eq!(hit.distance, 4.5);
+        assert_eq!(hit.normal, Tuple::new_vector(0.0, 0.0, -1.0));
+    }
+
     #[test]
     fn shading_intersection() {
         let w = test_util::default_world();
@@ -146,7 +530,8 @@ mod tests {
             shape,
         };
         assert_eq!(
-            w.shade_hit(&i.prepare_computations(&r)),
+            w.shade_hit(&i.prepare_computations(&r), &mut Rng::new(0))
+                .total(),
             Color::new(0.38066, 0.47583, 0.2855)
         )
     }
@@ -165,23 +550,21 @@ mod tests {
             shape,
         };
         assert_eq!(
-            w.shade_hit(&i.prepare_computations(&r)),
+            w.shade_hit(&i.prepare_computations(&r), &mut Rng::new(0))
+                .total(),
             Color::new(0.90498, 0.90498, 0.90498)
         )
     }
 
     #[test]
     fn shade_hit_given_intersection_in_shadow() {
-        let w = World {
-            light: PointLight::new(color::WHITE, Tuple::new_point(0.0, 0.0, -10.0)),
-            objects: vec![
+        let w = World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(0.0, 0.0, -10.0)),
+            vec![
                 Shape::new(Object::Sphere),
-                Shape {
-                    transform: transform::translation(0.0, 0.0, 10.0),
-                    ..Shape::new(Object::Sphere)
-                },
+                Shape::with_transform(Object::Sphere, transform::translation(0.0, 0.0, 10.0)),
             ],
-        };
+        );
 
         let r = Ray::new(
             Tuple::new_point(0.0, 0.0, 5.0),
@@ -194,7 +577,8 @@ mod tests {
         };
 
         assert_eq!(
-            w.shade_hit(&i.prepare_computations(&r)),
+            w.shade_hit(&i.prepare_computations(&r), &mut Rng::new(0))
+                .total(),
             Color::new(0.1, 0.1, 0.1)
         );
     }
@@ -209,6 +593,22 @@ mod tests {
         assert_eq!(w.color_at(&r), color::BLACK);
     }
 
+    #[test]
+    fn color_when_ray_misses_is_background() {
+        let mut w = test_util::default_world();
+        w.background = Background::Gradient {
+            top: color::WHITE,
+            bottom: color::BLACK,
+        };
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(w.color_at(&r), color::WHITE);
+        w.background = Background::Color(color::RED);
+        assert_eq!(w.color_at(&r), color::RED);
+    }
+
     #[test]
     fn color_when_ray_hits() {
         let w = test_util::default_world();
@@ -219,6 +619,41 @@ mod tests {
         assert_eq!(w.color_at(&r), Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn ambient_occlusion_dims_ambient_lighting() {
+        // Floor lit from below, so only ambient lighting remains, next to a
+        // sphere lying on it.
+        let mut w = World::new(
+            PointLight::new(color::WHITE, Tuple::new_point(0.0, -10.0, 0.0)),
+            vec![
+                Shape::new(Object::Plane),
+                Shape::with_transform(Object::Sphere, transform::translation(0.0, 1.0, 0.0)),
+            ],
+        );
+        let r = Ray::new(
+            Tuple::new_point(1.0, 5.0, -5.0),
+            Tuple::new_vector(0.0, -1.0, 1.0).normalized(),
+        );
+        assert_eq!(w.color_at(&r), Color::new(0.1, 0.1, 0.1));
+        w.ambient_occlusion = Some(AmbientOcclusion {
+            samples: 100,
+            radius: 2.0,
+        });
+        let c = w.sampled_color_at(&r, &mut Rng::new(0));
+        assert!(c.red() > 0.0 && c.red() < 0.09, "{:?}", c);
+    }
+
+    #[test]
+    fn color_of_emissive_object_includes_emission() {
+        let mut w = test_util::default_world();
+        w.objects[0].material.emission = Color::new(0.5, 0.0, 0.0);
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert_eq!(w.color_at(&r), Color::new(0.88066, 0.47583, 0.2855));
+    }
+
     #[test]
     fn color_with_intersection_behind_ray() {
         let mut w = test_util::default_world();
@@ -230,34 +665,138 @@ mod tests {
             Tuple::new_vector(0.0, 0.0, -1.0),
         );
         // Intersection is on inner object.
-        assert_eq!(w.color_at(&r), w.objects[0].material.color);
+        assert_eq!(w.color_at(&r), w.objects[1].material.color);
     }
 
     #[test]
     fn no_shadow_when_nothing_colinear_with_point_and_light() {
         let w = test_util::default_world();
         let p = Tuple::new_point(0.0, 10.0, 0.0);
-        assert_eq!(w.point_status(&p), PointStatus::InLight);
+        assert_eq!(w.transmittance(&p, &w.light.position, 0.0), 1.0);
     }
 
     #[test]
     fn shadow_when_object_between_point_and_light() {
         let w = test_util::default_world();
         let p = Tuple::new_point(10.0, -10.0, 10.0);
-        assert_eq!(w.point_status(&p), PointStatus::InShadow);
+        assert_eq!(w.transmittance(&p, &w.light.position, 0.0), 0.0);
     }
 
     #[test]
     fn no_shadow_when_object_behind_light() {
         let w = test_util::default_world();
         let p = Tuple::new_point(-20.0, 20.0, -20.0);
-        assert_eq!(w.point_status(&p), PointStatus::InLight);
+        assert_eq!(w.transmittance(&p, &w.light.position, 0.0), 1.0);
+    }
+
+    #[test]
+    fn shadow_of_moving_object_depends_on_time() {
+        let mut w = test_util::default_world();
+        for o in &mut w.objects {
+            o.motion = Some(Motion::linear(
+                Matrix::new_4x4_identity(),
+                transform::translation(0.0, 0.0, 20.0),
+            ));
+        }
+        let p = Tuple::new_point(10.0, -10.0, 10.0);
+        assert_eq!(w.transmittance(&p, &w.light.position, 0.0), 0.0);
+        assert_eq!(w.transmittance(&p, &w.light.position, 1.0), 1.0);
     }
 
     #[test]
     fn no_shadow_when_object_behind_point() {
         let w = test_util::default_world();
         let p = Tuple::new_point(-2.0, 2.0, -2.0);
-        assert_eq!(w.point_status(&p), PointStatus::InLight);
+        assert_eq!(w.transmittance(&p, &w.light.position, 0.0), 1.0);
+    }
+
+    fn nearly_equal(l: &Color, r: &Color) -> bool {
+        (l.red() - r.red()).abs() < 1e-6
+            && (l.green() - r.green()).abs() < 1e-6
+            && (l.blue() - r.blue()).abs() < 1e-6
+    }
+
+    #[test]
+    fn fog_blends_colors_with_distance() {
+        let mut w = test_util::default_world();
+        w.fog = Some(Fog {
+            color: color::WHITE,
+            density: 0.5,
+            height_falloff: 0.0,
+        });
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let t = (-2.0_f64).exp();
+        let expected = Color::new(0.38066, 0.47583, 0.2855).interpolated(&color::WHITE, 1.0 - t);
+        assert!(nearly_equal(&w.color_at(&r), &expected));
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 1.0, 0.0),
+        );
+        assert_eq!(w.color_at(&r), color::WHITE);
+    }
+
+    #[test]
+    fn media_have_no_surface() {
+        let w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.5,
+                scattering: 0.0,
+            },
+            color::WHITE,
+            color::WHITE,
+        );
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        assert!(w.intersects(&r).is_empty());
+        let segments = w.medium_segments(&r, f64::INFINITY);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].start, segments[0].end), (4.0, 6.0));
+        // Absorbed over 2 units.
+        assert!(nearly_equal(
+            &w.color_at(&r),
+            &Color::new(0.36788, 0.36788, 0.36788)
+        ));
+    }
+
+    #[test]
+    fn media_scatter_light_toward_the_eye() {
+        let mut w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.0,
+                scattering: 0.5,
+            },
+            color::WHITE,
+            color::WHITE,
+        );
+        w.background = Background::Color(color::BLACK);
+        let r = Ray::new(
+            Tuple::new_point(0.0, 0.0, -5.0),
+            Tuple::new_vector(0.0, 0.0, 1.0),
+        );
+        let c = w.color_at(&r);
+        assert!(c.red() > 0.05 && c.red() < 0.25, "{:?}", c);
+        // Light is tinted by the color of the medium.
+        w.objects[0].material.color = color::RED;
+        let c = w.color_at(&r);
+        assert!(c.red() > 0.05 && c.green() == 0.0);
+    }
+
+    #[test]
+    fn media_partially_block_light() {
+        let w = test_util::world_with_medium(
+            Medium {
+                absorption: 0.5,
+                scattering: 0.0,
+            },
+            color::WHITE,
+            color::WHITE,
+        );
+        let below = Tuple::new_point(0.0, -5.0, 0.0);
+        assert!((w.transmittance(&below, &w.light.position, 0.0) - (-1.0_f64).exp()).abs() < 1e-9);
     }
 }
@@ -0,0 +1,524 @@
+//! Minimal YAML reader.
+//!
+//! Supports the subset of YAML used by TRTC scene files: block sequences and
+//! mappings, flow sequences and mappings (`[1, 2, 3]`, `{ a: 1 }`), plain and
+//! quoted scalars and comments.  Anchors, tags, multi-line scalars and multiple
+//! documents are not supported.
+//!
+//! Every node remembers the line it starts on so that errors can point to the
+//! offending part of the source.
+
+use crate::scene::ParseError;
+
+/// A YAML value and the line (1-based) it starts on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Node {
+    pub line: usize,
+    pub value: Value,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Scalar(String),
+    Sequence(Vec<Node>),
+    /// Key-value pairs in source order.
+    Mapping(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn new(line: usize, value: Value) -> Node {
+        Node { line, value }
+    }
+
+    /// Returns an error located at this node.
+    pub fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    pub fn as_scalar(&self) -> Result<&str, ParseError> {
+        match &self.value {
+            Value::Scalar(s) => Ok(s),
+            _ => Err(self.error("expected a scalar")),
+        }
+    }
+
+    pub fn as_sequence(&self) -> Result<&[Node], ParseError> {
+        match &self.value {
+            Value::Sequence(items) => Ok(items),
+            _ => Err(self.error("expected a sequence")),
+        }
+    }
+
+    pub fn as_mapping(&self) -> Result<&[(String, Node)], ParseError> {
+        match &self.value {
+            Value::Mapping(entries) => Ok(entries),
+            _ => Err(self.error("expected a mapping")),
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64, ParseError> {
+        let s = self.as_scalar()?;
+        s.parse()
+            .map_err(|_| self.error(format!("expected a number, found '{}'", s)))
+    }
+
+    pub fn as_usize(&self) -> Result<usize, ParseError> {
+        let s = self.as_scalar()?;
+        s.parse()
+            .map_err(|_| self.error(format!("expected a positive integer, found '{}'", s)))
+    }
+
+    /// Returns value associated with `key` if this node is a mapping containing it.
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Mapping(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// A non-blank source line stripped from its comment.
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    content: &'a str,
+}
+
+/// Parses `src` and returns its root node.
+pub fn parse(src: &str) -> Result<Node, ParseError> {
+    let mut lines = Vec::new();
+    for (i, raw) in src.lines().enumerate() {
+        let content = strip_comment(raw).trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed == "---" {
+            continue;
+        }
+        if content.starts_with('\t') {
+            return Err(ParseError {
+                line: i + 1,
+                message: "tabs are not allowed for indentation".to_string(),
+            });
+        }
+        lines.push(Line {
+            number: i + 1,
+            indent: content.len() - trimmed.len(),
+            content: trimmed,
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Node::new(1, Value::Mapping(Vec::new())));
+    }
+
+    let mut pos = 0;
+    let indent = lines[0].indent;
+    let root = parse_block(&mut lines, &mut pos, indent)?;
+    if pos < lines.len() {
+        let message = if lines[pos].indent == indent {
+            "unexpected content"
+        } else {
+            "unexpected indentation"
+        };
+        return Err(ParseError {
+            line: lines[pos].number,
+            message: message.to_string(),
+        });
+    }
+    Ok(root)
+}
+
+/// Returns `s` without trailing comment if any.
+fn strip_comment(s: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return &s[..i],
+            None => {}
+        }
+        prev = c;
+    }
+    s
+}
+
+/// Parses the block node starting at `lines[*pos]` and indented by `indent` columns.
+fn parse_block(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Node, ParseError> {
+    let first = &lines[*pos];
+    if is_sequence_item(first.content) {
+        parse_block_sequence(lines, pos, indent)
+    } else if split_key(first.content).is_some() {
+        parse_block_mapping(lines, pos, indent)
+    } else {
+        let node = parse_flow(first.content, first.number)?;
+        *pos += 1;
+        Ok(node)
+    }
+}
+
+fn parse_block_sequence(
+    lines: &mut [Line],
+    pos: &mut usize,
+    indent: usize,
+) -> Result<Node, ParseError> {
+    let line = lines[*pos].number;
+    let mut items = Vec::new();
+    while *pos < lines.len()
+        && lines[*pos].indent == indent
+        && is_sequence_item(lines[*pos].content)
+    {
+        let content = lines[*pos].content;
+        let rest = content[1..].trim_start();
+        if rest.is_empty() {
+            *pos += 1;
+            items.push(parse_child(
+                lines,
+                pos,
+                indent,
+                false,
+                lines[*pos - 1].number,
+            )?);
+        } else {
+            // Reinterpret the item content as a line of its own, indented
+            // past the dash, so that following lines can continue it.
+            let item = &mut lines[*pos];
+            item.indent += content.len() - rest.len();
+            item.content = rest;
+            let item_indent = item.indent;
+            items.push(parse_block(lines, pos, item_indent)?);
+        }
+    }
+    Ok(Node::new(line, Value::Sequence(items)))
+}
+
+fn parse_block_mapping(
+    lines: &mut [Line],
+    pos: &mut usize,
+    indent: usize,
+) -> Result<Node, ParseError> {
+    let line = lines[*pos].number;
+    let mut entries: Vec<(String, Node)> = Vec::new();
+    while *pos < lines.len() && lines[*pos].indent == indent {
+        let number = lines[*pos].number;
+        let (key, value) = split_key(lines[*pos].content).ok_or_else(|| ParseError {
+            line: number,
+            message: "expected a 'key: value' pair".to_string(),
+        })?;
+        if entries.iter().any(|(k, _)| *k == key) {
+            return Err(ParseError {
+                line: number,
+                message: format!("duplicate key '{}'", key),
+            });
+        }
+        *pos += 1;
+        let node = if value.is_empty() {
+            parse_child(lines, pos, indent, true, number)?
+        } else {
+            parse_flow(value, number)?
+        };
+        entries.push((key, node));
+    }
+    Ok(Node::new(line, Value::Mapping(entries)))
+}
+
+/// Parses the block nested under a sequence item or mapping key with no inline value.
+///
+/// A sequence nested in a mapping may be indented at the same level as its key.
+fn parse_child(
+    lines: &mut [Line],
+    pos: &mut usize,
+    parent_indent: usize,
+    allow_same_indent_sequence: bool,
+    parent_line: usize,
+) -> Result<Node, ParseError> {
+    if *pos < lines.len() {
+        let next = &lines[*pos];
+        if next.indent > parent_indent
+            || (allow_same_indent_sequence
+                && next.indent == parent_indent
+                && is_sequence_item(next.content))
+        {
+            let indent = next.indent;
+            return parse_block(lines, pos, indent);
+        }
+    }
+    Ok(Node::new(parent_line, Value::Scalar(String::new())))
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Splits `content` into key and value if it looks like a mapping entry.
+fn split_key(content: &str) -> Option<(String, &str)> {
+    if content.starts_with('[') || content.starts_with('{') {
+        return None;
+    }
+    let idx = if let Some(stripped) = content.strip_suffix(':') {
+        if stripped.contains(": ") {
+            content.find(": ")?
+        } else {
+            stripped.len()
+        }
+    } else {
+        content.find(": ")?
+    };
+    let key = unquote(content[..idx].trim());
+    Some((key, content[idx + 1..].trim()))
+}
+
+fn unquote(s: &str) -> String {
+    let quoted = s.len() >= 2
+        && ((s.starts_with('"') && s.ends_with('"')) || (s.starts_with('\'') && s.ends_with('\'')));
+    if quoted {
+        s[1..s.len() - 1].to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Parses an inline value: flow collection or scalar.
+fn parse_flow(s: &str, line: usize) -> Result<Node, ParseError> {
+    let mut parser = FlowParser {
+        chars: s.chars().collect(),
+        pos: 0,
+        line,
+    };
+    let node = parser.value()?;
+    parser.skip_spaces();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected characters after value"));
+    }
+    Ok(node)
+}
+
+struct FlowParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl FlowParser {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, ParseError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('[') => self.sequence(),
+            Some('{') => self.mapping(),
+            _ => Ok(Node::new(self.line, Value::Scalar(self.scalar()))),
+        }
+    }
+
+    fn sequence(&mut self) -> Result<Node, ParseError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Node::new(self.line, Value::Sequence(items)));
+                }
+                None => return Err(self.error("unterminated sequence")),
+                _ => {}
+            }
+            items.push(self.value()?);
+            self.separator(']')?;
+        }
+    }
+
+    fn mapping(&mut self) -> Result<Node, ParseError> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Node::new(self.line, Value::Mapping(entries)));
+                }
+                None => return Err(self.error("unterminated mapping")),
+                _ => {}
+            }
+            let key = self.scalar();
+            self.skip_spaces();
+            if self.peek() != Some(':') {
+                return Err(self.error("expected ':' after mapping key"));
+            }
+            self.pos += 1;
+            entries.push((key, self.value()?));
+            self.separator('}')?;
+        }
+    }
+
+    /// Consumes the separator following a flow collection item.
+    fn separator(&mut self, close: char) -> Result<(), ParseError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) if c == close => Ok(()),
+            _ => Err(self.error(&format!("expected ',' or '{}'", close))),
+        }
+    }
+
+    fn scalar(&mut self) -> String {
+        self.skip_spaces();
+        if let Some(q) = self.peek().filter(|&c| c == '"' || c == '\'') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c != q) {
+                self.pos += 1;
+            }
+            let s = self.chars[start..self.pos].iter().collect();
+            if self.peek().is_some() {
+                self.pos += 1;
+            }
+            s
+        } else {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == ',' || c == ']' || c == '}' || c == ':' && self.is_flow_key_end() {
+                    break;
+                }
+                self.pos += 1;
+            }
+            self.chars[start..self.pos]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string()
+        }
+    }
+
+    /// Returns true if the ':' at the current position ends a flow mapping key.
+    fn is_flow_key_end(&self) -> bool {
+        self.chars
+            .get(self.pos + 1)
+            .is_none_or(|c| c.is_whitespace())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(line: usize, s: &str) -> Node {
+        Node::new(line, Value::Scalar(s.to_string()))
+    }
+
+    #[test]
+    fn parsing_scalar() {
+        assert_eq!(parse("hello").unwrap(), scalar(1, "hello"));
+    }
+
+    #[test]
+    fn parsing_flow_sequence() {
+        assert_eq!(
+            parse("[1, 2, [3, 'a b']]").unwrap(),
+            Node::new(
+                1,
+                Value::Sequence(vec![
+                    scalar(1, "1"),
+                    scalar(1, "2"),
+                    Node::new(1, Value::Sequence(vec![scalar(1, "3"), scalar(1, "a b")]))
+                ])
+            )
+        );
+    }
+
+    #[test]
+    fn parsing_block_mapping_with_comments() {
+        let src = "# A comment\na: 1\n\nb: [x, y] # trailing\n";
+        assert_eq!(
+            parse(src).unwrap(),
+            Node::new(
+                2,
+                Value::Mapping(vec![
+                    ("a".to_string(), scalar(2, "1")),
+                    (
+                        "b".to_string(),
+                        Node::new(4, Value::Sequence(vec![scalar(4, "x"), scalar(4, "y")]))
+                    ),
+                ])
+            )
+        );
+    }
+
+    #[test]
+    fn parsing_sequence_of_mappings() {
+        let src = "\
+- add: sphere
+  transform:
+    - [scale, 1, 2, 3]
+- add: light
+  at: [1, 2, 3]
+";
+        let root = parse(src).unwrap();
+        let items = root.as_sequence().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].line, 1);
+        assert_eq!(items[0].get("add").unwrap().as_scalar().unwrap(), "sphere");
+        let transform = items[0].get("transform").unwrap().as_sequence().unwrap();
+        assert_eq!(transform[0].line, 3);
+        assert_eq!(transform[0].as_sequence().unwrap().len(), 4);
+        assert_eq!(items[1].line, 4);
+        assert_eq!(items[1].get("at").unwrap().line, 5);
+    }
+
+    #[test]
+    fn parsing_sequence_indented_at_key_level() {
+        let src = "value:\n- a\n- b\nother: c\n";
+        let root = parse(src).unwrap();
+        assert_eq!(root.get("value").unwrap().as_sequence().unwrap().len(), 2);
+        assert_eq!(root.get("other").unwrap().as_scalar().unwrap(), "c");
+    }
+
+    #[test]
+    fn parsing_flow_mapping() {
+        let root = parse("{ a: 1, b: [2] }").unwrap();
+        assert_eq!(root.get("a").unwrap().as_f64().unwrap(), 1.0);
+        assert_eq!(root.get("b").unwrap().as_sequence().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reporting_line_of_bad_indentation() {
+        let err = parse("a:\n  b: 1\n c: 2\n").unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn reporting_unterminated_flow_sequence() {
+        let err = parse("a: 1\nb: [1, 2\n").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn reporting_duplicate_key() {
+        let err = parse("a: 1\na: 2\n").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
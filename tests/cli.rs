@@ -0,0 +1,157 @@
+//! Command-line renderer exit codes and output.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn rustytracer(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rustytracer"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Returns an empty directory for the outputs of `test`, unique to this run.
+fn output_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustytracer_cli_{}_{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns `name` in `dir` as a command-line argument.
+fn arg(dir: &Path, name: &str) -> String {
+    dir.join(name).to_str().unwrap().to_string()
+}
+
+#[test]
+fn rendering_scene_file() {
+    let dir = output_dir("rendering_scene_file");
+    let output = arg(&dir, "spheres_on_plane.ppm");
+    let out = rustytracer(&[
+        "--width",
+        "50",
+        "--samples",
+        "2",
+        "--threads",
+        "2",
+        "--format",
+        "ppm-raw",
+        "scenes/spheres_on_plane.yml",
+        &output,
+    ]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("rendered 50x25 (2 samples/pixel, 2 threads)"));
+
+    let ppm = fs::read(&output).unwrap();
+    assert!(ppm.starts_with(b"P6\n50 25\n255\n"));
+}
+
+#[test]
+fn rendering_with_path_tracing() {
+    let dir = output_dir("rendering_with_path_tracing");
+    let output = arg(&dir, "path_tracing.ppm");
+    let out = rustytracer(&[
+        "-W",
+        "20",
+        "-s",
+        "2",
+        "-i",
+        "path",
+        "scenes/spheres_on_plane.yml",
+        &output,
+    ]);
+    assert!(out.status.success());
+    let ppm = fs::read(&output).unwrap();
+    assert!(ppm.starts_with(b"P3\n20 10\n255\n"));
+}
+
+#[test]
+fn rendering_ambient_occlusion() {
+    let dir = output_dir("rendering_ambient_occlusion");
+    let output = arg(&dir, "ambient_occlusion.ppm");
+    let out = rustytracer(&[
+        "-W",
+        "20",
+        "-i",
+        "ao",
+        "scenes/spheres_on_plane.yml",
+        &output,
+    ]);
+    assert!(out.status.success());
+    let ppm = fs::read_to_string(&output).unwrap();
+    assert!(ppm.starts_with("P3\n20 10\n255\n"));
+    // Rays missing the scene are white.
+    assert!(ppm["P3\n20 10\n255\n".len()..].starts_with("255 255 255 "));
+}
+
+#[test]
+fn rendering_output_variables() {
+    let dir = output_dir("rendering_output_variables");
+    let (beauty, depth, object_id) = (
+        arg(&dir, "beauty.pfm"),
+        arg(&dir, "depth.pfm"),
+        arg(&dir, "object_id.pfm"),
+    );
+    let out = rustytracer(&[
+        "-W",
+        "20",
+        "-f",
+        "pfm",
+        "--aov",
+        &format!("depth={}", depth),
+        "-a",
+        &format!("object-id={}", object_id),
+        "scenes/spheres_on_plane.yml",
+        &beauty,
+    ]);
+    assert!(out.status.success());
+    for path in &[&beauty, &depth, &object_id] {
+        let pfm = fs::read(path).unwrap();
+        assert!(pfm.starts_with(b"PF\n20 10\n-1.0\n"));
+        assert_eq!(pfm.len(), b"PF\n20 10\n-1.0\n".len() + 20 * 10 * 3 * 4);
+    }
+
+    let normals = format!("normals={}", arg(&dir, "normals.pfm"));
+    let output = arg(&dir, "invalid.pfm");
+    for aov in &[normals.as_str(), "depth", "depth="] {
+        let out = rustytracer(&["-a", aov, "scenes/spheres_on_plane.yml", &output]);
+        assert_eq!(out.status.code(), Some(1));
+    }
+}
+
+#[test]
+fn invalid_command_line() {
+    assert_eq!(rustytracer(&["only-one-arg"]).status.code(), Some(1));
+    assert_eq!(
+        rustytracer(&["--samples", "0", "a.yml", "b.ppm"])
+            .status
+            .code(),
+        Some(1)
+    );
+    assert_eq!(
+        rustytracer(&["-i", "radiosity", "a.yml", "b.ppm"])
+            .status
+            .code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn invalid_scene() {
+    let dir = output_dir("invalid_scene");
+    let path = arg(&dir, "invalid_scene.yml");
+    fs::write(&path, "- add: camera\n  width: wide\n").unwrap();
+    let out = rustytracer(&[&path, &arg(&dir, "invalid_scene.ppm")]);
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("line 2"));
+}
+
+#[test]
+fn missing_scene_file() {
+    let dir = output_dir("missing_scene_file");
+    let out = rustytracer(&["/nonexistent/scene.yml", &arg(&dir, "missing.ppm")]);
+    assert_eq!(out.status.code(), Some(4));
+}
@@ -72,10 +72,10 @@ fn first_world() {
         },
     );
 
-    let world = World {
-        objects: vec![floor, left_wall, right_wall, middle, right, left],
-        light: PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
-    };
+    let world = World::new(
+        PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
+        vec![floor, left_wall, right_wall, middle, right, left],
+    );
 
     let camera = Camera::with_transform(
         500,
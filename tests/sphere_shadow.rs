@@ -29,7 +29,7 @@ fn render(sphere: &Shape, filename: &str) {
             let pos = Tuple::new_point(world_x, world_y, WALL_Z);
             let ray = Ray::new(ray_origin.clone(), (&pos - &ray_origin).normalized());
             let xs = sphere.intersections(&ray);
-            if xs.hit() != None {
+            if xs.hit().is_some() {
                 canvas.set(x, y, &color::RED);
             }
         }
@@ -56,10 +56,10 @@ fn spheres_on_plane() {
         },
     );
 
-    let world = World {
-        objects: vec![floor, middle, right, left],
-        light: PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
-    };
+    let world = World::new(
+        PointLight::new(color::WHITE, Tuple::new_point(-10.0, 10.0, -10.0)),
+        vec![floor, middle, right, left],
+    );
 
     let camera = Camera::with_transform(
         500,